@@ -0,0 +1,2033 @@
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+
+use crate::analysis::types::{self, Bound, Type};
+use crate::error::diagnostic::{Error, ErrorForm, Errors};
+use crate::interpreter::builtins;
+use crate::message;
+use crate::parser::ast::*;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::parser::token::TokenType;
+use crate::token;
+
+// Works out the type of every expression before the program runs, so that
+// `1 + "str"` is reported without running anything. Types are inferred in
+// the Hindley-Milner style: every unknown type starts out as a variable, and
+// each use of a value narrows down what its variable can be. Annotations,
+// like `let x: int = 1` or `fn f(a: str) -> int`, pin types down directly.
+//
+// Annotations are optional, and whatever isn't annotated, like the parameters
+// and result of `fn f(a) => a` or the variable in `let x = 1`, is `dynamic`:
+// it goes with anything, the way it did before there were types. Where a
+// `dynamic` value reaches something annotated, the value is wrapped in a cast
+// that checks its type when the program runs.
+//
+// Operators, attributes and indexes mean different things for different
+// types, so they can't be decided while the types they depend on are still
+// unknown. They wait as constraints until they can be, and a constraint still
+// waiting when a function is made generic is checked again wherever the
+// function is used.
+//
+// Functions, structs and enums can also be declared generic, like
+// `fn first<T>(xs: [T]) -> T`. Inside them a type parameter is a type of its
+// own, which only goes with itself and allows what its bounds do, like
+// arithmetic for `T: number`. Each use of one replaces its type parameters
+// with fresh variables, and checks the bounds on whatever they turn out to be.
+//
+// Files share one checker, so declarations in an earlier file are visible in
+// later ones, like with the resolver.
+pub struct Checker {
+    // What each type variable has turned out to be, if anything yet.
+    bindings: Vec<Option<Type>>,
+    // The variables the unification in progress has bound, undone if it fails.
+    trail: Vec<usize>,
+    scopes: Vec<HashMap<String, Entry>>,
+    structs: HashMap<String, StructType>,
+    enums: HashMap<String, EnumType>,
+    pending: Vec<Constraint>,
+    // Every type parameter declared so far, by number.
+    type_parameters: Vec<TypeParameterType>,
+    // The type parameters in scope by name, innermost last.
+    generics: Vec<HashMap<String, usize>>,
+    // The result type of every function being checked, innermost last, and
+    // where it was declared.
+    results: Vec<(Type, Position)>,
+    // The signatures of the functions and methods of the blocks being
+    // checked, by where each function or struct starts.
+    signatures: HashMap<(usize, usize), Vec<Signature>>,
+    pub exact_division: bool,
+    pub error: Errors
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    scheme: Scheme,
+    // What decided the variable's type, like its annotation or first value.
+    origin: Position,
+    // Where the types of its parameters are declared, if it's a function
+    // declared with `fn`.
+    signature: Vec<Position>
+}
+
+// A type that may be generic in some of its variables, which are replaced by
+// fresh ones each time it's used, along with the constraints on them.
+#[derive(Debug, Clone)]
+struct Scheme {
+    variables: Vec<usize>,
+    constraints: Vec<Constraint>,
+    ty: Type
+}
+
+impl Scheme {
+    fn simple(ty: Type) -> Scheme {
+        Scheme { variables: vec![], constraints: vec![], ty }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    name: String,
+    ty: Type,
+    position: Position
+}
+
+#[derive(Debug, Clone)]
+struct Method {
+    scheme: Scheme,
+    // Whether the first parameter is `self`, so the method is called on a record.
+    receiver: bool,
+    position: Position
+}
+
+#[derive(Debug, Clone)]
+struct TypeParameterType {
+    name: Identifier,
+    bounds: Vec<Bound>
+}
+
+// A function's type parameters, and the types of its parameters and result
+// with where each is declared.
+#[derive(Debug)]
+struct Signature {
+    generic: Vec<usize>,
+    parameters: Vec<(Type, Position)>,
+    result: (Type, Position)
+}
+
+#[derive(Debug)]
+struct StructType {
+    parameters: Vec<usize>,
+    fields: Vec<Member>,
+    methods: HashMap<String, Method>
+}
+
+#[derive(Debug)]
+struct EnumType {
+    parameters: Vec<usize>,
+    variants: Vec<(Identifier, Vec<Member>)>
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    ty: Type,
+    position: Position
+}
+
+#[derive(Debug, Clone)]
+struct Constraint {
+    requirement: Requirement,
+    // Where the generic function this was copied from was used, if it was.
+    used: Option<Position>
+}
+
+#[derive(Debug, Clone)]
+enum Requirement {
+    // `left operator right` gives a `result`.
+    Binary { operator: TokenType, left: Operand, right: Operand, result: Type },
+    Unary { operator: TokenType, operand: Operand, result: Type },
+    // `<`, `<=`, `>` and `>=` always give a `bool`, but not every two values
+    // can be ordered.
+    Ordering { operator: TokenType, left: Operand, right: Operand },
+    // `object.name` holds a `value`, which is being stored there if `store`.
+    Attribute { object: Operand, name: Identifier, value: Operand, store: bool },
+    // `object[index]`, where `literal` is the index if it's written out.
+    Index { object: Operand, index: Operand, literal: Option<i64>, value: Operand, store: bool, position: Position },
+    // `ty` is what `parameter` stands for in an `instance` of something
+    // generic, which has to satisfy the parameter's `bound`.
+    Bound { ty: Type, bound: Bound, parameter: usize, instance: Type, position: Position }
+}
+
+impl Constraint {
+    fn new(requirement: Requirement) -> Constraint {
+        Constraint { requirement, used: None }
+    }
+
+    fn map(&self, f: &mut impl FnMut(&Type) -> Type) -> Constraint {
+        let mut operand = |operand: &Operand| Operand { ty: f(&operand.ty), position: operand.position };
+        let requirement = match &self.requirement {
+            Requirement::Binary { operator, left, right, result } => {
+                Requirement::Binary { operator: *operator, left: operand(left), right: operand(right), result: f(result) }
+            },
+            Requirement::Unary { operator, operand: o, result } => {
+                Requirement::Unary { operator: *operator, operand: operand(o), result: f(result) }
+            },
+            Requirement::Ordering { operator, left, right } => {
+                Requirement::Ordering { operator: *operator, left: operand(left), right: operand(right) }
+            },
+            Requirement::Attribute { object, name, value, store } => {
+                Requirement::Attribute { object: operand(object), name: name.clone(), value: operand(value), store: *store }
+            },
+            Requirement::Index { object, index, literal, value, store, position } => Requirement::Index {
+                object: operand(object),
+                index: operand(index),
+                literal: *literal,
+                value: operand(value),
+                store: *store,
+                position: *position
+            },
+            Requirement::Bound { ty, bound, parameter, instance, position } => Requirement::Bound {
+                ty: f(ty),
+                bound: *bound,
+                parameter: *parameter,
+                instance: f(instance),
+                position: *position
+            }
+        };
+        Constraint { requirement, used: self.used }
+    }
+
+    fn variables(&self) -> Vec<usize> {
+        let mut variables = Vec::new();
+        self.map(&mut |ty: &Type| {
+            variables.extend(ty.variables());
+            ty.clone()
+        });
+        variables
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Checker {
+        Checker::new()
+    }
+}
+
+impl Checker {
+    pub fn new() -> Checker {
+        Checker {
+            bindings: vec![],
+            trail: vec![],
+            scopes: vec![HashMap::new()],
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            pending: vec![],
+            type_parameters: vec![],
+            generics: vec![],
+            results: vec![],
+            signatures: HashMap::new(),
+            exact_division: false,
+            error: Errors::new()
+        }
+    }
+
+    pub fn check(&mut self, code: &mut [AST]) {
+        self.hoist(code);
+        for ast in code {
+            self.ast(ast);
+            self.solve();
+        }
+    }
+
+    // The type of a global variable, written out.
+    pub fn type_of(&self, name: &str) -> Option<String> {
+        let entry = self.scopes.first()?.get(name)?;
+        Some(self.zonk(&entry.scheme.ty).to_string())
+    }
+
+    fn ast(&mut self, ast: &mut AST) -> Type {
+        match ast {
+            AST::Statement(statement) => self.statement(statement),
+            AST::Expression(expression) => self.expression(expression)
+        }
+    }
+
+    // The type of what a block gives, which is its last line, and where
+    // that line is.
+    fn block(&mut self, code: &mut [AST], position: Position) -> (Type, Position) {
+        self.hoist(code);
+        let mut last = (Type::Nil, position);
+        for ast in code {
+            last = (self.ast(ast), ast.position());
+        }
+        last
+    }
+
+    fn statement(&mut self, statement: &mut Statement) -> Type {
+        match statement {
+            Statement::Declaration(declaration) => self.declaration(declaration),
+            Statement::Assignment(assignment) => {
+                let value = self.expression(&mut assignment.value);
+                let at = assignment.value.position();
+                self.assign(&mut assignment.target, value, at, Some(&mut assignment.value));
+            },
+            Statement::Function(function) => self.named_function(function),
+            Statement::Struct(structure) => self.structure(structure),
+            Statement::Enum(enumeration) => {
+                let name = enumeration.name.name.clone();
+                self.declare(&enumeration.name, Scheme::simple(Type::Enum(name)), enumeration.name.position);
+            },
+            Statement::Return(r) => {
+                match &mut r.value {
+                    Some(value) => {
+                        let ty = self.expression(value);
+                        self.returns(ty, value.position(), Some(value));
+                    },
+                    None => self.returns(Type::Nil, r.position, None)
+                }
+                // Nothing comes after a return, so it fits wherever it is.
+                return self.fresh();
+            },
+            Statement::Body(body) => {
+                self.scopes.push(HashMap::new());
+                let (value, _) = self.block(&mut body.code, body.position);
+                self.scopes.pop();
+                return value;
+            }
+        }
+        Type::Nil
+    }
+
+    // Variables declared with `let` can be given anything later, so without
+    // an annotation they're `dynamic`. Constants keep the type of their value.
+    fn declaration(&mut self, declaration: &mut Declaration) {
+        let value = self.expression(&mut declaration.value);
+        let (ty, origin, typed) = match &declaration.annotation {
+            Some(annotation) => {
+                let expected = self.annotation(annotation);
+                self.coerce(&mut declaration.value, &value, &expected, annotation.position(), "E0006-mismatch.annotation");
+                (expected, annotation.position(), true)
+            },
+            None => {
+                let typed = declaration.kind == DeclarationKind::Const;
+                if !typed {
+                    self.expose(&mut declaration.value, &value);
+                }
+                (value, declaration.value.position(), typed)
+            }
+        };
+
+        match (&declaration.target, &*declaration.value) {
+            (Target::Identifier(name), value) if typed && syntactic_value(value) => {
+                let scheme = self.generalise(&ty);
+                self.declare(name, scheme, origin);
+            },
+            (target, _) => self.bind(target, ty, origin, typed)
+        }
+    }
+
+    // Declares the names in `target` with the parts of `ty` they unpack, or
+    // as `dynamic` if they aren't `typed`.
+    fn bind(&mut self, target: &Target, ty: Type, at: Position, typed: bool) {
+        match target {
+            Target::Identifier(name) | Target::Rest(Rest { name, .. }) => {
+                let ty = if typed { ty } else { Type::Dynamic };
+                self.declare(name, Scheme::simple(ty), at);
+            },
+            Target::Tuple(sequence) | Target::List(sequence) => {
+                let list = matches!(target, Target::List(_));
+                for (target, ty) in sequence.targets.iter().zip(self.unpack(sequence, list, ty, at)) {
+                    self.bind(target, ty, at, typed);
+                }
+            },
+            // The parser only allows names in declarations.
+            Target::Attribute(_) | Target::Index(_) => {}
+        }
+    }
+
+    // Checks a `ty` can be assigned to `target`. `value` is the expression
+    // being assigned, unless it's only part of one being unpacked.
+    fn assign(&mut self, target: &mut Target, ty: Type, at: Position, value: Option<&mut Expression>) {
+        let list = matches!(target, Target::List(_));
+        match target {
+            Target::Identifier(name) | Target::Rest(Rest { name, .. }) => {
+                if let Some(entry) = self.lookup(&name.name) {
+                    let expected = self.instantiate(&entry.scheme, name.position);
+                    self.fits(value, &ty, at, &expected, entry.origin, "E0006-mismatch.variable");
+                }
+            },
+            Target::Tuple(sequence) | Target::List(sequence) => {
+                let types = self.unpack(sequence, list, ty, at);
+                for (target, ty) in sequence.targets.iter_mut().zip(types) {
+                    self.assign(target, ty, at, None);
+                }
+            },
+            Target::Attribute(a) => {
+                let object = Operand { ty: self.expression(&mut a.object), position: a.object.position() };
+                if let Type::Record(structure, arguments) = self.resolve(&object.ty) {
+                    let field = self.structs.get(&structure).and_then(|s| {
+                        let field = s.fields.iter().find(|f| f.name == a.name.name)?;
+                        Some((instantiate(&field.ty, &s.parameters, &arguments), field.position))
+                    });
+                    if let Some((expected, declared)) = field {
+                        self.fits(value, &ty, at, &expected, declared, "E0006-mismatch.field");
+                        return;
+                    }
+                }
+                let value = Operand { ty, position: at };
+                self.constrain(Requirement::Attribute { object, name: a.name.clone(), value, store: true });
+            },
+            Target::Index(i) => {
+                let object = Operand { ty: self.expression(&mut i.object), position: i.object.position() };
+                let index = Operand { ty: self.expression(&mut i.index), position: i.index.position() };
+                let ty = match (self.resolve(&object.ty), value) {
+                    (Type::List(element), Some(value)) | (Type::Map(_, element), Some(value)) => {
+                        self.coerce(value, &ty, &element, object.position, "E0006-mismatch.element");
+                        *element
+                    },
+                    _ => ty
+                };
+                let value = Operand { ty, position: at };
+                let literal = literal_index(&i.index);
+                self.constrain(Requirement::Index { object, index, literal, value, store: true, position: i.position });
+            }
+        }
+    }
+
+    // The type each of `sequence`'s targets gets when unpacking a `ty`. A
+    // rest collects a tuple from a tuple and a list from a list.
+    fn unpack(&mut self, sequence: &TargetSequence, list: bool, ty: Type, at: Position) -> Vec<Type> {
+        let targets = &sequence.targets;
+        let rest = targets.iter().position(|target| matches!(target, Target::Rest(_)));
+        let required = targets.len() - rest.map_or(0, |_| 1);
+
+        let element = match self.resolve(&ty) {
+            Type::Tuple(mut elements) => {
+                let fits = if rest.is_some() { elements.len() >= required } else { elements.len() == required };
+                if !fits {
+                    let key = if rest.is_some() { "E0006-unpack-count.at-least" } else { "E0006-unpack-count" };
+                    self.report(
+                        message!(key, expected = required, found = elements.len(), type = self.zonk(&ty)),
+                        vec![primary(at).with_message(message!("E0006-type.label", type = self.zonk(&ty))), secondary(sequence.position)],
+                        None
+                    );
+                    return targets.iter().map(|_| self.fresh()).collect();
+                }
+                return match rest {
+                    None => elements,
+                    Some(rest) => {
+                        let suffix = elements.split_off(elements.len() - (targets.len() - rest - 1));
+                        let collected = elements.split_off(rest);
+                        elements.push(Type::Tuple(collected));
+                        elements.extend(suffix);
+                        elements
+                    }
+                };
+            },
+            Type::List(element) => *element,
+            Type::Dynamic => return targets.iter().map(|_| Type::Dynamic).collect(),
+            Type::Variable(_) if list => {
+                let element = self.fresh();
+                self.unify(&ty, &Type::List(Box::new(element.clone())));
+                element
+            },
+            Type::Variable(_) if rest.is_none() => {
+                let elements = targets.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                self.unify(&ty, &Type::Tuple(elements.clone()));
+                return elements;
+            },
+            Type::Variable(_) => return targets.iter().map(|_| self.fresh()).collect(),
+            other => {
+                self.report(
+                    message!("E0006-unpack", type = other),
+                    vec![primary(at).with_message(message!("E0006-type.label", type = other)), secondary(sequence.position)],
+                    None
+                );
+                return targets.iter().map(|_| self.fresh()).collect();
+            }
+        };
+
+        targets.iter().map(|target| match target {
+            Target::Rest(_) => Type::List(Box::new(element.clone())),
+            _ => element.clone()
+        }).collect()
+    }
+
+    // Declares the structs, enums and functions of a block before any of it
+    // is checked, like the resolver, so they can be used before they're
+    // written. Types come first, with their fields once every type in the
+    // block has a name, so signatures can refer to any of them. Bodies are
+    // checked when their statements are reached.
+    fn hoist(&mut self, code: &[AST]) {
+        for ast in code {
+            match ast {
+                AST::Statement(Statement::Struct(structure)) => {
+                    let name = structure.name.name.clone();
+                    let parameters = self.generics(&structure.type_parameters);
+                    self.generics.pop();
+                    self.structs.insert(name.clone(), StructType { parameters, fields: vec![], methods: HashMap::new() });
+                    self.declare(&structure.name, Scheme::simple(Type::Struct(name)), structure.name.position);
+                },
+                AST::Statement(Statement::Enum(enumeration)) => {
+                    let name = enumeration.name.name.clone();
+                    let parameters = self.generics(&enumeration.type_parameters);
+                    self.generics.pop();
+                    self.enums.insert(name.clone(), EnumType { parameters, variants: vec![] });
+                    self.declare(&enumeration.name, Scheme::simple(Type::Enum(name)), enumeration.name.position);
+                },
+                _ => {}
+            }
+        }
+        for ast in code {
+            match ast {
+                AST::Statement(Statement::Struct(structure)) => self.structure_members(structure),
+                AST::Statement(Statement::Enum(enumeration)) => self.enumeration(enumeration),
+                _ => {}
+            }
+        }
+        for ast in code {
+            if let AST::Statement(Statement::Function(function)) = ast {
+                if let Some(name) = &function.name {
+                    let generic = self.generics(&function.type_parameters);
+                    let (parameters, result) = self.signature(function, None);
+                    self.generics.pop();
+                    let signature = Signature { generic, parameters, result };
+                    self.declare_function(name, &signature);
+                    self.signatures.insert(key(function.position), vec![signature]);
+                }
+            }
+        }
+    }
+
+    // The signature was known from the annotations before the body is
+    // checked, so the function can call itself, generically if it's generic.
+    // It's declared again here in case something in between took its name.
+    fn named_function(&mut self, function: &mut Function) {
+        let name = match &function.name {
+            Some(name) => name.clone(),
+            None => return
+        };
+
+        let signature = self.hoisted(function.position).pop().expect("a function has one signature");
+        self.declare_function(&name, &signature);
+        self.generics.push(self.generic_scope(&signature.generic));
+        self.body(function, signature.parameters, signature.result);
+        self.generics.pop();
+    }
+
+    fn declare_function(&mut self, name: &Identifier, signature: &Signature) {
+        let parameters = signature.parameters.iter().map(|(ty, _)| ty.clone()).collect();
+        let ty = Type::Function(parameters, Box::new(signature.result.0.clone()));
+        let scheme = self.scheme(&ty, &signature.generic, name.position);
+        self.declare(name, scheme, name.position);
+        if let Some(entry) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.name)) {
+            entry.signature = signature.parameters.iter().map(|(_, position)| *position).collect();
+        }
+    }
+
+    // The signatures worked out for a function or struct when its block was hoisted.
+    fn hoisted(&mut self, position: Position) -> Vec<Signature> {
+        self.signatures.remove(&key(position)).expect("declarations are hoisted with their block")
+    }
+
+    // The type parameters numbered `generic`, by name.
+    fn generic_scope(&self, generic: &[usize]) -> HashMap<String, usize> {
+        generic.iter().map(|&parameter| (self.type_parameters[parameter].name.name.clone(), parameter)).collect()
+    }
+
+    // The type of a function written as a value. A generic one gets fresh
+    // variables for its type parameters, since it's only used where it is.
+    fn function(&mut self, function: &mut Function) -> Type {
+        let generic = self.generics(&function.type_parameters);
+        let (parameters, result) = self.signature(function, None);
+        let ty = Type::Function(parameters.iter().map(|(ty, _)| ty.clone()).collect(), Box::new(result.0.clone()));
+        self.body(function, parameters, result);
+        self.generics.pop();
+
+        let arguments = self.arguments(&generic);
+        let ty = ty.instantiate(&arguments);
+        for requirement in self.bounds(&generic, &arguments, &ty, function.position) {
+            self.constrain(requirement);
+        }
+        ty
+    }
+
+    // The types of a function's parameters and result, and where each is
+    // declared, with `receiver` the type of `self` for methods. Parameters
+    // and results without an annotation are `dynamic`.
+    fn signature(&mut self, function: &Function, receiver: Option<&Type>) -> (Vec<(Type, Position)>, (Type, Position)) {
+        let mut parameters = Vec::new();
+        for (i, parameter) in function.parameters.iter().enumerate() {
+            parameters.push(match (&parameter.annotation, receiver) {
+                (Some(annotation), _) => (self.annotation(annotation), annotation.position()),
+                (None, Some(receiver)) if i == 0 && parameter.name.name == "self" => (receiver.clone(), parameter.name.position),
+                (None, _) => (Type::Dynamic, parameter.name.position)
+            });
+        }
+        let result = match &function.result {
+            Some(annotation) => (self.annotation(annotation), annotation.position()),
+            None => (Type::Dynamic, function.position)
+        };
+        (parameters, result)
+    }
+
+    // Checks the body of a function against its signature.
+    fn body(&mut self, function: &mut Function, parameters: Vec<(Type, Position)>, result: (Type, Position)) {
+        let scope = function.parameters.iter().zip(parameters)
+            .map(|(parameter, (ty, origin))| (parameter.name.name.clone(), Entry { scheme: Scheme::simple(ty), origin, signature: vec![] }))
+            .collect();
+        self.scopes.push(scope);
+        self.results.push(result);
+        let body = Rc::get_mut(&mut function.body).expect("function bodies are only shared once the program runs");
+        let (value, at) = self.block(&mut body.code, body.position);
+        let last = match body.code.last_mut() {
+            Some(AST::Expression(expression)) => Some(expression),
+            _ => None
+        };
+        self.returns(value, at, last);
+        self.results.pop();
+        self.scopes.pop();
+    }
+
+    // Declares type parameters in a new scope of them, which the caller
+    // leaves, and gives back their numbers.
+    fn generics(&mut self, declared: &[TypeParameter]) -> Vec<usize> {
+        let mut scope = HashMap::new();
+        let mut numbers = Vec::new();
+        for parameter in declared {
+            let mut bounds = Vec::new();
+            for bound in &parameter.bounds {
+                match Bound::named(&bound.name) {
+                    Some(named) => bounds.push(named),
+                    None => {
+                        let error = Error::new(
+                            ErrorForm::NameError,
+                            message!("E0003-unknown-bound", name = bound.name),
+                            vec![primary(bound.position)],
+                            vec![message!("E0003-unknown-bound.note")]
+                        );
+                        self.error.register_error(error);
+                    }
+                }
+            }
+            scope.insert(parameter.name.name.clone(), self.type_parameters.len());
+            numbers.push(self.type_parameters.len());
+            self.type_parameters.push(TypeParameterType { name: parameter.name.clone(), bounds });
+        }
+        self.generics.push(scope);
+        numbers
+    }
+
+    // Fresh variables to stand in for type parameters.
+    fn arguments(&mut self, parameters: &[usize]) -> HashMap<usize, Type> {
+        parameters.iter().map(|&parameter| (parameter, self.fresh())).collect()
+    }
+
+    // The bounds the type parameters of an `instance` of something generic
+    // put on the types given for them.
+    fn bounds(&self, parameters: &[usize], arguments: &HashMap<usize, Type>, instance: &Type, position: Position) -> Vec<Requirement> {
+        let mut requirements = Vec::new();
+        for &parameter in parameters {
+            for &bound in &self.type_parameters[parameter].bounds {
+                let ty = arguments[&parameter].clone();
+                requirements.push(Requirement::Bound { ty, bound, parameter, instance: instance.clone(), position });
+            }
+        }
+        requirements
+    }
+
+    // A generic type for something declared with type parameters, which are
+    // replaced wherever it's used.
+    fn scheme(&mut self, ty: &Type, parameters: &[usize], position: Position) -> Scheme {
+        let arguments = self.arguments(parameters);
+        let ty = ty.instantiate(&arguments);
+        let constraints = self.bounds(parameters, &arguments, &ty, position).into_iter().map(Constraint::new).collect();
+        let variables = parameters.iter().flat_map(|parameter| arguments[parameter].variables()).collect();
+        Scheme { variables, constraints, ty }
+    }
+
+    // Checks a value the innermost function gives back, from `expression` if
+    // it's a whole one.
+    fn returns(&mut self, value: Type, at: Position, expression: Option<&mut Expression>) {
+        if let Some((expected, because)) = self.results.last().cloned() {
+            self.fits(expression, &value, at, &expected, because, "E0006-mismatch.result");
+        }
+    }
+
+    // The fields of a hoisted struct, and the signatures of its methods.
+    // Methods can call each other, so each has a type before any is checked.
+    fn structure_members(&mut self, structure: &Struct) {
+        let name = structure.name.name.clone();
+        let parameters = self.struct_type(&name).parameters.clone();
+        self.generics.push(self.generic_scope(&parameters));
+        let fields = structure.fields.iter().map(|field| self.member(field)).collect();
+        self.struct_type(&name).fields = fields;
+
+        let record = Type::Record(name.clone(), parameters.iter().map(|&p| self.parameter(p)).collect());
+        let mut signatures = Vec::new();
+        for method in &structure.methods {
+            let generic = self.generics(&method.type_parameters);
+            let (parameters, result) = self.signature(method, Some(&record));
+            let method_name = method.name.as_ref().map(|n| n.name.clone()).unwrap_or_default();
+            let receiver = method.parameters.first().is_some_and(|p| p.name.name == "self");
+            let position = method.name.as_ref().map_or(method.position, |n| n.position);
+            let ty = Type::Function(parameters.iter().map(|(ty, _)| ty.clone()).collect(), Box::new(result.0.clone()));
+            let scheme = self.scheme(&ty, &generic, position);
+            self.struct_type(&name).methods.insert(method_name, Method { scheme, receiver, position });
+            self.generics.pop();
+            signatures.push(Signature { generic, parameters, result });
+        }
+        self.generics.pop();
+        self.signatures.insert(key(structure.position), signatures);
+    }
+
+    // Checks the bodies of a hoisted struct's methods.
+    fn structure(&mut self, structure: &mut Struct) {
+        let name = structure.name.name.clone();
+        self.declare(&structure.name, Scheme::simple(Type::Struct(name.clone())), structure.name.position);
+        let parameters = self.struct_type(&name).parameters.clone();
+        self.generics.push(self.generic_scope(&parameters));
+        for (method, signature) in structure.methods.iter_mut().zip(self.hoisted(structure.position)) {
+            self.generics.push(self.generic_scope(&signature.generic));
+            self.body(method, signature.parameters, signature.result);
+            self.generics.pop();
+        }
+        self.generics.pop();
+    }
+
+    fn struct_type(&mut self, name: &str) -> &mut StructType {
+        self.structs.get_mut(name).expect("the struct was just declared")
+    }
+
+    // The variants of a hoisted enum.
+    fn enumeration(&mut self, enumeration: &Enum) {
+        let name = enumeration.name.name.clone();
+        let parameters = self.enums[&name].parameters.clone();
+        self.generics.push(self.generic_scope(&parameters));
+        let variants = enumeration.variants.iter()
+            .map(|variant| (variant.name.clone(), variant.fields.iter().map(|field| self.member(field)).collect()))
+            .collect();
+        self.generics.pop();
+        if let Some(enumeration) = self.enums.get_mut(&name) {
+            enumeration.variants = variants;
+        }
+    }
+
+    fn parameter(&self, parameter: usize) -> Type {
+        Type::Parameter(parameter, self.type_parameters[parameter].name.name.clone())
+    }
+
+    // A field of a struct or variant, which is `dynamic` without an annotation.
+    fn member(&mut self, field: &Annotated) -> Member {
+        let (ty, position) = match &field.annotation {
+            Some(annotation) => (self.annotation(annotation), annotation.position()),
+            None => (Type::Dynamic, field.name.position)
+        };
+        Member { name: field.name.name.clone(), ty, position }
+    }
+
+    fn annotation(&mut self, annotation: &Annotation) -> Type {
+        match annotation {
+            Annotation::Name(name) => {
+                if let Some(&parameter) = self.generics.iter().rev().find_map(|scope| scope.get(&name.name)) {
+                    return self.parameter(parameter);
+                }
+                match name.name.as_str() {
+                    "nil" => Type::Nil,
+                    "bool" => Type::Boolean,
+                    "int" => Type::Integer,
+                    "decimal" => Type::Decimal,
+                    "rational" => Type::Rational,
+                    "float" => Type::Float,
+                    "str" => Type::String,
+                    "dynamic" => Type::Dynamic,
+                    // A generic type without type arguments could be any of its instances.
+                    other => match self.declared_type(other) {
+                        Some(parameters) => self.generic_type(other, parameters.iter().map(|_| Type::Dynamic).collect()),
+                        None => self.unknown_type(name)
+                    }
+                }
+            },
+            Annotation::Generic(g) => {
+                let arguments = g.arguments.iter().map(|a| self.annotation(a)).collect::<Vec<_>>();
+                let parameters = match self.declared_type(&g.name.name) {
+                    Some(parameters) => parameters,
+                    None if self.generics.iter().any(|scope| scope.contains_key(&g.name.name)) || builtin_type(&g.name.name) => vec![],
+                    None => return self.unknown_type(&g.name)
+                };
+                if parameters.len() != arguments.len() {
+                    let key = match parameters.len() {
+                        0 => "E0006-type-arguments.none",
+                        1 => "E0006-type-arguments.one",
+                        _ => "E0006-type-arguments"
+                    };
+                    let message = message!(key, name = g.name.name, expected = parameters.len(), given = arguments.len());
+                    self.report(message, vec![primary(g.position)], None);
+                    return self.fresh();
+                }
+                let replacements = parameters.iter().copied().zip(arguments.iter().cloned()).collect();
+                let ty = self.generic_type(&g.name.name, arguments);
+                for requirement in self.bounds(&parameters, &replacements, &ty, g.position) {
+                    self.constrain(requirement);
+                }
+                ty
+            },
+            Annotation::Tuple(t) => Type::Tuple(t.annotations.iter().map(|a| self.annotation(a)).collect()),
+            Annotation::List(l) => Type::List(Box::new(self.annotation(&l.element))),
+            Annotation::Map(m) => Type::Map(Box::new(self.annotation(&m.key)), Box::new(self.annotation(&m.value))),
+            Annotation::Function(f) => {
+                let parameters = f.parameters.iter().map(|a| self.annotation(a)).collect();
+                Type::Function(parameters, Box::new(self.annotation(&f.result)))
+            }
+        }
+    }
+
+    // The type parameters of the struct or enum with this name.
+    fn declared_type(&self, name: &str) -> Option<Vec<usize>> {
+        match (self.structs.get(name), self.enums.get(name)) {
+            (Some(structure), _) => Some(structure.parameters.clone()),
+            (_, Some(enumeration)) => Some(enumeration.parameters.clone()),
+            _ => None
+        }
+    }
+
+    // The type of the values of the struct or enum with this name, given
+    // `arguments` for its type parameters.
+    fn generic_type(&self, name: &str, arguments: Vec<Type>) -> Type {
+        if self.structs.contains_key(name) {
+            Type::Record(name.to_string(), arguments)
+        } else {
+            Type::Variant(name.to_string(), arguments)
+        }
+    }
+
+    fn unknown_type(&mut self, name: &Identifier) -> Type {
+        let error = Error::new(
+            ErrorForm::NameError,
+            message!("E0003-unknown-type", name = name.name),
+            vec![primary(name.position)],
+            vec![message!("E0003-unknown-type.note")]
+        );
+        self.error.register_error(error);
+        self.fresh()
+    }
+
+    // Long chains of operators nest deeply, so the stack grows as needed
+    // rather than overflowing.
+    fn expression(&mut self, expression: &mut Expression) -> Type {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.infer(expression))
+    }
+
+    fn infer(&mut self, expression: &mut Expression) -> Type {
+        match expression {
+            Expression::Literal(l) => literal(&l.form),
+            Expression::Identifier(i) => match self.lookup(&i.name) {
+                Some(entry) => self.instantiate(&entry.scheme, i.position),
+                None => match builtins::get(&i.name) {
+                    Some(builtin) => {
+                        let argument = self.fresh();
+                        let result = self.builtin(builtin.name);
+                        Type::Function(vec![argument], Box::new(result))
+                    },
+                    // The resolver reports names that were never declared.
+                    None => self.fresh()
+                }
+            },
+            Expression::BinaryOperation(b) => self.binary(b),
+            Expression::UnaryOperation(u) => {
+                let operand = Operand { ty: self.expression(&mut u.operand), position: u.operand.position() };
+                if u.operator == token!(not) {
+                    return Type::Boolean;
+                }
+                let result = self.fresh();
+                self.constrain(Requirement::Unary { operator: u.operator, operand, result: result.clone() });
+                result
+            },
+            Expression::Comparison(c) => {
+                let operands = c.operands.iter_mut()
+                    .map(|operand| Operand { ty: self.expression(operand), position: operand.position() })
+                    .collect::<Vec<_>>();
+                for (operator, pair) in c.operators.iter().zip(operands.windows(2)) {
+                    if ordering(*operator) {
+                        self.constrain(Requirement::Ordering { operator: *operator, left: pair[0].clone(), right: pair[1].clone() });
+                    }
+                }
+                Type::Boolean
+            },
+            Expression::Tuple(s) => self.tuple(s),
+            Expression::List(s) => self.list(s),
+            Expression::Spread(s) => {
+                self.expression(&mut s.value);
+                self.fresh()
+            },
+            Expression::Attribute(a) => {
+                let object = Operand { ty: self.expression(&mut a.object), position: a.object.position() };
+                let value = self.fresh();
+                let operand = Operand { ty: value.clone(), position: a.position };
+                self.constrain(Requirement::Attribute { object, name: a.name.clone(), value: operand, store: false });
+                value
+            },
+            Expression::Index(i) => {
+                let object = Operand { ty: self.expression(&mut i.object), position: i.object.position() };
+                let index = Operand { ty: self.expression(&mut i.index), position: i.index.position() };
+                let value = self.fresh();
+                let operand = Operand { ty: value.clone(), position: i.position };
+                let literal = literal_index(&i.index);
+                let container = object.ty.clone();
+                let declared = match &*i.object {
+                    Expression::Identifier(name) => self.lookup(&name.name).map_or(i.object.position(), |entry| entry.origin),
+                    _ => i.object.position()
+                };
+                self.constrain(Requirement::Index { object, index, literal, value: operand, store: false, position: i.position });
+                self.check_read(expression, &container, declared);
+                value
+            },
+            Expression::Call(c) => self.call(c),
+            Expression::Lambda(l) => self.function(l),
+            Expression::Match(m) => self.match_expression(m),
+            Expression::Construction(c) => {
+                let structure = self.structs.get(&c.name.name).map(|structure| (structure.parameters.clone(), structure.fields.clone()));
+                let (parameters, fields) = structure.unwrap_or_default();
+                let arguments = self.arguments(&parameters);
+                for field in &mut c.fields {
+                    let value = self.expression(&mut field.value);
+                    if let Some(member) = fields.iter().find(|member| member.name == field.name.name) {
+                        let expected = member.ty.instantiate(&arguments);
+                        self.coerce(&mut field.value, &value, &expected, member.position, "E0006-mismatch.field");
+                    }
+                }
+                if !self.structs.contains_key(&c.name.name) {
+                    return self.fresh();
+                }
+                let record = Type::Record(c.name.name.clone(), parameters.iter().map(|p| arguments[p].clone()).collect());
+                for requirement in self.bounds(&parameters, &arguments, &record, c.name.position) {
+                    self.constrain(requirement);
+                }
+                record
+            },
+            Expression::Map(m) => {
+                let mut key = None;
+                let mut value = None;
+                for entry in &mut m.entries {
+                    let ty = self.expression(&mut entry.key);
+                    self.join(&mut key, ty);
+                    let ty = self.expression(&mut entry.value);
+                    self.join(&mut value, ty);
+                }
+                let key = key.unwrap_or_else(|| self.fresh());
+                let value = value.unwrap_or_else(|| self.fresh());
+                Type::Map(Box::new(key), Box::new(value))
+            },
+            // Casts are only added once their value has been checked.
+            Expression::Cast(c) => c.ty.clone()
+        }
+    }
+
+    fn binary(&mut self, b: &mut BinaryOperation) -> Type {
+        let left = Operand { ty: self.expression(&mut b.loperand), position: b.loperand.position() };
+        let right = Operand { ty: self.expression(&mut b.roperand), position: b.roperand.position() };
+
+        match b.operator {
+            // Either operand can be what `and` and `or` give back, so when
+            // they differ it could be either.
+            token!(and) | token!(or) => if self.unify(&left.ty, &right.ty) { left.ty } else { Type::Dynamic },
+            // Any two values can be compared for equality.
+            token!(==) | token!(!=) => Type::Boolean,
+            operator if ordering(operator) => {
+                self.constrain(Requirement::Ordering { operator, left, right });
+                Type::Boolean
+            },
+            operator => {
+                let result = self.fresh();
+                self.constrain(Requirement::Binary { operator, left, right, result: result.clone() });
+                result
+            }
+        }
+    }
+
+    fn tuple(&mut self, sequence: &mut Sequence) -> Type {
+        let mut elements = Vec::new();
+        let mut known = true;
+        for element in &mut sequence.elements {
+            match element {
+                Expression::Spread(s) => {
+                    let spread = self.expression(&mut s.value);
+                    match self.resolve(&spread) {
+                        Type::Tuple(spread) => elements.extend(spread),
+                        // How many elements a list has isn't known before it runs.
+                        _ => known = false
+                    }
+                },
+                _ => elements.push(self.expression(element))
+            }
+        }
+        if known { Type::Tuple(elements) } else { Type::Dynamic }
+    }
+
+    fn list(&mut self, sequence: &mut Sequence) -> Type {
+        let mut element = None;
+        for item in &mut sequence.elements {
+            match item {
+                Expression::Spread(s) => {
+                    let spread = self.expression(&mut s.value);
+                    match self.resolve(&spread) {
+                        Type::List(inner) => self.join(&mut element, *inner),
+                        Type::Tuple(inner) => {
+                            for ty in inner {
+                                self.join(&mut element, ty);
+                            }
+                        },
+                        Type::Dynamic => self.join(&mut element, Type::Dynamic),
+                        Type::Variable(_) => {},
+                        other => {
+                            let position = s.value.position();
+                            self.report(
+                                message!("E0006-spread", type = other),
+                                vec![primary(position).with_message(message!("E0006-type.label", type = other))],
+                                None
+                            );
+                        }
+                    }
+                },
+                _ => {
+                    let ty = self.expression(item);
+                    self.join(&mut element, ty);
+                }
+            }
+        }
+        let element = element.unwrap_or_else(|| self.fresh());
+        Type::List(Box::new(element))
+    }
+
+    // The one type the elements of a list or map, or the arms of a match,
+    // have between them. Numbers of different ranks are promoted to the
+    // highest one, and anything else that differs is `dynamic`.
+    fn join(&mut self, joined: &mut Option<Type>, ty: Type) {
+        let so_far = match joined {
+            Some(so_far) => so_far,
+            None => return *joined = Some(ty)
+        };
+        let (a, b) = (self.resolve(so_far), self.resolve(&ty));
+        if let (Some(a), Some(b)) = (a.rank(), b.rank()) {
+            if b > a {
+                *so_far = self.resolve(&ty);
+            }
+        } else if a == Type::Dynamic || b == Type::Dynamic || !self.unify(so_far, &ty) {
+            *so_far = Type::Dynamic;
+        }
+    }
+
+    fn call(&mut self, call: &mut Call) -> Type {
+        let spread = call.arguments.iter().any(|argument| matches!(argument, Expression::Spread(_)));
+
+        // Builtins take numbers or strings, and some of them take a varying
+        // number of arguments.
+        if let Expression::Identifier(name) = &*call.callee {
+            if let (None, Some(builtin)) = (self.lookup(&name.name), builtins::get(&name.name)) {
+                call.arguments.iter_mut().for_each(|argument| { self.expression(argument); });
+                if !spread && !builtin.arity.contains(&call.arguments.len()) {
+                    self.arity(call, builtin.arity.start(), builtin.arity.end(), None);
+                }
+                return self.builtin(builtin.name);
+            }
+        }
+
+        let callee = self.expression(&mut call.callee);
+        // Each argument's type, where it is, and which argument it is unless
+        // it came from a spread.
+        let mut arguments = Vec::new();
+        let mut known = true;
+        for (i, argument) in call.arguments.iter_mut().enumerate() {
+            match argument {
+                Expression::Spread(s) => {
+                    let spread = self.expression(&mut s.value);
+                    match self.resolve(&spread) {
+                        Type::Tuple(spread) => arguments.extend(spread.into_iter().map(|ty| (ty, s.position, None))),
+                        _ => known = false
+                    }
+                },
+                _ => arguments.push((self.expression(argument), argument.position(), Some(i)))
+            }
+        }
+
+        match self.resolve(&callee) {
+            Type::Function(parameters, result) => {
+                if !known {
+                    return *result;
+                }
+                if parameters.len() != arguments.len() {
+                    let callee = self.zonk(&callee);
+                    self.arity(call, &parameters.len(), &parameters.len(), Some(&callee));
+                    return *result;
+                }
+                // Blame the annotations when the function is known, and the callee otherwise.
+                let entry = match &*call.callee {
+                    Expression::Identifier(name) => self.lookup(&name.name).map(|entry| (name.name.clone(), entry)),
+                    _ => None
+                };
+                let signature = entry.as_ref().map(|(_, entry)| entry.signature.clone()).unwrap_or_default();
+                let errors = self.error.errors.len();
+                for (n, ((argument, at, i), parameter)) in arguments.iter().zip(&parameters).enumerate() {
+                    let (because, reason) = match signature.get(n) {
+                        Some(&annotation) => (annotation, "E0006-mismatch.annotation"),
+                        None => (call.callee.position(), "E0006-mismatch.parameter")
+                    };
+                    let expression = i.map(|i| &mut call.arguments[i]);
+                    self.fits(expression, argument, *at, parameter, because, reason);
+                }
+                // The annotations of a generic function name its type
+                // parameters, so say what they stood for in this call.
+                if let Some((name, _)) = entry.filter(|(_, entry)| !entry.scheme.variables.is_empty()) {
+                    let instance = self.zonk(&callee);
+                    for error in &mut self.error.errors[errors..] {
+                        error.notes.push(message!("E0006-instance.note", name = name, type = instance));
+                    }
+                }
+                *result
+            },
+            Type::Variable(_) => {
+                let result = self.fresh();
+                if known {
+                    let parameters = arguments.into_iter().map(|(ty, _, _)| ty).collect();
+                    self.unify(&callee, &Type::Function(parameters, Box::new(result.clone())));
+                }
+                result
+            },
+            Type::Dynamic => {
+                for (argument, _, i) in arguments {
+                    if let Some(i) = i {
+                        self.expose(&mut call.arguments[i], &argument);
+                    }
+                }
+                Type::Dynamic
+            },
+            Type::Struct(name) => {
+                let position = call.callee.position();
+                self.report(message!("E0006-call-struct", name = name), vec![primary(position)], None);
+                self.fresh()
+            },
+            other => {
+                let position = call.callee.position();
+                self.report(
+                    message!("E0006-call-value", type = other),
+                    vec![primary(position).with_message(message!("E0006-type.label", type = other))],
+                    None
+                );
+                self.fresh()
+            }
+        }
+    }
+
+    fn arity(&mut self, call: &Call, min: &usize, max: &usize, callee: Option<&Type>) {
+        let given = call.arguments.len();
+        let message = if min != max {
+            message!("E0006-call-arity.between", min = min, max = max, given = given)
+        } else if *min == 1 {
+            message!("E0006-call-arity.one", given = given)
+        } else {
+            message!("E0006-call-arity", expected = min, given = given)
+        };
+        let mut labels = vec![primary(call.position)];
+        if let Some(callee) = callee {
+            labels.push(secondary(call.callee.position()).with_message(message!("E0006-type.label", type = callee)));
+        }
+        self.report(message, labels, None);
+    }
+
+    fn builtin(&mut self, name: &str) -> Type {
+        match name {
+            "int" => Type::Integer,
+            "decimal" => Type::Decimal,
+            "rational" => Type::Rational,
+            "float" => Type::Float,
+            _ => self.fresh()
+        }
+    }
+
+    fn match_expression(&mut self, m: &mut Match) -> Type {
+        let scrutinee = self.expression(&mut m.scrutinee);
+        let at = m.scrutinee.position();
+        let mut result = None;
+
+        for arm in &mut m.arms {
+            self.scopes.push(HashMap::new());
+            self.pattern(&arm.pattern, &scrutinee, at);
+            if let Some(guard) = &mut arm.guard {
+                self.expression(guard);
+            }
+            let (ty, _) = self.block(&mut arm.body.code, arm.body.position);
+            self.scopes.pop();
+            self.join(&mut result, ty);
+        }
+
+        result.unwrap_or_else(|| self.fresh())
+    }
+
+    // Checks `pattern` can match the `ty` of the value at `at`, and declares
+    // the names it binds.
+    fn pattern(&mut self, pattern: &Pattern, ty: &Type, at: Position) {
+        match pattern {
+            Pattern::Wildcard(_) | Pattern::Rest(_) => {},
+            Pattern::Binding(name) => self.declare(name, Scheme::simple(ty.clone()), name.position),
+            Pattern::Literal(l) => self.literal_pattern(literal(&l.form), l.position, ty, at),
+            Pattern::Range(r) => self.literal_pattern(literal(&r.start.form), r.position, ty, at),
+            Pattern::Tuple(sequence) => match self.resolve(ty) {
+                Type::Tuple(elements) => self.sequence_pattern(sequence, elements, ty, at),
+                Type::Dynamic => for pattern in &sequence.patterns {
+                    match pattern {
+                        Pattern::Rest(RestPattern { name: Some(name), .. }) => self.declare(name, Scheme::simple(Type::Dynamic), name.position),
+                        pattern => self.pattern(pattern, &Type::Dynamic, at)
+                    }
+                },
+                Type::Variable(_) => {
+                    let elements = sequence.patterns.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                    if !sequence.patterns.iter().any(|p| matches!(p, Pattern::Rest(_))) {
+                        self.unify(ty, &Type::Tuple(elements.clone()));
+                    }
+                    self.sequence_pattern(sequence, elements, ty, at);
+                },
+                _ => self.impossible_pattern(pattern.position(), ty, at)
+            },
+            Pattern::List(sequence) => {
+                let element = match self.resolve(ty) {
+                    Type::List(element) => *element,
+                    Type::Dynamic => Type::Dynamic,
+                    Type::Variable(_) => {
+                        let element = self.fresh();
+                        self.unify(ty, &Type::List(Box::new(element.clone())));
+                        element
+                    },
+                    _ => return self.impossible_pattern(pattern.position(), ty, at)
+                };
+                for pattern in &sequence.patterns {
+                    match pattern {
+                        Pattern::Rest(RestPattern { name: Some(name), .. }) => {
+                            self.declare(name, Scheme::simple(Type::List(Box::new(element.clone()))), name.position)
+                        },
+                        pattern => self.pattern(pattern, &element, at)
+                    }
+                }
+            },
+            Pattern::Alternation(alternatives) => {
+                let mut alternatives = alternatives.patterns.iter();
+                if let Some(first) = alternatives.next() {
+                    self.pattern(first, ty, at);
+                }
+                // Every alternative binds the same names, which need the same types.
+                for alternative in alternatives {
+                    self.scopes.push(HashMap::new());
+                    self.pattern(alternative, ty, at);
+                    let bindings = self.scopes.pop().unwrap_or_default();
+                    for (name, entry) in bindings {
+                        if let Some(first) = self.lookup(&name) {
+                            self.expect(&entry.scheme.ty, entry.origin, &first.scheme.ty, first.origin, "E0006-mismatch.binding");
+                        }
+                    }
+                }
+            },
+            Pattern::Variant(v) => {
+                let parameters = self.enums.get(&v.enumeration.name).map(|e| e.parameters.clone()).unwrap_or_default();
+                let arguments = parameters.iter().map(|_| self.fresh()).collect::<Vec<_>>();
+                let variant = self.enums.get(&v.enumeration.name)
+                    .and_then(|enumeration| enumeration.variants.iter().find(|(name, _)| name.name == v.variant.name))
+                    .map(|(_, fields)| fields.iter().map(|field| instantiate(&field.ty, &parameters, &arguments)).collect::<Vec<_>>());
+                if !self.unify(ty, &Type::Variant(v.enumeration.name.clone(), arguments)) {
+                    return self.impossible_pattern(pattern.position(), ty, at);
+                }
+                if let Some(fields) = &v.fields {
+                    // The resolver reports unknown enums and variants.
+                    let elements = variant.unwrap_or_else(|| fields.patterns.iter().map(|_| self.fresh()).collect());
+                    self.sequence_pattern(fields, elements, ty, at);
+                }
+            }
+        }
+    }
+
+    fn literal_pattern(&mut self, literal: Type, position: Position, ty: &Type, at: Position) {
+        // Numbers of different ranks can still be equal.
+        if literal.rank().is_some() && self.resolve(ty).rank().is_some() {
+            return;
+        }
+        self.expect(&literal, position, ty, at, "E0006-mismatch.pattern");
+    }
+
+    // The patterns of a tuple or a variant against the types of its elements.
+    fn sequence_pattern(&mut self, sequence: &PatternSequence, elements: Vec<Type>, ty: &Type, at: Position) {
+        let patterns = &sequence.patterns;
+        let rest = patterns.iter().position(|pattern| matches!(pattern, Pattern::Rest(_)));
+        let fits = match rest {
+            Some(_) => elements.len() + 1 >= patterns.len(),
+            None => elements.len() == patterns.len()
+        };
+        if !fits {
+            return self.impossible_pattern(sequence.position, ty, at);
+        }
+
+        let rest = match rest {
+            Some(rest) => rest,
+            None => {
+                for (pattern, element) in patterns.iter().zip(&elements) {
+                    self.pattern(pattern, element, at);
+                }
+                return;
+            }
+        };
+
+        let after = patterns.len() - rest - 1;
+        for (pattern, element) in patterns[..rest].iter().zip(&elements) {
+            self.pattern(pattern, element, at);
+        }
+        for (pattern, element) in patterns[rest + 1..].iter().zip(&elements[elements.len() - after..]) {
+            self.pattern(pattern, element, at);
+        }
+        if let Pattern::Rest(RestPattern { name: Some(name), .. }) = &patterns[rest] {
+            let collected = elements[rest..elements.len() - after].to_vec();
+            self.declare(name, Scheme::simple(Type::Tuple(collected)), name.position);
+        }
+    }
+
+    fn impossible_pattern(&mut self, position: Position, ty: &Type, at: Position) {
+        let ty = self.zonk(ty);
+        self.report(
+            message!("E0006-pattern", type = ty),
+            vec![primary(position), secondary(at).with_message(message!("E0006-type.label", type = ty))],
+            None
+        );
+    }
+
+    fn constrain(&mut self, requirement: Requirement) {
+        let constraint = Constraint::new(requirement);
+        if let Some(waiting) = self.settle(constraint) {
+            self.pending.push(waiting);
+        }
+    }
+
+    // Settles every constraint it can, until none of the rest can be.
+    fn solve(&mut self) {
+        loop {
+            let pending = mem::take(&mut self.pending);
+            let before = pending.len();
+            for constraint in pending {
+                if let Some(waiting) = self.settle(constraint) {
+                    self.pending.push(waiting);
+                }
+            }
+            if self.pending.len() == before {
+                break;
+            }
+        }
+    }
+
+    // Checks a constraint, reporting it if it can't hold. It's given back to
+    // wait if a type it depends on isn't known yet.
+    fn settle(&mut self, constraint: Constraint) -> Option<Constraint> {
+        let used = constraint.used;
+        match &constraint.requirement {
+            Requirement::Binary { operator, left, right, result } => {
+                let (l, r) = (self.resolve(&left.ty), self.resolve(&right.ty));
+                if l == Type::Dynamic || r == Type::Dynamic {
+                    self.unify(result, &Type::Dynamic);
+                    return None;
+                }
+                match (&l, &r) {
+                    (Type::Variable(_), Type::Variable(_)) => return Some(constraint),
+                    // Strings, lists and booleans only go with their own kind.
+                    (Type::Variable(_), known) | (known, Type::Variable(_)) => {
+                        let decided = matches!(
+                            (operator, known),
+                            (token!(+), Type::String) | (token!(+), Type::List(_))
+                                | (token!(&), Type::Boolean) | (token!(|), Type::Boolean) | (token!(@), Type::Boolean)
+                        );
+                        if !decided {
+                            return Some(constraint);
+                        }
+                        self.unify(&l, &r);
+                    },
+                    _ => {}
+                }
+
+                let (l, r) = (self.zonk(&l), self.zonk(&r));
+                let ty = match (operator, &l, &r) {
+                    (token!(+), Type::List(a), Type::List(b)) if self.unify(a, b) => Some(self.zonk(&l)),
+                    _ => self.operation(*operator, &l, &r)
+                };
+                match ty {
+                    Some(ty) => {
+                        let position = left.position.merge(right.position);
+                        self.expect(&ty, position, result, position, "E0006-mismatch.use");
+                    },
+                    None => {
+                        let names = types::describe(&[&l, &r]);
+                        let labels = vec![
+                            primary(left.position).with_message(message!("E0006-type.label", type = names[0])),
+                            primary(right.position).with_message(message!("E0006-type.label", type = names[1]))
+                        ];
+                        self.report(message!("E0006-binary-operands", operator = operator, left = names[0], right = names[1]), labels, used);
+                    }
+                }
+            },
+            Requirement::Unary { operator, operand, result } => {
+                let ty = self.resolve(&operand.ty);
+                let allowed = match ty {
+                    Type::Variable(_) => return Some(constraint),
+                    Type::Dynamic => {
+                        self.unify(result, &Type::Dynamic);
+                        return None;
+                    },
+                    Type::Integer => true,
+                    _ => *operator == token!(-) && self.satisfies(&ty, Bound::Number)
+                };
+                if allowed {
+                    self.expect(&ty, operand.position, result, operand.position, "E0006-mismatch.use");
+                } else {
+                    let labels = vec![primary(operand.position).with_message(message!("E0006-type.label", type = ty))];
+                    self.report(message!("E0006-unary-operand", operator = operator, type = ty), labels, used);
+                }
+            },
+            Requirement::Ordering { operator, left, right } => {
+                let (l, r) = (self.resolve(&left.ty), self.resolve(&right.ty));
+                let allowed = match (&l, &r) {
+                    (Type::Dynamic, _) | (_, Type::Dynamic) => true,
+                    (Type::Variable(_), Type::Variable(_)) => return Some(constraint),
+                    (Type::Variable(_), known) | (known, Type::Variable(_)) => match known {
+                        Type::String | Type::List(_) => self.unify(&l, &r),
+                        _ => return Some(constraint)
+                    },
+                    _ if l.rank().is_some() && r.rank().is_some() => true,
+                    // Numbers of any rank can be ordered between them, but
+                    // other type parameters only with themselves.
+                    (Type::Parameter(..), _) | (_, Type::Parameter(..)) => {
+                        (l == r && self.satisfies(&l, Bound::Ordered)) || (self.satisfies(&l, Bound::Number) && self.satisfies(&r, Bound::Number))
+                    },
+                    (Type::String, Type::String) => true,
+                    (Type::Tuple(_), Type::Tuple(_)) | (Type::List(_), Type::List(_)) => self.unify(&l, &r),
+                    _ => false
+                };
+                if !allowed {
+                    let (l, r) = (self.zonk(&l), self.zonk(&r));
+                    let names = types::describe(&[&l, &r]);
+                    let labels = vec![
+                        primary(left.position).with_message(message!("E0006-type.label", type = names[0])),
+                        primary(right.position).with_message(message!("E0006-type.label", type = names[1]))
+                    ];
+                    self.report(message!("E0006-binary-operands", operator = operator, left = names[0], right = names[1]), labels, used);
+                }
+            },
+            Requirement::Attribute { object, name, value, store } => {
+                let ty = self.resolve(&object.ty);
+                let member = match &ty {
+                    Type::Variable(_) => return Some(constraint),
+                    Type::Dynamic => {
+                        if !*store {
+                            self.unify(&value.ty, &Type::Dynamic);
+                        }
+                        return None;
+                    },
+                    Type::Record(structure, arguments) => self.record_member(structure, arguments, &name.name),
+                    // A static method of a generic struct is generic in the struct's type parameters too.
+                    Type::Struct(structure) => {
+                        let method = self.structs.get(structure).and_then(|s| Some((s.parameters.clone(), s.methods.get(&name.name)?.clone())));
+                        method.map(|(parameters, method)| {
+                            let arguments = self.arguments(&parameters);
+                            (self.instantiate(&method.scheme, name.position).instantiate(&arguments), method.position)
+                        })
+                    },
+                    Type::Enum(enumeration) => {
+                        let parameters = self.enums.get(enumeration).map(|e| e.parameters.clone()).unwrap_or_default();
+                        let arguments = self.arguments(&parameters);
+                        let variant = self.enums.get(enumeration)
+                            .and_then(|e| e.variants.iter().find(|(variant, _)| variant.name == name.name))
+                            .map(|(variant, fields)| (variant.position, fields.iter().map(|f| f.ty.instantiate(&arguments)).collect::<Vec<_>>()));
+                        variant.map(|(position, fields)| {
+                            let tagged = Type::Variant(enumeration.clone(), parameters.iter().map(|p| arguments[p].clone()).collect());
+                            for requirement in self.bounds(&parameters, &arguments, &tagged, name.position) {
+                                self.constrain(requirement);
+                            }
+                            let ty = if fields.is_empty() { tagged } else { Type::Function(fields, Box::new(tagged)) };
+                            (ty, position)
+                        })
+                    },
+                    Type::Variant(enumeration, arguments) => self.enums.get(enumeration)
+                        .and_then(|e| {
+                            let field = e.variants.iter().flat_map(|(_, fields)| fields).find(|field| field.name == name.name)?;
+                            Some((instantiate(&field.ty, &e.parameters, arguments), field.position))
+                        }),
+                    _ => None
+                };
+
+                match member {
+                    Some((member, declared)) if *store => {
+                        self.expect(&value.ty, value.position, &member, declared, "E0006-mismatch.field");
+                    },
+                    Some((member, _)) => {
+                        self.expect(&member, name.position, &value.ty, value.position, "E0006-mismatch.use");
+                    },
+                    None => {
+                        let ty = self.zonk(&ty);
+                        let labels = vec![
+                            primary(name.position),
+                            secondary(object.position).with_message(message!("E0006-type.label", type = ty))
+                        ];
+                        self.report(message!("E0006-no-fields", type = ty, name = name.name), labels, used);
+                    }
+                }
+            },
+            Requirement::Index { object, index, literal, value, store, position } => {
+                let ty = self.resolve(&object.ty);
+                let element = match &ty {
+                    Type::Variable(_) => return Some(constraint),
+                    Type::Dynamic => Some(Type::Dynamic),
+                    Type::List(element) => {
+                        self.expect(&index.ty, index.position, &Type::Integer, object.position, "E0006-mismatch.index");
+                        Some((**element).clone())
+                    },
+                    Type::Map(key, element) => {
+                        // Numbers find the keys they're equal to, whatever their rank.
+                        if self.resolve(&index.ty).rank().is_none() || self.resolve(key).rank().is_none() {
+                            self.expect(&index.ty, index.position, key, object.position, "E0006-mismatch.index");
+                        }
+                        Some((**element).clone())
+                    },
+                    Type::String | Type::Tuple(_) if *store => {
+                        let labels = vec![primary(*position), secondary(object.position).with_message(message!("E0006-type.label", type = ty))];
+                        self.report(message!("E0006-index-assignment", type = self.zonk(&ty)), labels, used);
+                        return None;
+                    },
+                    Type::String => {
+                        self.expect(&index.ty, index.position, &Type::Integer, object.position, "E0006-mismatch.index");
+                        Some(Type::String)
+                    },
+                    Type::Tuple(elements) => {
+                        self.expect(&index.ty, index.position, &Type::Integer, object.position, "E0006-mismatch.index");
+                        match self.tuple_element(elements, *literal, *position, object, used) {
+                            Some(element) => Some(element),
+                            None => return None
+                        }
+                    },
+                    _ => None
+                };
+
+                match element {
+                    Some(element) if *store => {
+                        self.expect(&value.ty, value.position, &element, object.position, "E0006-mismatch.element");
+                    },
+                    Some(element) => {
+                        self.expect(&element, *position, &value.ty, value.position, "E0006-mismatch.use");
+                    },
+                    None => {
+                        let (ty, index) = (self.zonk(&ty), self.zonk(&index.ty));
+                        let names = types::describe(&[&ty, &index]);
+                        let labels = vec![primary(*position), secondary(object.position).with_message(message!("E0006-type.label", type = names[0]))];
+                        self.report(message!("E0006-index", type = names[0], index = names[1]), labels, used);
+                    }
+                }
+            },
+            Requirement::Bound { ty, bound, parameter, instance, position } => {
+                let resolved = self.resolve(ty);
+                if let Type::Variable(_) = resolved {
+                    return Some(constraint);
+                }
+                if !self.satisfies(&resolved, *bound) {
+                    let (found, instance) = (self.zonk(&resolved), self.zonk(instance));
+                    let names = types::describe(&[&found, &instance]);
+                    let declared = &self.type_parameters[*parameter].name;
+                    let labels = vec![
+                        primary(used.unwrap_or(*position)).with_message(message!("E0006-bound.label", parameter = declared.name, type = names[0])),
+                        secondary(declared.position).with_message(message!("E0006-bound.parameter", parameter = declared.name, bound = bound))
+                    ];
+                    let message = message!("E0006-bound", type = names[0], bound = bound, instance = names[1]);
+                    self.error.register_error(Error::new(ErrorForm::TypeError, message, labels, vec![]));
+                }
+            }
+        }
+        None
+    }
+
+    // Whether a known type satisfies a bound. A type parameter satisfies
+    // the bounds it's declared with.
+    fn satisfies(&self, ty: &Type, bound: Bound) -> bool {
+        match ty {
+            Type::Dynamic => true,
+            Type::Parameter(parameter, _) => self.type_parameters[*parameter].bounds.iter().any(|declared| declared.implies(bound)),
+            _ if ty.rank().is_some() => true,
+            Type::String | Type::Tuple(_) | Type::List(_) => bound == Bound::Ordered,
+            _ => false
+        }
+    }
+
+    // What an operator gives for two known operands, if they're allowed.
+    fn operation(&self, operator: TokenType, left: &Type, right: &Type) -> Option<Type> {
+        if matches!(left, Type::Parameter(..)) || matches!(right, Type::Parameter(..)) {
+            return self.parameter_operation(operator, left, right);
+        }
+        if let (Some(a), Some(b)) = (left.rank(), right.rank()) {
+            let integers = a == 0 && b == 0;
+            return match operator {
+                token!(&) | token!(|) | token!(@) | token!(<<) | token!(>>) => if integers { Some(Type::Integer) } else { None },
+                token!(/) if integers => Some(if self.exact_division { Type::Rational } else { Type::Float }),
+                // Dividing decimals gives a rational when the result never ends.
+                token!(/) if a.max(b) == 1 => Some(Type::Rational),
+                _ => Some(if a >= b { left.clone() } else { right.clone() })
+            };
+        }
+
+        match (operator, left, right) {
+            (token!(&), Type::Boolean, Type::Boolean) | (token!(|), Type::Boolean, Type::Boolean) | (token!(@), Type::Boolean, Type::Boolean) => {
+                Some(Type::Boolean)
+            },
+            (token!(+), Type::String, Type::String) => Some(Type::String),
+            (token!(+), Type::Tuple(a), Type::Tuple(b)) => Some(Type::Tuple(a.iter().chain(b).cloned().collect())),
+            _ => None
+        }
+    }
+
+    // What an operator gives when an operand is a type parameter. A number
+    // parameter could be any rank, so it only goes with itself and with
+    // integers, which every rank takes in without changing.
+    fn parameter_operation(&self, operator: TokenType, left: &Type, right: &Type) -> Option<Type> {
+        let parameter = match (left, right) {
+            (Type::Parameter(a, _), Type::Parameter(b, _)) if a == b => left,
+            (Type::Parameter(..), Type::Integer) => left,
+            (Type::Integer, Type::Parameter(..)) => right,
+            _ => return None
+        };
+        if !self.satisfies(parameter, Bound::Number) {
+            return None;
+        }
+        match operator {
+            token!(+) | token!(-) | token!(*) | token!(%) | token!(^) => Some(parameter.clone()),
+            // Dividing gives a different rank depending on which one it is.
+            token!(/) => Some(Type::Dynamic),
+            _ => None
+        }
+    }
+
+    // A field or method of a record made from a struct given `arguments`
+    // for its type parameters.
+    fn record_member(&mut self, structure: &str, arguments: &[Type], name: &str) -> Option<(Type, Position)> {
+        let declared = self.structs.get(structure)?;
+        if let Some(field) = declared.fields.iter().find(|field| field.name == name) {
+            return Some((instantiate(&field.ty, &declared.parameters, arguments), field.position));
+        }
+        let parameters = declared.parameters.clone();
+        let method = declared.methods.get(name)?.clone();
+        let ty = instantiate(&self.instantiate(&method.scheme, method.position), &parameters, arguments);
+        // Calling the method passes the record as `self`.
+        match ty {
+            Type::Function(mut parameters, result) if method.receiver && !parameters.is_empty() => {
+                let receiver = parameters.remove(0);
+                self.unify(&receiver, &Type::Record(structure.to_string(), arguments.to_vec()));
+                Some((Type::Function(parameters, result), method.position))
+            },
+            ty => Some((ty, method.position))
+        }
+    }
+
+    fn tuple_element(&mut self, elements: &[Type], literal: Option<i64>, position: Position, object: &Operand, used: Option<Position>) -> Option<Type> {
+        let tuple = Type::Tuple(elements.to_vec());
+        match literal {
+            Some(n) => {
+                let length = elements.len() as i64;
+                let at = if n < 0 { n + length } else { n };
+                if (0..length).contains(&at) {
+                    return Some(elements[at as usize].clone());
+                }
+                let ty = self.zonk(&tuple);
+                let labels = vec![primary(position), secondary(object.position).with_message(message!("E0006-type.label", type = ty))];
+                self.report(message!("E0006-tuple-index", type = ty, index = n), labels, used);
+                None
+            },
+            // Any element could be meant, so they all need the same type.
+            None => {
+                let first = elements.first()?.clone();
+                if elements.iter().all(|element| self.unify(element, &first)) {
+                    return Some(first);
+                }
+                let ty = self.zonk(&tuple);
+                let labels = vec![primary(position), secondary(object.position).with_message(message!("E0006-type.label", type = ty))];
+                self.report(message!("E0006-tuple-index-unknown", type = ty), labels, used);
+                None
+            }
+        }
+    }
+
+    // Checks the `found` type of what's at `at` fits where an `expected`
+    // type is needed. `because` is what made it expected, which the label
+    // `reason` describes.
+    fn expect(&mut self, found: &Type, at: Position, expected: &Type, because: Position, reason: &str) -> bool {
+        // A number can stand in for one of a higher rank, which it's promoted to.
+        if let (Some(a), Some(b)) = (self.resolve(found).rank(), self.resolve(expected).rank()) {
+            if a <= b {
+                return true;
+            }
+        }
+        if self.unify(found, expected) {
+            return true;
+        }
+
+        let (found, expected) = (self.zonk(found), self.zonk(expected));
+        let names = types::describe(&[&expected, &found]);
+        let mut labels = vec![primary(at).with_message(message!("E0006-type.label", type = names[1]))];
+        if because != at {
+            labels.push(secondary(because).with_message(message!(reason, type = names[0])));
+        }
+        self.report(message!("E0006-mismatch", expected = names[0], found = names[1]), labels, None);
+        false
+    }
+
+    // Checks the value of `expression` fits where an `expected` type is
+    // needed, like `expect`. Parts of it that are `dynamic` can only be
+    // checked when the program runs, so it's wrapped in a cast to do that.
+    fn coerce(&mut self, expression: &mut Expression, found: &Type, expected: &Type, because: Position, reason: &str) -> bool {
+        let at = expression.position();
+        if !self.expect(found, at, expected, because, reason) {
+            self.imported(expression, found);
+            return false;
+        }
+        let expected = self.zonk(expected);
+        if expected == Type::Dynamic {
+            self.expose(expression, found);
+        } else if uncertain(&self.zonk(found), &expected) {
+            let origin = match expression {
+                Expression::Identifier(name) => self.lookup(&name.name).map(|entry| entry.origin).filter(|&origin| origin != at),
+                _ => None
+            };
+            let placeholder = Expression::Tuple(Sequence { elements: vec![], position: at });
+            let value = Box::new(mem::replace(expression, placeholder));
+            *expression = Expression::Cast(Cast { value, ty: expected, declared: because, origin });
+        }
+        true
+    }
+
+    // Points the error just reported for a variable declared in another file
+    // at where it got its type, which the rest of the error doesn't show.
+    fn imported(&mut self, expression: &Expression, found: &Type) {
+        let (name, entry) = match expression {
+            Expression::Identifier(name) => match self.lookup(&name.name) {
+                Some(entry) if entry.origin.file != name.position.file => (name, entry),
+                _ => return
+            },
+            _ => return
+        };
+        let label = secondary(entry.origin).with_message(message!("E0006-mismatch.imported", name = name.name, type = self.zonk(found)));
+        if let Some(error) = self.error.errors.last_mut() {
+            error.labels.push(label);
+        }
+    }
+
+    // Wraps a typed function going somewhere `dynamic` in a cast to its own
+    // type, so code without annotations can't call it with the wrong
+    // arguments. Its errors point to where its types were declared.
+    fn expose(&mut self, expression: &mut Expression, found: &Type) {
+        let ty = self.zonk(found);
+        if !exposed(&ty) {
+            return;
+        }
+        let at = expression.position();
+        let declared = match expression {
+            Expression::Identifier(name) => self.lookup(&name.name).map_or(at, |entry| entry.origin),
+            _ => at
+        };
+        let placeholder = Expression::Tuple(Sequence { elements: vec![], position: at });
+        let value = Box::new(mem::replace(expression, placeholder));
+        *expression = Expression::Cast(Cast { value, ty, declared, origin: Some(declared) });
+    }
+
+    // Wraps a read from a list or map in a cast to the type of its elements.
+    // Code without annotations may share the container and put anything in
+    // it, so what was checked when it got its type may not hold any more.
+    // Elements are only checked on the outside, since reading from them
+    // checks what's inside.
+    fn check_read(&mut self, expression: &mut Expression, container: &Type, declared: Position) {
+        let element = match self.zonk(container) {
+            Type::List(element) | Type::Map(_, element) => shallow(&element),
+            _ => return
+        };
+        if matches!(element, Type::Dynamic | Type::Variable(_) | Type::Parameter(..)) {
+            return;
+        }
+        let at = expression.position();
+        let placeholder = Expression::Tuple(Sequence { elements: vec![], position: at });
+        let value = Box::new(mem::replace(expression, placeholder));
+        *expression = Expression::Cast(Cast { value, ty: element, declared, origin: None });
+    }
+
+    // `coerce` when there's a whole expression to cast, and `expect` otherwise.
+    fn fits(&mut self, expression: Option<&mut Expression>, found: &Type, at: Position, expected: &Type, because: Position, reason: &str) -> bool {
+        match expression {
+            Some(expression) => self.coerce(expression, found, expected, because, reason),
+            None => self.expect(found, at, expected, because, reason)
+        }
+    }
+
+    fn report(&mut self, message: String, mut labels: Vec<Label<usize>>, used: Option<Position>) {
+        if let Some(used) = used {
+            labels.push(secondary(used).with_message(message!("E0006-constraint.used")));
+        }
+        self.error.register_error(Error::new(ErrorForm::TypeError, message, labels, vec![]));
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.bindings.push(None);
+        Type::Variable(self.bindings.len() - 1)
+    }
+
+    // Follows variables that have been bound until reaching a type that isn't one.
+    fn resolve(&self, ty: &Type) -> Type {
+        let mut ty = ty.clone();
+        while let Type::Variable(v) = ty {
+            match &self.bindings[v] {
+                Some(bound) => ty = bound.clone(),
+                None => break
+            }
+        }
+        ty
+    }
+
+    // Replaces every bound variable in a type, however deep.
+    fn zonk(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Tuple(elements) => Type::Tuple(elements.iter().map(|element| self.zonk(element)).collect()),
+            Type::List(element) => Type::List(Box::new(self.zonk(&element))),
+            Type::Map(key, value) => Type::Map(Box::new(self.zonk(&key)), Box::new(self.zonk(&value))),
+            Type::Function(parameters, result) => Type::Function(
+                parameters.iter().map(|parameter| self.zonk(parameter)).collect(),
+                Box::new(self.zonk(&result))
+            ),
+            Type::Record(name, arguments) => Type::Record(name, arguments.iter().map(|argument| self.zonk(argument)).collect()),
+            Type::Variant(name, arguments) => Type::Variant(name, arguments.iter().map(|argument| self.zonk(argument)).collect()),
+            other => other
+        }
+    }
+
+    // Makes two types the same by binding variables in them, if they can be.
+    // Nothing is bound if they can't.
+    fn unify(&mut self, a: &Type, b: &Type) -> bool {
+        let start = self.trail.len();
+        if self.unify_inner(a, b) {
+            self.trail.truncate(start);
+            return true;
+        }
+        for variable in self.trail.split_off(start) {
+            self.bindings[variable] = None;
+        }
+        false
+    }
+
+    fn unify_inner(&mut self, a: &Type, b: &Type) -> bool {
+        let (a, b) = (self.resolve(a), self.resolve(b));
+        match (&a, &b) {
+            (Type::Variable(x), Type::Variable(y)) if x == y => true,
+            (Type::Variable(v), other) | (other, Type::Variable(v)) => {
+                // A type can't contain itself.
+                if self.zonk(other).contains(*v) {
+                    return false;
+                }
+                self.bindings[*v] = Some(other.clone());
+                self.trail.push(*v);
+                true
+            },
+            // Anything goes with `dynamic`; whether it really fits is checked when it runs.
+            (Type::Dynamic, _) | (_, Type::Dynamic) => true,
+            (Type::Tuple(xs), Type::Tuple(ys)) => xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.unify_inner(x, y)),
+            (Type::List(x), Type::List(y)) => self.unify_inner(x, y),
+            (Type::Map(k1, v1), Type::Map(k2, v2)) => self.unify_inner(k1, k2) && self.unify_inner(v1, v2),
+            (Type::Function(p1, r1), Type::Function(p2, r2)) => {
+                p1.len() == p2.len() && p1.iter().zip(p2).all(|(x, y)| self.unify_inner(x, y)) && self.unify_inner(r1, r2)
+            },
+            (Type::Record(n1, xs), Type::Record(n2, ys)) | (Type::Variant(n1, xs), Type::Variant(n2, ys)) => {
+                n1 == n2 && xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| self.unify_inner(x, y))
+            },
+            _ => a == b
+        }
+    }
+
+    // Makes a type generic in the variables nothing outside it refers to.
+    // Constraints on those variables go with it, to be checked wherever it's used.
+    fn generalise(&mut self, ty: &Type) -> Scheme {
+        self.solve();
+        let ty = self.zonk(ty);
+        let fixed = self.fixed_variables();
+        let mut variables = ty.variables().into_iter().filter(|v| !fixed.contains(v)).collect::<Vec<_>>();
+        let mut constraints = Vec::new();
+
+        loop {
+            let pending = mem::take(&mut self.pending).into_iter()
+                .map(|constraint| constraint.map(&mut |ty: &Type| self.zonk(ty)))
+                .collect::<Vec<_>>();
+            let (taken, kept): (Vec<_>, Vec<_>) = pending.into_iter()
+                .partition(|constraint| constraint.variables().iter().any(|v| variables.contains(v)));
+            self.pending = kept;
+            if taken.is_empty() {
+                break;
+            }
+            for constraint in taken {
+                for variable in constraint.variables() {
+                    if !fixed.contains(&variable) && !variables.contains(&variable) {
+                        variables.push(variable);
+                    }
+                }
+                constraints.push(constraint);
+            }
+        }
+
+        Scheme { variables, constraints, ty }
+    }
+
+    // The variables anything in scope refers to, which can't be made generic.
+    fn fixed_variables(&self) -> Vec<usize> {
+        let mut types: Vec<(Type, &[usize])> = Vec::new();
+        for entry in self.scopes.iter().flat_map(|scope| scope.values()) {
+            types.push((self.zonk(&entry.scheme.ty), &entry.scheme.variables));
+        }
+        for structure in self.structs.values() {
+            for field in &structure.fields {
+                types.push((self.zonk(&field.ty), &[][..]));
+            }
+            for method in structure.methods.values() {
+                types.push((self.zonk(&method.scheme.ty), &method.scheme.variables));
+            }
+        }
+        for field in self.enums.values().flat_map(|e| e.variants.iter().flat_map(|(_, fields)| fields)) {
+            types.push((self.zonk(&field.ty), &[][..]));
+        }
+        for (result, _) in &self.results {
+            types.push((self.zonk(result), &[][..]));
+        }
+
+        let mut fixed = Vec::new();
+        for (ty, generic) in types {
+            fixed.extend(ty.variables().into_iter().filter(|v| !generic.contains(v)));
+        }
+        fixed
+    }
+
+    // A copy of a generic type with fresh variables, and of its constraints.
+    fn instantiate(&mut self, scheme: &Scheme, used: Position) -> Type {
+        if scheme.variables.is_empty() {
+            return scheme.ty.clone();
+        }
+        let replacements = scheme.variables.iter().map(|v| (*v, self.fresh())).collect::<HashMap<_, _>>();
+        for constraint in &scheme.constraints {
+            let mut constraint = constraint.map(&mut |ty: &Type| ty.substitute(&replacements));
+            constraint.used = Some(used);
+            if let Some(waiting) = self.settle(constraint) {
+                self.pending.push(waiting);
+            }
+        }
+        scheme.ty.substitute(&replacements)
+    }
+
+    fn declare(&mut self, name: &Identifier, scheme: Scheme, origin: Position) {
+        let scope = self.scopes.last_mut().expect("there's always a global scope");
+        scope.insert(name.name.clone(), Entry { scheme, origin, signature: vec![] });
+    }
+
+    fn lookup(&self, name: &str) -> Option<Entry> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).cloned()
+    }
+}
+
+// Where a declaration starts, to find what was hoisted for it.
+fn key(position: Position) -> (usize, usize) {
+    (position.file, position.start)
+}
+
+fn literal(form: &Form) -> Type {
+    match form {
+        Form::Integer(_) | Form::BigInteger(_) => Type::Integer,
+        Form::Decimal(_) => Type::Decimal,
+        Form::Float(_) => Type::Float,
+        Form::String(_) => Type::String,
+        Form::Boolean(_) => Type::Boolean
+    }
+}
+
+// The index of `xs[1]` or `xs[-1]`.
+fn literal_index(index: &Expression) -> Option<i64> {
+    match index {
+        Expression::Literal(Literal { form: Form::Integer(n), .. }) => Some(*n),
+        Expression::UnaryOperation(UnaryOperation { operator: token!(-), operand, .. }) => literal_index(operand).map(|n| -n),
+        _ => None
+    }
+}
+
+// Whether `expression` is a value without running anything, so making its type
+// generic can't share one instance between uses. Lists and maps are left out
+// since their elements can be changed.
+fn syntactic_value(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal(_) | Expression::Identifier(_) | Expression::Lambda(_) => true,
+        Expression::Tuple(tuple) => tuple.elements.iter().all(syntactic_value),
+        Expression::Cast(cast) => syntactic_value(&cast.value),
+        _ => false
+    }
+}
+
+// Whether a value of type `found` might not be an `expected` when the program
+// runs, because parts of it are `dynamic` where `expected` says what they are.
+// Functions are only ever checked to be functions, which their type already says.
+fn uncertain(found: &Type, expected: &Type) -> bool {
+    match (found, expected) {
+        (_, Type::Dynamic) | (_, Type::Variable(_)) | (_, Type::Parameter(..)) => false,
+        (Type::Dynamic, _) => true,
+        (Type::Tuple(a), Type::Tuple(b)) => a.iter().zip(b).any(|(a, b)| uncertain(a, b)),
+        (Type::List(a), Type::List(b)) => uncertain(a, b),
+        (Type::Map(k1, v1), Type::Map(k2, v2)) => uncertain(k1, k2) || uncertain(v1, v2),
+        // Arguments go the other way, from the caller into the function.
+        (Type::Function(p1, r1), Type::Function(p2, r2)) => {
+            p1.iter().zip(p2).any(|(a, b)| uncertain(b, a)) || uncertain(r1, r2)
+        },
+        // Type arguments aren't kept when the program runs, so only the
+        // struct or enum a value was made from can be checked.
+        _ => false
+    }
+}
+
+// Whether a value of type `ty` holds a function that relies on being given
+// arguments of the types it declares.
+fn exposed(ty: &Type) -> bool {
+    match ty {
+        Type::Function(parameters, result) => {
+            parameters.iter().any(|p| !matches!(p, Type::Dynamic | Type::Variable(_) | Type::Parameter(..))) || exposed(result)
+        },
+        Type::Tuple(types) => types.iter().any(exposed),
+        _ => false
+    }
+}
+
+// `ty` with what's inside its lists and maps left unchecked.
+fn shallow(ty: &Type) -> Type {
+    match ty {
+        Type::List(_) => Type::List(Box::new(Type::Dynamic)),
+        Type::Map(_, _) => Type::Map(Box::new(Type::Dynamic), Box::new(Type::Dynamic)),
+        Type::Tuple(types) => Type::Tuple(types.iter().map(shallow).collect()),
+        ty => ty.clone()
+    }
+}
+
+// A type from inside something generic, with its type `parameters` replaced
+// by the `arguments` an instance of it was given.
+fn instantiate(ty: &Type, parameters: &[usize], arguments: &[Type]) -> Type {
+    ty.instantiate(&parameters.iter().copied().zip(arguments.iter().cloned()).collect())
+}
+
+// Whether `name` is a type every program has.
+fn builtin_type(name: &str) -> bool {
+    matches!(name, "nil" | "bool" | "int" | "decimal" | "rational" | "float" | "str" | "dynamic")
+}
+
+fn ordering(operator: TokenType) -> bool {
+    matches!(operator, token!(<) | token!(<=) | token!(>) | token!(>=))
+}
+
+fn primary(position: Position) -> Label<usize> {
+    Label::primary(position.file, position.start..position.end)
+}
+
+fn secondary(position: Position) -> Label<usize> {
+    Label::secondary(position.file, position.start..position.end)
+}
@@ -0,0 +1,411 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+
+use crate::error::diagnostic::Error;
+use crate::error::diagnostic::ErrorForm;
+use crate::message;
+use crate::interpreter::ops;
+use crate::interpreter::value::Value;
+use crate::parser::ast::*;
+
+// Checks `match` expressions for arms that can never be reached and for
+// values that no arm accepts.
+//
+// This is the usefulness algorithm from Maranget's "Warnings for pattern
+// matching". The arms form a matrix with one row per arm; an arm is
+// unreachable when its pattern isn't useful against the rows above it, and
+// the match is exhaustive when a lone `_` isn't useful against all of them.
+// The language has no static types, so the shape of the scrutinee is taken
+// from the patterns: if every arm looks at a pair, the value is assumed to
+// be a pair. Only booleans, tuples, lists and enums can ever be covered
+// without a catch-all; numbers and strings always need a `_` or a binding.
+pub fn check(m: &Match, enums: &HashMap<String, Rc<EnumShape>>) -> Vec<Error> {
+    let mut errors = Vec::new();
+    let mut rows: Vec<(Vec<Pat>, &Arm)> = Vec::new();
+
+    for arm in &m.arms {
+        let pattern = lower(&arm.pattern, enums);
+        let matrix = rows.iter().map(|(row, _)| row.clone()).collect::<Vec<_>>();
+
+        if !useful(&matrix, std::slice::from_ref(&pattern)) {
+            let position = arm.pattern.position();
+            let mut labels = vec![Label::primary(position.file, position.start..position.end).with_message(message!("E0009-unreachable-arm.label"))];
+            let covering = rows.iter().find(|(row, _)| !useful(std::slice::from_ref(row), std::slice::from_ref(&pattern)));
+            if let Some((_, covering)) = covering {
+                let position = covering.pattern.position();
+                labels.push(Label::secondary(position.file, position.start..position.end).with_message(message!("E0009-unreachable-arm.covering")));
+            }
+
+            errors.push(Error::new(
+                ErrorForm::UnreachableArm,
+                message!("E0009-unreachable-arm"),
+                labels,
+                vec![message!("E0009-unreachable-arm.note")]
+            ));
+        }
+
+        // A guard can always fail, so guarded arms don't cover anything.
+        if arm.guard.is_none() {
+            rows.push((vec![pattern], arm));
+        }
+    }
+
+    let matrix = rows.into_iter().map(|(row, _)| row).collect::<Vec<_>>();
+    if let Some(witness) = witness(&matrix, 1) {
+        let missing = render(&witness[0]);
+        errors.push(Error::new(
+            ErrorForm::NonExhaustiveMatch,
+            message!("E0008-non-exhaustive"),
+            vec![Label::primary(m.position.file, m.position.start..m.position.end).with_message(message!("E0008-non-exhaustive.label", pattern = missing))],
+            vec![message!("E0008-non-exhaustive.note", pattern = missing)]
+        ));
+    }
+
+    errors
+}
+
+// What the checker needs to know about a declared enum: its variants and how
+// many values each one carries.
+#[derive(Debug)]
+pub struct EnumShape {
+    pub name: String,
+    pub variants: Vec<(String, usize)>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Tuple,
+    List
+}
+
+// A pattern with bindings and positions stripped away.
+#[derive(Debug, Clone)]
+enum Pat {
+    Wild,
+    Bool(bool),
+    Constant(Value),
+    Range(Value, Value, bool),
+    // The patterns before a rest, and the patterns after it if there is one.
+    Sequence(Kind, Vec<Pat>, Option<Vec<Pat>>),
+    Variant(Rc<EnumShape>, usize, Vec<Pat>),
+    Or(Vec<Pat>)
+}
+
+// What a value can be built from. A sequence constructor is either an exact
+// length or, with `at_least`, every length from there on.
+#[derive(Debug, Clone)]
+enum Constructor {
+    Bool(bool),
+    Constant(Value),
+    Range(Value, Value, bool),
+    Sequence(Kind, usize, bool),
+    Variant(Rc<EnumShape>, usize)
+}
+
+impl Constructor {
+    fn arity(&self) -> usize {
+        match self {
+            Constructor::Sequence(_, length, _) => *length,
+            Constructor::Variant(shape, index) => shape.variants[*index].1,
+            _ => 0
+        }
+    }
+}
+
+fn lower(pattern: &Pattern, enums: &HashMap<String, Rc<EnumShape>>) -> Pat {
+    let lower = |pattern| self::lower(pattern, enums);
+    match pattern {
+        Pattern::Wildcard(_) | Pattern::Binding(_) | Pattern::Rest(_) => Pat::Wild,
+        Pattern::Literal(literal) => match Value::from_form(&literal.form) {
+            Value::Boolean(b) => Pat::Bool(b),
+            value => Pat::Constant(value)
+        },
+        Pattern::Range(range) => Pat::Range(
+            Value::from_form(&range.start.form),
+            Value::from_form(&range.end.form),
+            range.inclusive
+        ),
+        Pattern::Tuple(sequence) => lower_sequence(Kind::Tuple, &sequence.patterns, enums),
+        Pattern::List(sequence) => lower_sequence(Kind::List, &sequence.patterns, enums),
+        Pattern::Alternation(alternatives) => Pat::Or(alternatives.patterns.iter().map(lower).collect()),
+        Pattern::Variant(v) => {
+            let shape = enums.get(&v.enumeration.name);
+            let index = shape.and_then(|shape| shape.variants.iter().position(|(name, _)| *name == v.variant.name));
+            let (shape, index) = match (shape, index) {
+                (Some(shape), Some(index)) => (shape.clone(), index),
+                // The resolver has already reported this, so it only has to
+                // not cover anything else.
+                _ => return Pat::Constant(Value::String(pattern.as_str()))
+            };
+
+            // Rests are spread out into wildcards, and a pattern with the
+            // wrong number of fields (also already reported) is padded or cut.
+            let arity = shape.variants[index].1;
+            let patterns = v.fields.as_ref().map_or(&[][..], |f| &f.patterns[..]);
+            let mut fields = match lower_sequence(Kind::Tuple, patterns, enums) {
+                Pat::Sequence(_, prefix, Some(suffix)) => {
+                    let missing = arity.saturating_sub(prefix.len() + suffix.len());
+                    prefix.into_iter().chain(vec![Pat::Wild; missing]).chain(suffix).collect()
+                },
+                Pat::Sequence(_, fields, None) => fields,
+                _ => unreachable!("lower_sequence always gives a sequence")
+            };
+            fields.resize(arity, Pat::Wild);
+            Pat::Variant(shape, index, fields)
+        }
+    }
+}
+
+fn lower_sequence(kind: Kind, patterns: &[Pattern], enums: &HashMap<String, Rc<EnumShape>>) -> Pat {
+    let lower = |pattern| self::lower(pattern, enums);
+    match patterns.iter().position(|pattern| matches!(pattern, Pattern::Rest(_))) {
+        Some(rest) => Pat::Sequence(
+            kind,
+            patterns[..rest].iter().map(lower).collect(),
+            Some(patterns[rest + 1..].iter().map(lower).collect())
+        ),
+        None => Pat::Sequence(kind, patterns.iter().map(lower).collect(), None)
+    }
+}
+
+// Is there a value matched by the row `q` that no row of `matrix` matches?
+fn useful(matrix: &[Vec<Pat>], q: &[Pat]) -> bool {
+    if q.is_empty() {
+        return matrix.is_empty();
+    }
+
+    let matrix = expand(matrix);
+    let heads = matrix.iter().map(|row| &row[0]).collect::<Vec<_>>();
+
+    match &q[0] {
+        Pat::Or(alternatives) => alternatives.iter().any(|alternative| {
+            let mut row = vec![alternative.clone()];
+            row.extend_from_slice(&q[1..]);
+            useful(&matrix, &row)
+        }),
+        Pat::Wild => match signature(&heads) {
+            Some(constructors) => constructors.iter().any(|constructor| {
+                useful(&specialise_matrix(&matrix, constructor), &specialise(q, constructor).expect("a wildcard matches anything"))
+            }),
+            None => useful(&default_matrix(&matrix), &q[1..])
+        },
+        Pat::Sequence(kind, _, Some(_)) => {
+            let mut heads = heads;
+            heads.push(&q[0]);
+            sequence_signature(*kind, &heads, true).iter().any(|constructor| match specialise(q, constructor) {
+                Some(q) => useful(&specialise_matrix(&matrix, constructor), &q),
+                None => false
+            })
+        },
+        head => {
+            let constructor = constructor_of(head);
+            let q = specialise(q, &constructor).expect("a pattern matches its own constructor");
+            useful(&specialise_matrix(&matrix, &constructor), &q)
+        }
+    }
+}
+
+// Like `useful` with a row of wildcards, but returns an example of a value
+// that isn't matched, one pattern per column.
+fn witness(matrix: &[Vec<Pat>], width: usize) -> Option<Vec<Pat>> {
+    if width == 0 {
+        return if matrix.is_empty() { Some(Vec::new()) } else { None };
+    }
+
+    let matrix = expand(matrix);
+    let heads = matrix.iter().map(|row| &row[0]).collect::<Vec<_>>();
+
+    match signature(&heads) {
+        Some(constructors) => constructors.iter().find_map(|constructor| {
+            let arity = constructor.arity();
+            let mut found = witness(&specialise_matrix(&matrix, constructor), arity + width - 1)?;
+            let rest = found.split_off(arity);
+            let mut result = vec![build(constructor, found)];
+            result.extend(rest);
+            Some(result)
+        }),
+        None => {
+            let mut found = witness(&default_matrix(&matrix), width - 1)?;
+            found.insert(0, Pat::Wild);
+            Some(found)
+        }
+    }
+}
+
+// Splits alternations in the first column into one row per alternative.
+fn expand(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    let mut expanded = Vec::new();
+    for row in matrix {
+        match &row[0] {
+            Pat::Or(alternatives) => {
+                let rows = alternatives.iter().map(|alternative| {
+                    let mut row = row.clone();
+                    row[0] = alternative.clone();
+                    row
+                }).collect::<Vec<_>>();
+                expanded.extend(expand(&rows));
+            },
+            _ => expanded.push(row.clone())
+        }
+    }
+    expanded
+}
+
+// The full set of constructors for a column, or `None` if the patterns in it
+// can't cover every value without a catch-all.
+fn signature(heads: &[&Pat]) -> Option<Vec<Constructor>> {
+    let heads = heads.iter().copied().filter(|head| !matches!(head, Pat::Wild)).collect::<Vec<_>>();
+    if heads.is_empty() {
+        return None;
+    }
+
+    if heads.iter().all(|head| matches!(head, Pat::Bool(_))) {
+        return Some(vec![Constructor::Bool(true), Constructor::Bool(false)]);
+    }
+
+    if let Pat::Variant(shape, _, _) = heads[0] {
+        if heads.iter().all(|head| matches!(head, Pat::Variant(s, _, _) if Rc::ptr_eq(s, shape))) {
+            return Some((0..shape.variants.len()).map(|index| Constructor::Variant(shape.clone(), index)).collect());
+        }
+    }
+
+    for kind in &[Kind::Tuple, Kind::List] {
+        if heads.iter().all(|head| matches!(head, Pat::Sequence(k, _, _) if k == kind)) {
+            return Some(sequence_signature(*kind, &heads, false));
+        }
+    }
+
+    None
+}
+
+// Tuples are assumed to have the one length every tuple pattern agrees on.
+// Lists can be any length: every length up to the longest pattern is tried
+// on its own, and everything longer is one "at least" constructor.
+fn sequence_signature(kind: Kind, heads: &[&Pat], force_lengths: bool) -> Vec<Constructor> {
+    let mut longest = 0;
+    let mut lengths = Vec::new();
+    let mut has_rest = false;
+
+    for head in heads {
+        if let Pat::Sequence(_, prefix, suffix) = head {
+            let length = prefix.len() + suffix.as_ref().map_or(0, |suffix| suffix.len());
+            longest = longest.max(length);
+            if !lengths.contains(&length) {
+                lengths.push(length);
+            }
+            has_rest |= suffix.is_some();
+        }
+    }
+
+    if kind == Kind::Tuple && !has_rest && !force_lengths && lengths.len() == 1 {
+        return vec![Constructor::Sequence(kind, lengths[0], false)];
+    }
+
+    let mut constructors = (0..=longest).map(|length| Constructor::Sequence(kind, length, false)).collect::<Vec<_>>();
+    constructors.push(Constructor::Sequence(kind, longest + 1, true));
+    constructors
+}
+
+fn constructor_of(pattern: &Pat) -> Constructor {
+    match pattern {
+        Pat::Bool(b) => Constructor::Bool(*b),
+        Pat::Constant(value) => Constructor::Constant(value.clone()),
+        Pat::Range(start, end, inclusive) => Constructor::Range(start.clone(), end.clone(), *inclusive),
+        Pat::Sequence(kind, prefix, None) => Constructor::Sequence(*kind, prefix.len(), false),
+        Pat::Variant(shape, index, _) => Constructor::Variant(shape.clone(), *index),
+        Pat::Wild | Pat::Or(_) | Pat::Sequence(_, _, Some(_)) => unreachable!("handled by `useful`")
+    }
+}
+
+fn specialise_matrix(matrix: &[Vec<Pat>], constructor: &Constructor) -> Vec<Vec<Pat>> {
+    matrix.iter().filter_map(|row| specialise(row, constructor)).collect()
+}
+
+// The rest of `row` if its first pattern accepts values built with
+// `constructor`, with that pattern replaced by its sub-patterns.
+fn specialise(row: &[Pat], constructor: &Constructor) -> Option<Vec<Pat>> {
+    let fields = match (&row[0], constructor) {
+        (Pat::Wild, _) => vec![Pat::Wild; constructor.arity()],
+        (Pat::Bool(a), Constructor::Bool(b)) if a == b => Vec::new(),
+        (Pat::Constant(a), Constructor::Constant(b)) if ops::equals(a, b) => Vec::new(),
+        (Pat::Range(start, end, inclusive), Constructor::Constant(value)) if ops::in_range(value, start, end, *inclusive) => Vec::new(),
+        (Pat::Range(start, end, inclusive), Constructor::Range(low, high, high_inclusive))
+            if contains_range((start, end, *inclusive), (low, high, *high_inclusive)) => Vec::new(),
+        (Pat::Variant(a, i, fields), Constructor::Variant(b, j)) if Rc::ptr_eq(a, b) && i == j => fields.clone(),
+        (Pat::Sequence(k, prefix, None), Constructor::Sequence(kind, length, false))
+            if k == kind && prefix.len() == *length => prefix.clone(),
+        (Pat::Sequence(k, prefix, Some(suffix)), Constructor::Sequence(kind, length, _))
+            if k == kind && prefix.len() + suffix.len() <= *length => {
+            let mut fields = prefix.clone();
+            fields.extend(vec![Pat::Wild; length - prefix.len() - suffix.len()]);
+            fields.extend(suffix.iter().cloned());
+            fields
+        },
+        _ => return None
+    };
+
+    let mut specialised = fields;
+    specialised.extend_from_slice(&row[1..]);
+    Some(specialised)
+}
+
+fn default_matrix(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    matrix.iter()
+        .filter(|row| matches!(row[0], Pat::Wild))
+        .map(|row| row[1..].to_vec())
+        .collect()
+}
+
+fn contains_range(outer: (&Value, &Value, bool), inner: (&Value, &Value, bool)) -> bool {
+    let starts_inside = matches!(ops::compare(outer.0, inner.0), Some(Ordering::Less) | Some(Ordering::Equal));
+    let ends_inside = match ops::compare(inner.1, outer.1) {
+        Some(Ordering::Less) => true,
+        Some(Ordering::Equal) => outer.2 || !inner.2,
+        _ => false
+    };
+    starts_inside && ends_inside
+}
+
+fn build(constructor: &Constructor, fields: Vec<Pat>) -> Pat {
+    match constructor {
+        Constructor::Bool(b) => Pat::Bool(*b),
+        Constructor::Constant(value) => Pat::Constant(value.clone()),
+        Constructor::Range(start, end, inclusive) => Pat::Range(start.clone(), end.clone(), *inclusive),
+        Constructor::Sequence(kind, _, false) => Pat::Sequence(*kind, fields, None),
+        Constructor::Sequence(kind, _, true) => Pat::Sequence(*kind, fields, Some(Vec::new())),
+        Constructor::Variant(shape, index) => Pat::Variant(shape.clone(), *index, fields)
+    }
+}
+
+fn render(pattern: &Pat) -> String {
+    match pattern {
+        Pat::Wild => String::from("_"),
+        Pat::Bool(b) => b.to_string(),
+        Pat::Constant(value) => value.to_string(),
+        Pat::Range(start, end, inclusive) => format!("{}{}{}", start, if *inclusive { "..=" } else { ".." }, end),
+        Pat::Sequence(kind, prefix, suffix) => {
+            let mut parts = prefix.iter().map(render).collect::<Vec<_>>();
+            if let Some(suffix) = suffix {
+                parts.push(String::from(".."));
+                parts.extend(suffix.iter().map(render));
+            }
+            match kind {
+                Kind::Tuple if parts.len() == 1 => format!("({},)", parts[0]),
+                Kind::Tuple => format!("({})", parts.join(", ")),
+                Kind::List => format!("[{}]", parts.join(", "))
+            }
+        },
+        Pat::Variant(shape, index, fields) => {
+            let name = format!("{}.{}", shape.name, shape.variants[*index].0);
+            if fields.is_empty() {
+                name
+            } else {
+                format!("{}({})", name, fields.iter().map(render).collect::<Vec<_>>().join(", "))
+            }
+        },
+        Pat::Or(alternatives) => alternatives.iter().map(render).collect::<Vec<_>>().join(" | ")
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+use num_traits::{Signed, ToPrimitive};
+
+use crate::error::diagnostic::{Error, ErrorForm, Errors};
+use crate::interpreter::numeric;
+use crate::interpreter::ops;
+use crate::interpreter::value::Value;
+use crate::message;
+use crate::parser::ast::*;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::parser::token::TokenType;
+use crate::token;
+
+// Works out operators whose operands are all literals before the program
+// runs, and drops operations that can't change their operand, like `x * 1`.
+// Everything is computed with the same `ops` the interpreter and the VM use,
+// so a folded program gives the same results as the original.
+//
+// An operation that would fail at runtime, like `1 / 0`, is left alone for
+// the runtime to report, with a warning now. The same goes for results a
+// literal can't hold, like rationals.
+//
+// Without static types, `x + 0` can only become `x` when `x` is known to be
+// an integer: `"a" + 0` has to fail, and `-0.0 + 0` is `0.0`. Integers are
+// known from literals, from operators that only give integers for integer
+// operands, and from constants declared with one. Only a file's own
+// constants are used, so what it folds to doesn't change with other files.
+//
+// Results too big to be worth writing into the program, like `2 ^ 100000`,
+// are left to be worked out when they run.
+pub struct Folder {
+    // The innermost scope is last. A name maps to whether it's a constant
+    // holding an integer.
+    scopes: Vec<HashMap<String, bool>>,
+    pub exact_division: bool,
+    pub error: Errors
+}
+
+// The most bits a folded number can take.
+const LITERAL_BITS: u64 = 4096;
+
+impl Default for Folder {
+    fn default() -> Folder {
+        Folder::new()
+    }
+}
+
+impl Folder {
+    pub fn new() -> Folder {
+        Folder {
+            scopes: vec![HashMap::new()],
+            exact_division: false,
+            error: Errors::new()
+        }
+    }
+
+    pub fn fold(&mut self, code: &mut [AST]) {
+        for integer in self.scopes[0].values_mut() {
+            *integer = false;
+        }
+        self.block(code);
+    }
+
+    fn block(&mut self, code: &mut [AST]) {
+        for ast in code {
+            self.ast(ast);
+        }
+    }
+
+    fn ast(&mut self, ast: &mut AST) {
+        match ast {
+            AST::Statement(statement) => self.statement(statement),
+            AST::Expression(expression) => self.expression(expression)
+        }
+    }
+
+    fn statement(&mut self, statement: &mut Statement) {
+        match statement {
+            Statement::Declaration(declaration) => {
+                self.expression(&mut declaration.value);
+                let integer = declaration.kind == DeclarationKind::Const && self.integer(&declaration.value);
+                match &declaration.target {
+                    Target::Identifier(identifier) => self.declare(&identifier.name, integer),
+                    target => target.names().iter().for_each(|name| self.declare(&name.name, false))
+                }
+            },
+            Statement::Assignment(assignment) => {
+                self.expression(&mut assignment.value);
+                self.target(&mut assignment.target);
+            },
+            Statement::Function(function) => {
+                if let Some(name) = &function.name {
+                    self.declare(&name.name, false);
+                }
+                self.function(function);
+            },
+            Statement::Struct(structure) => {
+                self.declare(&structure.name.name, false);
+                structure.methods.iter_mut().for_each(|method| self.function(method));
+            },
+            Statement::Enum(enumeration) => self.declare(&enumeration.name.name, false),
+            Statement::Return(r) => {
+                if let Some(value) = &mut r.value {
+                    self.expression(value);
+                }
+            },
+            Statement::Body(body) => {
+                self.scopes.push(HashMap::new());
+                self.block(&mut body.code);
+                self.scopes.pop();
+            }
+        }
+    }
+
+    // Targets that aren't names can hold expressions too, like `xs[i + 1] = 0`.
+    fn target(&mut self, target: &mut Target) {
+        match target {
+            Target::Tuple(sequence) | Target::List(sequence) => sequence.targets.iter_mut().for_each(|target| self.target(target)),
+            Target::Attribute(a) => self.expression(&mut a.object),
+            Target::Index(i) => {
+                self.expression(&mut i.object);
+                self.expression(&mut i.index);
+            },
+            Target::Identifier(_) | Target::Rest(_) => {}
+        }
+    }
+
+    fn function(&mut self, function: &mut Function) {
+        let body = Rc::get_mut(&mut function.body).expect("function bodies are only shared once the program runs");
+        self.scopes.push(function.parameters.iter().map(|parameter| (parameter.name.name.clone(), false)).collect());
+        self.block(&mut body.code);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str, integer: bool) {
+        self.scopes.last_mut().expect("there's always a global scope").insert(name.to_string(), integer);
+    }
+
+    fn expression(&mut self, expression: &mut Expression) {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.fold_expression(expression))
+    }
+
+    fn fold_expression(&mut self, expression: &mut Expression) {
+        match expression {
+            Expression::BinaryOperation(b) => {
+                self.expression(&mut b.loperand);
+                self.expression(&mut b.roperand);
+            },
+            Expression::UnaryOperation(u) => self.expression(&mut u.operand),
+            Expression::Comparison(c) => c.operands.iter_mut().for_each(|operand| self.expression(operand)),
+            Expression::Tuple(s) | Expression::List(s) => s.elements.iter_mut().for_each(|element| self.expression(element)),
+            Expression::Spread(s) => self.expression(&mut s.value),
+            Expression::Cast(c) => self.expression(&mut c.value),
+            Expression::Attribute(a) => self.expression(&mut a.object),
+            Expression::Index(i) => {
+                self.expression(&mut i.object);
+                self.expression(&mut i.index);
+            },
+            Expression::Call(c) => {
+                self.expression(&mut c.callee);
+                c.arguments.iter_mut().for_each(|argument| self.expression(argument));
+            },
+            Expression::Lambda(l) => self.function(l),
+            Expression::Match(m) => {
+                self.expression(&mut m.scrutinee);
+                for arm in &mut m.arms {
+                    // The bindings, the guard and the body share a scope.
+                    self.scopes.push(arm.pattern.bindings().iter().map(|name| (name.name.clone(), false)).collect());
+                    if let Some(guard) = &mut arm.guard {
+                        self.expression(guard);
+                    }
+                    self.block(&mut arm.body.code);
+                    self.scopes.pop();
+                }
+            },
+            Expression::Construction(c) => c.fields.iter_mut().for_each(|field| self.expression(&mut field.value)),
+            Expression::Map(m) => {
+                for entry in &mut m.entries {
+                    self.expression(&mut entry.key);
+                    self.expression(&mut entry.value);
+                }
+            },
+            Expression::Literal(_) | Expression::Identifier(_) => {}
+        }
+
+        if let Some(folded) = self.simplify(expression) {
+            *expression = folded;
+        }
+    }
+
+    // What an operation whose operands have already been folded can be
+    // replaced with, if anything.
+    fn simplify(&mut self, expression: &mut Expression) -> Option<Expression> {
+        match expression {
+            Expression::UnaryOperation(u) => {
+                let operand = literal(&u.operand)?;
+                let result = ops::unary(u.operator, &operand);
+                self.result(result, u.operator, &[operand], u.position)
+            },
+            Expression::BinaryOperation(b) => match b.operator {
+                // `and` and `or` give back whichever operand decided the result.
+                token!(and) | token!(or) => {
+                    let left = literal(&b.loperand)?;
+                    let decided = left.is_truthy() == (b.operator == token!(or));
+                    Some(if decided { take(&mut b.loperand) } else { take(&mut b.roperand) })
+                },
+                operator => match (literal(&b.loperand), literal(&b.roperand)) {
+                    (Some(left), Some(right)) if large(operator, &left, &right) => None,
+                    (Some(left), Some(right)) => {
+                        let result = if operator == token!(/) && self.exact_division {
+                            ops::divide_exactly(&left, &right)
+                        } else {
+                            ops::binary(operator, &left, &right)
+                        };
+                        self.result(result, operator, &[left, right], b.position)
+                    },
+                    _ => self.identity(b)
+                }
+            },
+            Expression::Comparison(c) => {
+                let operands = c.operands.iter().map(literal).collect::<Option<Vec<_>>>()?;
+                let mut result = Value::Boolean(true);
+                for (operator, pair) in c.operators.iter().zip(operands.windows(2)) {
+                    result = ops::binary(*operator, &pair[0], &pair[1]).ok()?;
+                    if !result.is_truthy() {
+                        break;
+                    }
+                }
+                Some(Expression::Literal(Literal { form: form(&result)?, position: c.position }))
+            },
+            _ => None
+        }
+    }
+
+    // Replaces a computed operation with its result. Failures that don't
+    // depend on types, like dividing by zero, are warned about; type errors
+    // are left to the runtime, which explains them better.
+    fn result(&mut self, result: ops::OperationResult, operator: TokenType, operands: &[Value], position: Position) -> Option<Expression> {
+        let value = match result {
+            Ok(value) => value,
+            Err((ErrorForm::RuntimeError, reason)) => {
+                self.error.register_error(Error::new(
+                    ErrorForm::ConstantEvaluation,
+                    reason,
+                    vec![Label::primary(position.file, position.start..position.end).with_message(message!("E0011-constant-failure.label"))],
+                    vec![message!("E0011-constant-failure.note")]
+                ));
+                return None;
+            },
+            Err(_) => return None
+        };
+
+        let finite = |value: &Value| !matches!(value, Value::Float(f) if !f.is_finite());
+        if !finite(&value) && operands.iter().all(finite) {
+            self.error.register_error(Error::new(
+                ErrorForm::ConstantEvaluation,
+                message!("E0011-infinite", operator = operator),
+                vec![Label::primary(position.file, position.start..position.end).with_message(message!("E0011-infinite.label"))],
+                vec![]
+            ));
+        }
+        Some(Expression::Literal(Literal { form: form(&value)?, position }))
+    }
+
+    // `x * 1`, `1 * x`, `x + 0`, `0 + x`, `x - 0` and `x - x`.
+    fn identity(&self, b: &mut BinaryOperation) -> Option<Expression> {
+        let is = |expression: &Expression, n: i64| matches!(expression, Expression::Literal(Literal { form: Form::Integer(i), .. }) if *i == n);
+        match b.operator {
+            token!(*) if is(&b.roperand, 1) && numeric(&b.loperand) => Some(take(&mut b.loperand)),
+            token!(*) if is(&b.loperand, 1) && numeric(&b.roperand) => Some(take(&mut b.roperand)),
+            token!(+) | token!(-) if is(&b.roperand, 0) && self.integer(&b.loperand) => Some(take(&mut b.loperand)),
+            token!(+) if is(&b.loperand, 0) && self.integer(&b.roperand) => Some(take(&mut b.roperand)),
+            token!(-) => match (&*b.loperand, &*b.roperand) {
+                (Expression::Identifier(left), Expression::Identifier(right)) if left.name == right.name && self.integer(&b.loperand) => {
+                    Some(Expression::Literal(Literal { form: Form::Integer(0), position: b.position }))
+                },
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    // Whether `expression` can only ever give an integer, when it gives
+    // anything at all.
+    fn integer(&self, expression: &Expression) -> bool {
+        match expression {
+            Expression::Literal(l) => matches!(l.form, Form::Integer(_) | Form::BigInteger(_)),
+            Expression::Identifier(i) => self.scopes.iter().rev().find_map(|scope| scope.get(&i.name)).copied().unwrap_or(false),
+            Expression::UnaryOperation(u) => match u.operator {
+                token!(~) => true,
+                token!(-) => self.integer(&u.operand),
+                _ => false
+            },
+            Expression::BinaryOperation(b) => match b.operator {
+                token!(+) | token!(-) | token!(*) | token!(%) | token!(&) | token!(|) | token!(@) | token!(<<) | token!(>>) => {
+                    self.integer(&b.loperand) && self.integer(&b.roperand)
+                },
+                _ => false
+            },
+            _ => false
+        }
+    }
+}
+
+// Whether `expression` can only ever give a number, when it gives anything
+// at all. Multiplying any number by 1 gives it back unchanged.
+fn numeric(expression: &Expression) -> bool {
+    match expression {
+        Expression::Literal(l) => !matches!(l.form, Form::String(_) | Form::Boolean(_)),
+        Expression::UnaryOperation(u) => u.operator != token!(not),
+        Expression::BinaryOperation(b) => matches!(
+            b.operator,
+            token!(-) | token!(*) | token!(/) | token!(%) | token!(^) | token!(<<) | token!(>>)
+        ),
+        _ => false
+    }
+}
+
+// Whether `left operator right` gives a number too big to be worth writing
+// into the program, which is checked before working it out. A result too big
+// to work out at all fails straight away, so that's still warned about.
+fn large(operator: TokenType, left: &Value, right: &Value) -> bool {
+    let (bits, amount) = match (numeric::bits(left), numeric::to_integer(right)) {
+        (Some(bits), Some(amount)) => (bits, amount),
+        _ => return false
+    };
+    let within = |limit: u64| match operator {
+        token!(^) => numeric::power_within(bits, &amount.abs(), limit),
+        token!(<<) => amount.is_negative() || amount.to_u64().is_some_and(|shift| bits.saturating_add(shift) <= limit),
+        _ => true
+    };
+    !within(LITERAL_BITS) && within(numeric::MAX_BITS)
+}
+
+fn literal(expression: &Expression) -> Option<Value> {
+    match expression {
+        Expression::Literal(l) => Some(Value::from_form(&l.form)),
+        _ => None
+    }
+}
+
+fn form(value: &Value) -> Option<Form> {
+    Some(match value {
+        Value::Integer(i) => Form::Integer(*i),
+        Value::BigInteger(i) => Form::BigInteger(i.clone()),
+        Value::Decimal(d) => Form::Decimal(d.clone()),
+        Value::Float(f) => Form::Float(*f),
+        Value::String(s) => Form::String(s.clone()),
+        Value::Boolean(b) => Form::Boolean(*b),
+        _ => return None
+    })
+}
+
+// Moves an operand out of the operation it's about to replace.
+fn take(operand: &mut Box<Expression>) -> Expression {
+    let position = operand.position();
+    std::mem::replace(operand, Expression::Literal(Literal { form: Form::Boolean(false), position }))
+}
@@ -0,0 +1,5 @@
+pub mod checker;
+pub mod exhaustiveness;
+pub mod fold;
+pub mod resolver;
+pub mod types;
@@ -0,0 +1,485 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+
+use crate::analysis::exhaustiveness;
+use crate::analysis::exhaustiveness::EnumShape;
+use crate::error::diagnostic::Error;
+use crate::error::diagnostic::ErrorForm;
+use crate::error::diagnostic::Errors;
+use crate::error::fix::{Applicability, Fix};
+use crate::error::spelling;
+use crate::interpreter::builtins::{self, BUILTINS};
+use crate::message;
+use crate::parser::lexer::Lexer;
+use crate::parser::ast::*;
+use crate::parser::position::Position;
+use crate::token;
+
+// Where a variable was used, which decides the keywords a misspelt one
+// might have been.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Use {
+    // On its own line, where any keyword that starts something could go.
+    Statement,
+    // Where a value goes, which only the literal keywords are.
+    Operand,
+    // Called, which no keyword can be.
+    Callee
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Binding {
+    kind: DeclarationKind,
+    position: Position
+}
+
+// Checks that every name is declared before it is used and that constants
+// are never reassigned. Scopes follow `Body` blocks, and the outermost scope
+// is kept between calls to `resolve` so several files can share globals.
+pub struct Resolver {
+    scopes: Vec<HashMap<String, Binding>>,
+    // Every enum declared so far by name, for checking variant patterns.
+    enums: HashMap<String, Rc<EnumShape>>,
+    functions: usize,
+    pub error: Errors
+}
+
+impl Default for Resolver {
+    fn default() -> Resolver {
+        Resolver::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            scopes: vec![HashMap::new()],
+            enums: HashMap::new(),
+            functions: 0,
+            error: Errors::new()
+        }
+    }
+
+    pub fn resolve(&mut self, code: &[AST]) {
+        self.hoist(code);
+        for ast in code {
+            self.ast(ast);
+        }
+    }
+
+    // Functions, structs and enums are declared before anything else in their
+    // block, so they can be used before they're written, and functions can
+    // call each other.
+    fn hoist(&mut self, code: &[AST]) {
+        for ast in code {
+            match ast {
+                AST::Statement(Statement::Function(Function { name: Some(name), .. })) => self.declare(name, DeclarationKind::Let),
+                AST::Statement(Statement::Struct(structure)) => self.declare(&structure.name, DeclarationKind::Const),
+                AST::Statement(Statement::Enum(enumeration)) => {
+                    self.declare(&enumeration.name, DeclarationKind::Const);
+                    let shape = EnumShape {
+                        name: enumeration.name.name.clone(),
+                        variants: enumeration.variants.iter().map(|v| (v.name.name.clone(), v.fields.len())).collect()
+                    };
+                    self.enums.insert(enumeration.name.name.clone(), Rc::new(shape));
+                },
+                _ => {}
+            }
+        }
+    }
+
+    fn ast(&mut self, ast: &AST) {
+        match ast {
+            AST::Statement(statement) => self.statement(statement),
+            // A name on its own line could be a misspelt keyword starting a statement.
+            AST::Expression(Expression::Identifier(identifier)) => self.variable(identifier, Use::Statement),
+            AST::Expression(expression) => self.expression(expression)
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Declaration(declaration) => {
+                // The value is resolved first, so `let x = x + 1` refers to an outer `x`.
+                self.expression(&declaration.value);
+                for name in declaration.target.names() {
+                    self.declare(name, declaration.kind);
+                }
+            },
+            Statement::Assignment(assignment) => {
+                self.expression(&assignment.value);
+                self.assign(&assignment.target);
+            },
+            // Their names were declared when the block was hoisted.
+            Statement::Function(function) => self.function(function),
+            Statement::Struct(structure) => {
+                for method in &structure.methods {
+                    self.function(method);
+                }
+            },
+            Statement::Enum(_) => {},
+            Statement::Return(ret) => {
+                if self.functions == 0 {
+                    let error = Error::new(
+                        ErrorForm::SyntaxError,
+                        message!("E0001-return-outside-function"),
+                        vec![Label::primary(ret.position.file, ret.position.start..ret.position.end)],
+                        vec![message!("E0001-return-outside-function.note")]
+                    );
+                    self.error.register_error(error);
+                }
+                if let Some(value) = &ret.value {
+                    self.expression(value);
+                }
+            },
+            Statement::Body(body) => {
+                self.scopes.push(HashMap::new());
+                self.resolve(&body.code);
+                self.scopes.pop();
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.resolve_expression(expression))
+    }
+
+    fn resolve_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(_) => {},
+            Expression::Identifier(identifier) => self.variable(identifier, Use::Operand),
+            Expression::BinaryOperation(b) => {
+                self.expression(&b.loperand);
+                self.expression(&b.roperand);
+            },
+            Expression::UnaryOperation(u) => self.expression(&u.operand),
+            Expression::Comparison(c) => {
+                for operand in &c.operands {
+                    self.expression(operand);
+                }
+            },
+            Expression::Tuple(s) | Expression::List(s) => {
+                for element in &s.elements {
+                    self.expression(element);
+                }
+            },
+            Expression::Spread(s) => self.expression(&s.value),
+            Expression::Cast(c) => self.expression(&c.value),
+            Expression::Attribute(a) => self.expression(&a.object),
+            Expression::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+            },
+            Expression::Call(c) => {
+                match &*c.callee {
+                    Expression::Identifier(identifier) => self.variable(identifier, Use::Callee),
+                    callee => self.expression(callee)
+                }
+                for argument in &c.arguments {
+                    self.expression(argument);
+                }
+            },
+            Expression::Lambda(l) => self.function(l),
+            Expression::Construction(c) => {
+                if self.lookup(&c.name.name).is_none() {
+                    self.undeclared(&c.name, message!("E0003-undeclared-struct", name = c.name.name), &[]);
+                }
+                for field in &c.fields {
+                    self.expression(&field.value);
+                }
+            },
+            Expression::Map(m) => {
+                for entry in &m.entries {
+                    self.expression(&entry.key);
+                    self.expression(&entry.value);
+                }
+            },
+            Expression::Match(m) => {
+                self.expression(&m.scrutinee);
+                for arm in &m.arms {
+                    self.arm(arm);
+                }
+                for error in exhaustiveness::check(m, &self.enums) {
+                    self.error.register_error(error);
+                }
+            }
+        }
+    }
+
+    // Each arm gets its own scope holding the names its pattern binds.
+    fn arm(&mut self, arm: &Arm) {
+        self.check_pattern(&arm.pattern);
+
+        let mut scope = HashMap::new();
+        for identifier in arm.pattern.bindings() {
+            if scope.contains_key(&identifier.name) {
+                let first: Binding = scope[&identifier.name];
+                let error = Error::new(
+                    ErrorForm::NameError,
+                    message!("E0003-duplicate-binding", name = identifier.name),
+                    vec![
+                        Label::primary(identifier.position.file, identifier.position.start..identifier.position.end)
+                            .with_message(message!("E0003-duplicate-binding.again")),
+                        Label::secondary(first.position.file, first.position.start..first.position.end)
+                            .with_message(message!("E0003-duplicate-binding.first"))
+                    ],
+                    vec![message!("E0003-duplicate-binding.note")]
+                );
+                self.error.register_error(error);
+                continue;
+            }
+            scope.insert(identifier.name.clone(), Binding { kind: DeclarationKind::Let, position: identifier.position });
+        }
+
+        self.scopes.push(scope);
+        if let Some(guard) = &arm.guard {
+            self.expression(guard);
+        }
+        self.resolve(&arm.body.code);
+        self.scopes.pop();
+    }
+
+    // Every alternative of an `|` pattern has to bind the same names, or the
+    // arm body could see a variable that was never given a value.
+    fn check_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Alternation(alternatives) => {
+                let first = &alternatives.patterns[0];
+                let mut expected = first.bindings().iter().map(|i| i.name.clone()).collect::<Vec<_>>();
+                expected.sort();
+
+                for alternative in &alternatives.patterns[1..] {
+                    let mut names = alternative.bindings().iter().map(|i| i.name.clone()).collect::<Vec<_>>();
+                    names.sort();
+                    if names == expected {
+                        continue;
+                    }
+
+                    let missing = expected.iter().chain(names.iter())
+                        .find(|name| !expected.contains(name) || !names.contains(name))
+                        .expect("the two sets of names differ");
+                    let error = Error::new(
+                        ErrorForm::NameError,
+                        message!("E0003-alternative-bindings", name = missing),
+                        vec![
+                            Label::primary(alternative.position().file, alternative.position().start..alternative.position().end)
+                                .with_message(message!("E0003-alternative-bindings.label", names = describe_names(&names))),
+                            Label::secondary(first.position().file, first.position().start..first.position().end)
+                                .with_message(message!("E0003-alternative-bindings.label", names = describe_names(&expected)))
+                        ],
+                        vec![message!("E0003-alternative-bindings.note")]
+                    );
+                    self.error.register_error(error);
+                }
+
+                for alternative in &alternatives.patterns {
+                    self.check_pattern(alternative);
+                }
+            },
+            Pattern::Tuple(sequence) | Pattern::List(sequence) => {
+                for pattern in &sequence.patterns {
+                    self.check_pattern(pattern);
+                }
+            },
+            Pattern::Variant(variant) => {
+                self.check_variant(variant);
+                for pattern in variant.fields.iter().flat_map(|f| &f.patterns) {
+                    self.check_pattern(pattern);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn check_variant(&mut self, pattern: &VariantPattern) {
+        let enumeration = &pattern.enumeration;
+        if self.lookup(&enumeration.name).is_none() {
+            self.undeclared(enumeration, message!("E0003-undeclared-enum", name = enumeration.name), &[]);
+            return;
+        }
+
+        let shape = match self.enums.get(&enumeration.name) {
+            Some(shape) => shape.clone(),
+            None => {
+                let error = Error::new(
+                    ErrorForm::TypeError,
+                    message!("E0006-not-an-enum", name = enumeration.name),
+                    vec![Label::primary(enumeration.position.file, enumeration.position.start..enumeration.position.end)],
+                    vec![]
+                );
+                self.error.register_error(error);
+                return;
+            }
+        };
+
+        let variant = &pattern.variant;
+        let arity = match shape.variants.iter().find(|(name, _)| *name == variant.name) {
+            Some((_, arity)) => *arity,
+            None => {
+                let suggestions = spelling::suggestions(&variant.name, shape.variants.iter().map(|(name, _)| name.as_str()));
+                let error = Error::new(
+                    ErrorForm::NameError,
+                    message!("E0003-unknown-variant", enumeration = shape.name, name = variant.name),
+                    vec![Label::primary(variant.position.file, variant.position.start..variant.position.end).with_message(message!("E0003-unknown-variant.label"))],
+                    spelling::did_you_mean(&suggestions).into_iter().collect()
+                );
+                self.error.register_error(error);
+                return;
+            }
+        };
+
+        let patterns = pattern.fields.as_ref().map_or(&[][..], |f| &f.patterns[..]);
+        let has_rest = patterns.iter().any(|p| matches!(p, Pattern::Rest(_)));
+        let given = patterns.len() - has_rest as usize;
+        if given == arity || (has_rest && given < arity) {
+            return;
+        }
+
+        let error = Error::new(
+            ErrorForm::TypeError,
+            message!(
+                if arity == 1 { "E0006-variant-arity.one" } else { "E0006-variant-arity" },
+                enumeration = shape.name,
+                variant = variant.name,
+                arity = arity,
+                given = given
+            ),
+            vec![Label::primary(pattern.position.file, pattern.position.start..pattern.position.end)],
+            vec![]
+        );
+        self.error.register_error(error);
+    }
+
+    // Parameters and the body share one scope. Names from enclosing scopes
+    // stay visible, which is what lets closures capture them.
+    fn function(&mut self, function: &Function) {
+        self.functions += 1;
+        let mut scope = HashMap::new();
+        for parameter in function.parameters.iter().map(|p| &p.name) {
+            scope.insert(parameter.name.clone(), Binding { kind: DeclarationKind::Let, position: parameter.position });
+        }
+        self.scopes.push(scope);
+        self.resolve(&function.body.code);
+        self.scopes.pop();
+        self.functions -= 1;
+    }
+
+    fn assign(&mut self, target: &Target) {
+        match target {
+            Target::Identifier(identifier) => self.assign_name(identifier),
+            Target::Rest(rest) => self.assign_name(&rest.name),
+            Target::Tuple(t) | Target::List(t) => {
+                for target in &t.targets {
+                    self.assign(target);
+                }
+            },
+            Target::Attribute(a) => self.expression(&a.object),
+            Target::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+            }
+        }
+    }
+
+    fn assign_name(&mut self, identifier: &Identifier) {
+        match self.lookup(&identifier.name) {
+            None => self.undeclared(identifier, message!("E0003-undeclared-assignment", name = identifier.name), &[]),
+            Some(binding) if binding.kind == DeclarationKind::Const => {
+                let declared = binding.position;
+                let error = Error::new(
+                    ErrorForm::ConstantReassignment,
+                    message!("E0004-constant-reassignment", name = identifier.name),
+                    vec![
+                        Label::primary(identifier.position.file, identifier.position.start..identifier.position.end)
+                            .with_message(message!("E0004-constant-reassignment.assignment")),
+                        Label::secondary(declared.file, declared.start..declared.end)
+                            .with_message(message!("E0004-constant-reassignment.declaration"))
+                    ],
+                    vec![message!("E0004-constant-reassignment.note", name = identifier.name)]
+                );
+                self.error.register_error(error);
+            },
+            Some(_) => {}
+        }
+    }
+
+    fn declare(&mut self, identifier: &Identifier, kind: DeclarationKind) {
+        if identifier.name != "_" {
+            if let Some(previous) = self.lookup(&identifier.name) {
+                let error = Error::new(
+                    ErrorForm::Shadowing,
+                    message!("E0005-shadowing", name = identifier.name),
+                    vec![
+                        Label::primary(identifier.position.file, identifier.position.start..identifier.position.end)
+                            .with_message(message!("E0005-shadowing.new")),
+                        Label::secondary(previous.position.file, previous.position.start..previous.position.end)
+                            .with_message(message!("E0005-shadowing.old"))
+                    ],
+                    vec![message!("E0005-shadowing.note")]
+                );
+                self.error.register_error(error);
+            }
+        }
+
+        let scope = self.scopes.last_mut().expect("the global scope is never popped");
+        scope.insert(identifier.name.clone(), Binding { kind, position: identifier.position });
+    }
+
+    fn lookup(&self, name: &str) -> Option<Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    fn variable(&mut self, identifier: &Identifier, used: Use) {
+        if self.lookup(&identifier.name).is_none() && builtins::get(&identifier.name).is_none() {
+            let keywords = Lexer::keywords().into_iter()
+                .filter(|(_, form)| match used {
+                    Use::Statement => !matches!(form, token!(and) | token!(or)),
+                    Use::Operand => matches!(form, token!(true) | token!(false)),
+                    Use::Callee => false
+                })
+                .map(|(keyword, _)| keyword)
+                .collect::<Vec<_>>();
+            let message = message!("E0003-undeclared-variable", name = identifier.name);
+            self.undeclared(identifier, message, &keywords);
+        }
+    }
+
+    // Misspelt names are suggested from everything in scope, the builtins and
+    // the `keywords` that fit where the name is, each with a fix that swaps
+    // in the suggestion.
+    fn undeclared(&mut self, identifier: &Identifier, message: String, keywords: &[&str]) {
+        let candidates = self.scopes.iter().flat_map(|scope| scope.keys().map(String::as_str))
+            .chain(keywords.iter().copied())
+            .chain(BUILTINS.iter().map(|builtin| builtin.name));
+        let suggestions = spelling::suggestions(&identifier.name, candidates);
+
+        let position = identifier.position;
+        let notes = if suggestions.is_empty() {
+            vec![message!("E0003-undeclared.note", name = identifier.name)]
+        } else {
+            Vec::new()
+        };
+        let mut error = Error::new(
+            ErrorForm::NameError,
+            message,
+            vec![Label::primary(position.file, position.start..position.end)
+                .with_message(message!("E0003-undeclared.label"))],
+            notes
+        );
+        for suggestion in suggestions.iter().take(3) {
+            let message = spelling::did_you_mean(&[suggestion]).expect("there is one suggestion");
+            let fix = Fix::new(message, position.file, position.start..position.end, suggestion, Applicability::MaybeIncorrect);
+            error = error.with_fix(fix);
+        }
+        self.error.register_error(error);
+    }
+}
+
+fn describe_names(names: &[String]) -> String {
+    if names.is_empty() {
+        return message!("E0003-alternative-bindings.nothing");
+    }
+    names.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fmt;
+
+// The type of a value as the checker sees it. A `Variable` stands for a type
+// that hasn't been worked out yet, and the checker keeps track of what each
+// one turns out to be.
+//
+// `Dynamic` is the type of values the checker knows nothing about, like
+// unannotated parameters. It fits anywhere, and anything fits it.
+//
+// A `Parameter` is a type parameter inside the generic function or type that
+// declares it, standing for whatever type each use picks. Only what its
+// bounds allow can be done with it. Parameters are numbered so that two
+// declared with the same name stay apart.
+//
+// Numbers keep the ranks the runtime promotes between, so `int + float` is a
+// `float`. Structs and enums are told apart by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Variable(usize),
+    Dynamic,
+    Parameter(usize, String),
+    Nil,
+    Boolean,
+    Integer,
+    Decimal,
+    Rational,
+    Float,
+    String,
+    Tuple(Vec<Type>),
+    List(Box<Type>),
+    Map(Box<Type>, Box<Type>),
+    Function(Vec<Type>, Box<Type>),
+    // A record made from the struct with this name, given the types for its
+    // type parameters, and the struct itself.
+    Record(String, Vec<Type>),
+    Struct(String),
+    // A value of the enum with this name, given the types for its type
+    // parameters, and the enum itself.
+    Variant(String, Vec<Type>),
+    Enum(String)
+}
+
+// What a type parameter can require of the types it stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    // Numbers of any rank, which arithmetic works on.
+    Number,
+    // Types `<` works on: numbers, strings, tuples and lists.
+    Ordered
+}
+
+impl Bound {
+    pub fn named(name: &str) -> Option<Bound> {
+        match name {
+            "number" => Some(Bound::Number),
+            "ordered" => Some(Bound::Ordered),
+            _ => None
+        }
+    }
+
+    // Whether a type with this bound always satisfies `other` too.
+    pub fn implies(self, other: Bound) -> bool {
+        self == other || (self, other) == (Bound::Number, Bound::Ordered)
+    }
+}
+
+impl fmt::Display for Bound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bound::Number => write!(f, "number"),
+            Bound::Ordered => write!(f, "ordered")
+        }
+    }
+}
+
+impl Type {
+    // Where a number sits among the ranks, lowest first, the same order
+    // `numeric::Rank` promotes in.
+    pub fn rank(&self) -> Option<u8> {
+        match self {
+            Type::Integer => Some(0),
+            Type::Decimal => Some(1),
+            Type::Rational => Some(2),
+            Type::Float => Some(3),
+            _ => None
+        }
+    }
+
+    // Every variable in this type, each once, in the order they appear.
+    pub fn variables(&self) -> Vec<usize> {
+        let mut variables = Vec::new();
+        self.collect(&mut variables);
+        variables
+    }
+
+    fn collect(&self, variables: &mut Vec<usize>) {
+        match self {
+            Type::Variable(v) if !variables.contains(v) => variables.push(*v),
+            Type::Tuple(elements) => elements.iter().for_each(|element| element.collect(variables)),
+            Type::List(element) => element.collect(variables),
+            Type::Map(key, value) => {
+                key.collect(variables);
+                value.collect(variables);
+            },
+            Type::Function(parameters, result) => {
+                parameters.iter().for_each(|parameter| parameter.collect(variables));
+                result.collect(variables);
+            },
+            Type::Record(_, arguments) | Type::Variant(_, arguments) => arguments.iter().for_each(|argument| argument.collect(variables)),
+            _ => {}
+        }
+    }
+
+    pub fn contains(&self, variable: usize) -> bool {
+        self.variables().contains(&variable)
+    }
+
+    // Replaces the variables `replacements` has an entry for.
+    pub fn substitute(&self, replacements: &HashMap<usize, Type>) -> Type {
+        self.replace(&|ty| match ty {
+            Type::Variable(v) => replacements.get(v).cloned(),
+            _ => None
+        })
+    }
+
+    // Replaces the type parameters `replacements` has an entry for.
+    pub fn instantiate(&self, replacements: &HashMap<usize, Type>) -> Type {
+        self.replace(&|ty| match ty {
+            Type::Parameter(p, _) => replacements.get(p).cloned(),
+            _ => None
+        })
+    }
+
+    // Replaces every part of this type `f` gives a replacement for.
+    fn replace(&self, f: &impl Fn(&Type) -> Option<Type>) -> Type {
+        if let Some(replacement) = f(self) {
+            return replacement;
+        }
+        let all = |types: &[Type]| types.iter().map(|ty| ty.replace(f)).collect();
+        match self {
+            Type::Tuple(elements) => Type::Tuple(all(elements)),
+            Type::List(element) => Type::List(Box::new(element.replace(f))),
+            Type::Map(key, value) => Type::Map(Box::new(key.replace(f)), Box::new(value.replace(f))),
+            Type::Function(parameters, result) => Type::Function(all(parameters), Box::new(result.replace(f))),
+            Type::Record(name, arguments) => Type::Record(name.clone(), all(arguments)),
+            Type::Variant(name, arguments) => Type::Variant(name.clone(), all(arguments)),
+            other => other.clone()
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, names: &HashMap<usize, String>) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter<'_>, types: &[Type]| -> fmt::Result {
+            for (i, t) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                t.write(f, names)?;
+            }
+            Ok(())
+        };
+
+        match self {
+            Type::Variable(v) => write!(f, "{}", names.get(v).map(String::as_str).unwrap_or("?")),
+            Type::Dynamic => write!(f, "dynamic"),
+            Type::Parameter(_, name) => write!(f, "{}", name),
+            Type::Nil => write!(f, "nil"),
+            Type::Boolean => write!(f, "bool"),
+            Type::Integer => write!(f, "int"),
+            Type::Decimal => write!(f, "decimal"),
+            Type::Rational => write!(f, "rational"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "str"),
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                list(f, elements)?;
+                write!(f, "{})", if elements.len() == 1 { "," } else { "" })
+            },
+            Type::List(element) => {
+                write!(f, "[")?;
+                element.write(f, names)?;
+                write!(f, "]")
+            },
+            Type::Map(key, value) => {
+                write!(f, "{{")?;
+                key.write(f, names)?;
+                write!(f, ": ")?;
+                value.write(f, names)?;
+                write!(f, "}}")
+            },
+            Type::Function(parameters, result) => {
+                write!(f, "fn(")?;
+                list(f, parameters)?;
+                write!(f, ") -> ")?;
+                result.write(f, names)
+            },
+            Type::Record(name, arguments) | Type::Variant(name, arguments) => {
+                write!(f, "{}", name)?;
+                if !arguments.is_empty() {
+                    write!(f, "<")?;
+                    list(f, arguments)?;
+                    write!(f, ">")?;
+                }
+                Ok(())
+            },
+            Type::Struct(name) => write!(f, "struct {}", name),
+            Type::Enum(name) => write!(f, "enum {}", name)
+        }
+    }
+}
+
+// Writes out several types that appear in one message, naming their
+// variables `a`, `b`, `c` and so on, so a variable shared between them gets
+// the same name in each.
+pub fn describe(types: &[&Type]) -> Vec<String> {
+    let mut names = HashMap::new();
+    for t in types {
+        for variable in t.variables() {
+            let next = names.len();
+            names.entry(variable).or_insert_with(|| name(next));
+        }
+    }
+    types.iter().map(|t| Described(t, &names).to_string()).collect()
+}
+
+fn name(index: usize) -> String {
+    if index < 26 {
+        ((b'a' + index as u8) as char).to_string()
+    } else {
+        format!("t{}", index)
+    }
+}
+
+struct Described<'a>(&'a Type, &'a HashMap<usize, String>);
+
+impl fmt::Display for Described<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, self.1)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", describe(&[self])[0])
+    }
+}
@@ -0,0 +1,624 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+
+use crate::analysis::types::Type;
+use crate::compiler::chunk::*;
+use crate::error::diagnostic::FileId;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+
+// Compiled files are kept next to their source, in a directory like Python's
+// `__pycache__`, so later runs can skip compiling them. A `.langc` file is:
+//
+//     magic      4 bytes, "LNGC"
+//     version    u16, bumped whenever the format or the instruction set changes
+//     sources    u64 hash of the sources it was compiled with
+//     checksum   u64 hash of everything after it
+//     function   the compiled script
+//
+// A file is checked and folded after the files before it, and what it
+// compiles to depends on the names, types and constants they declare, so
+// its compiled form is only reused with the same sources before it too.
+//
+// Numbers are little endian, lengths and counts are u32 and strings are UTF-8
+// with their length in front. A position can point into one of the files
+// before, like the annotation a cast checks against, so its file is stored
+// as how many files before the compiled one it is. Anything that doesn't
+// check out is treated as a miss, and the file is compiled again.
+const MAGIC: &[u8; 4] = b"LNGC";
+pub const VERSION: u16 = 6;
+const DIRECTORY: &str = "__langcache__";
+const HEADER: usize = 4 + 2 + 8 + 8;
+
+// Where the compiled form of the source file at `path` is kept.
+pub fn cache_path(path: &Path) -> PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(DIRECTORY).join(format!("{}.langc", name))
+}
+
+// The compiled form of the file `file`, if there is an up to date one in the
+// cache. `sources` are the sources of every file checked so far, in order and
+// ending with this one, which is what the file's ids in this run count.
+pub fn load(path: &Path, sources: &[&str], file: FileId) -> Option<Rc<Function>> {
+    let bytes = fs::read(cache_path(path)).ok()?;
+    decode(&bytes, sources, file).map(Rc::new)
+}
+
+// Keeps `function`, compiled from the last of `sources`, in the cache. A cache
+// that can't be written just means compiling again next time, so failures
+// are ignored.
+pub fn store(path: &Path, sources: &[&str], file: FileId, function: &Function) {
+    let cache = cache_path(path);
+    if let Some(directory) = cache.parent() {
+        let _ = fs::create_dir_all(directory);
+    }
+    let _ = fs::write(cache, encode(function, sources, file));
+}
+
+pub fn encode(function: &Function, sources: &[&str], file: FileId) -> Vec<u8> {
+    let mut body = Writer { bytes: Vec::new(), file };
+    body.function(function);
+
+    let mut bytes = Vec::with_capacity(HEADER + body.bytes.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&key(sources).to_le_bytes());
+    bytes.extend_from_slice(&hash(&body.bytes).to_le_bytes());
+    bytes.extend(body.bytes);
+    bytes
+}
+
+// Reads back what `encode` wrote, or nothing if it was written by another
+// version, for other sources, or has been damaged since.
+pub fn decode(bytes: &[u8], sources: &[&str], file: FileId) -> Option<Function> {
+    let mut header = Reader { bytes: bytes.get(..HEADER)?, at: 0, file, files: sources.len() };
+    if header.take(4)? != MAGIC || header.u16()? != VERSION || header.u64()? != key(sources) {
+        return None;
+    }
+    let checksum = header.u64()?;
+
+    let body = &bytes[HEADER..];
+    if hash(body) != checksum {
+        return None;
+    }
+    let mut reader = Reader { bytes: body, at: 0, file, files: sources.len() };
+    let function = reader.function()?;
+    if reader.at != body.len() {
+        return None;
+    }
+    Some(function)
+}
+
+// A hash of every source in order, each with its length in front so that
+// moving text from one file to the next changes it.
+fn key(sources: &[&str]) -> u64 {
+    let mut bytes = Vec::new();
+    for source in sources {
+        bytes.extend_from_slice(&(source.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(source.as_bytes());
+    }
+    hash(&bytes)
+}
+
+// 64-bit FNV-1a, which is enough to notice a changed source or a damaged
+// file, and unlike `DefaultHasher` gives the same answer on every build.
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+    // The file being compiled, which positions are stored relative to.
+    file: FileId
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn length(&mut self, length: usize) {
+        self.u32(u32::try_from(length).expect("nothing in a chunk has 2^32 parts"));
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.length(bytes.len());
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    fn strings(&mut self, strings: &[String]) {
+        self.length(strings.len());
+        strings.iter().for_each(|string| self.string(string));
+    }
+
+    fn position(&mut self, position: Position) {
+        self.length(self.file.checked_sub(position.file).expect("positions only point into files checked before"));
+        for part in &[position.start, position.end, position.line_number, position.column_number] {
+            self.u32(u32::try_from(*part).expect("source files are shorter than 4GB"));
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        match &function.name {
+            Some(name) => {
+                self.u8(1);
+                self.string(name);
+            },
+            None => self.u8(0)
+        }
+        self.strings(&function.parameters);
+        self.u16(function.locals);
+        self.u16(function.cells);
+        self.length(function.captures.len());
+        for capture in &function.captures {
+            match capture {
+                Capture::Cell(cell) => {
+                    self.u8(0);
+                    self.u16(*cell);
+                },
+                Capture::Capture(index) => {
+                    self.u8(1);
+                    self.u16(*index);
+                }
+            }
+        }
+        self.position(function.position);
+        self.chunk(&function.chunk);
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.bytes(&chunk.code);
+        self.length(chunk.constants.len());
+        chunk.constants.iter().for_each(|constant| self.constant(constant));
+        self.length(chunk.lines.len());
+        for &(offset, position) in &chunk.lines {
+            self.u32(offset);
+            self.position(position);
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Literal(form) => {
+                self.u8(0);
+                self.form(form);
+            },
+            Constant::Name(name) => {
+                self.u8(1);
+                self.string(name);
+            },
+            Constant::Function(function) => {
+                self.u8(2);
+                self.function(function);
+            },
+            Constant::Struct(layout) => {
+                self.u8(3);
+                self.string(&layout.name);
+                self.strings(&layout.fields);
+                self.strings(&layout.methods);
+                self.position(layout.position);
+            },
+            Constant::Enum(layout) => {
+                self.u8(4);
+                self.string(&layout.name);
+                self.length(layout.variants.len());
+                for (variant, fields) in &layout.variants {
+                    self.string(variant);
+                    self.strings(fields);
+                }
+                self.position(layout.position);
+            },
+            Constant::Construction(layout) => {
+                self.u8(5);
+                self.string(&layout.name);
+                self.position(layout.name_position);
+                self.length(layout.fields.len());
+                for (field, position) in &layout.fields {
+                    self.string(field);
+                    self.position(*position);
+                }
+            },
+            Constant::Pattern(pattern) => {
+                self.u8(6);
+                self.pattern(pattern);
+            },
+            Constant::Cast(layout) => {
+                self.u8(7);
+                self.ty(&layout.ty);
+                self.position(layout.declared);
+                match layout.origin {
+                    Some(origin) => {
+                        self.u8(1);
+                        self.position(origin);
+                    },
+                    None => self.u8(0)
+                }
+            }
+        }
+    }
+
+    fn form(&mut self, form: &Form) {
+        match form {
+            Form::Integer(integer) => {
+                self.u8(0);
+                self.u64(*integer as u64);
+            },
+            Form::BigInteger(integer) => {
+                self.u8(1);
+                self.bytes(&integer.to_signed_bytes_le());
+            },
+            Form::Decimal(decimal) => {
+                self.u8(2);
+                self.string(&decimal.to_string());
+            },
+            Form::Float(float) => {
+                self.u8(3);
+                self.u64(float.to_bits());
+            },
+            Form::String(string) => {
+                self.u8(4);
+                self.string(string);
+            },
+            Form::Boolean(boolean) => {
+                self.u8(5);
+                self.u8(*boolean as u8);
+            }
+        }
+    }
+
+    fn types(&mut self, types: &[Type]) {
+        self.length(types.len());
+        types.iter().for_each(|ty| self.ty(ty));
+    }
+
+    fn ty(&mut self, ty: &Type) {
+        match ty {
+            Type::Variable(variable) => {
+                self.u8(0);
+                self.length(*variable);
+            },
+            Type::Dynamic => self.u8(1),
+            Type::Nil => self.u8(2),
+            Type::Boolean => self.u8(3),
+            Type::Integer => self.u8(4),
+            Type::Decimal => self.u8(5),
+            Type::Rational => self.u8(6),
+            Type::Float => self.u8(7),
+            Type::String => self.u8(8),
+            Type::Tuple(elements) => {
+                self.u8(9);
+                self.types(elements);
+            },
+            Type::List(element) => {
+                self.u8(10);
+                self.ty(element);
+            },
+            Type::Map(key, value) => {
+                self.u8(11);
+                self.ty(key);
+                self.ty(value);
+            },
+            Type::Function(parameters, result) => {
+                self.u8(12);
+                self.types(parameters);
+                self.ty(result);
+            },
+            Type::Record(name, arguments) => {
+                self.u8(13);
+                self.string(name);
+                self.types(arguments);
+            },
+            Type::Struct(name) => {
+                self.u8(14);
+                self.string(name);
+            },
+            Type::Variant(name, arguments) => {
+                self.u8(15);
+                self.string(name);
+                self.types(arguments);
+            },
+            Type::Enum(name) => {
+                self.u8(16);
+                self.string(name);
+            },
+            Type::Parameter(parameter, name) => {
+                self.u8(17);
+                self.length(*parameter);
+                self.string(name);
+            }
+        }
+    }
+
+    fn slot(&mut self, slot: Slot) {
+        match slot {
+            Slot::Local(slot) => {
+                self.u8(0);
+                self.u16(slot);
+            },
+            Slot::Cell(cell) => {
+                self.u8(1);
+                self.u16(cell);
+            }
+        }
+    }
+
+    fn patterns(&mut self, patterns: &[Pattern]) {
+        self.length(patterns.len());
+        patterns.iter().for_each(|pattern| self.pattern(pattern));
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Wildcard => self.u8(0),
+            Pattern::Binding(slot) => {
+                self.u8(1);
+                self.slot(*slot);
+            },
+            Pattern::Literal(form) => {
+                self.u8(2);
+                self.form(form);
+            },
+            Pattern::Range(start, end, inclusive) => {
+                self.u8(3);
+                self.form(start);
+                self.form(end);
+                self.u8(*inclusive as u8);
+            },
+            Pattern::Tuple(patterns) => {
+                self.u8(4);
+                self.patterns(patterns);
+            },
+            Pattern::List(patterns) => {
+                self.u8(5);
+                self.patterns(patterns);
+            },
+            Pattern::Rest(slot) => {
+                self.u8(6);
+                match slot {
+                    Some(slot) => {
+                        self.u8(1);
+                        self.slot(*slot);
+                    },
+                    None => self.u8(0)
+                }
+            },
+            Pattern::Alternation(patterns) => {
+                self.u8(7);
+                self.patterns(patterns);
+            },
+            Pattern::Variant { enumeration, variant, fields } => {
+                self.u8(8);
+                self.string(enumeration);
+                self.string(variant);
+                match fields {
+                    Some(fields) => {
+                        self.u8(1);
+                        self.patterns(fields);
+                    },
+                    None => self.u8(0)
+                }
+            }
+        }
+    }
+}
+
+// Every read gives nothing instead of panicking when the bytes run out or
+// don't make sense, since a cache file can hold anything.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    file: FileId,
+    // How many files were checked up to the one being loaded.
+    files: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self.bytes.get(self.at..self.at.checked_add(length)?)?;
+        self.at += length;
+        Some(bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(<[u8; 2]>::try_from(self.take(2)?).ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(<[u8; 4]>::try_from(self.take(4)?).ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(<[u8; 8]>::try_from(self.take(8)?).ok()?))
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        match self.u8()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None
+        }
+    }
+
+    // Reads `count` of something, checking first that there are at least
+    // that many bytes left so a damaged count can't allocate too much.
+    fn many<T>(&mut self, read: impl Fn(&mut Self) -> Option<T>) -> Option<Vec<T>> {
+        let count = self.u32()? as usize;
+        if count > self.bytes.len() - self.at {
+            return None;
+        }
+        (0..count).map(|_| read(self)).collect()
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let length = self.u32()? as usize;
+        self.take(length)
+    }
+
+    fn string(&mut self) -> Option<String> {
+        String::from_utf8(self.bytes()?.to_vec()).ok()
+    }
+
+    fn strings(&mut self) -> Option<Vec<String>> {
+        self.many(Self::string)
+    }
+
+    fn position(&mut self) -> Option<Position> {
+        let back = self.u32()? as usize;
+        if back >= self.files {
+            return None;
+        }
+        Some(Position {
+            file: self.file.checked_sub(back)?,
+            start: self.u32()? as usize,
+            end: self.u32()? as usize,
+            line_number: self.u32()? as usize,
+            column_number: self.u32()? as usize
+        })
+    }
+
+    fn function(&mut self) -> Option<Function> {
+        let name = if self.flag()? { Some(self.string()?) } else { None };
+        let parameters = self.strings()?;
+        let locals = self.u16()?;
+        let cells = self.u16()?;
+        let captures = self.many(|reader| match reader.u8()? {
+            0 => Some(Capture::Cell(reader.u16()?)),
+            1 => Some(Capture::Capture(reader.u16()?)),
+            _ => None
+        })?;
+        let position = self.position()?;
+        let chunk = self.chunk()?;
+        Some(Function { name, parameters, chunk, locals, cells, captures, position })
+    }
+
+    fn chunk(&mut self) -> Option<Chunk> {
+        let code = self.bytes()?.to_vec();
+        let constants = self.many(Self::constant)?;
+        let lines = self.many(|reader| Some((reader.u32()?, reader.position()?)))?;
+        Some(Chunk { code, constants, lines })
+    }
+
+    fn constant(&mut self) -> Option<Constant> {
+        Some(match self.u8()? {
+            0 => Constant::Literal(self.form()?),
+            1 => Constant::Name(self.string()?),
+            2 => Constant::Function(Rc::new(self.function()?)),
+            3 => Constant::Struct(StructLayout {
+                name: self.string()?,
+                fields: self.strings()?,
+                methods: self.strings()?,
+                position: self.position()?
+            }),
+            4 => Constant::Enum(EnumLayout {
+                name: self.string()?,
+                variants: self.many(|reader| Some((reader.string()?, reader.strings()?)))?,
+                position: self.position()?
+            }),
+            5 => Constant::Construction(ConstructionLayout {
+                name: self.string()?,
+                name_position: self.position()?,
+                fields: self.many(|reader| Some((reader.string()?, reader.position()?)))?
+            }),
+            6 => Constant::Pattern(self.pattern()?),
+            7 => Constant::Cast(CastLayout {
+                ty: self.ty()?,
+                declared: self.position()?,
+                origin: if self.flag()? { Some(self.position()?) } else { None }
+            }),
+            _ => return None
+        })
+    }
+
+    fn form(&mut self) -> Option<Form> {
+        Some(match self.u8()? {
+            0 => Form::Integer(self.u64()? as i64),
+            1 => Form::BigInteger(BigInt::from_signed_bytes_le(self.bytes()?)),
+            2 => Form::Decimal(BigDecimal::from_str(&self.string()?).ok()?),
+            3 => Form::Float(f64::from_bits(self.u64()?)),
+            4 => Form::String(self.string()?),
+            5 => Form::Boolean(self.flag()?),
+            _ => return None
+        })
+    }
+
+    fn ty(&mut self) -> Option<Type> {
+        Some(match self.u8()? {
+            0 => Type::Variable(self.u32()? as usize),
+            1 => Type::Dynamic,
+            2 => Type::Nil,
+            3 => Type::Boolean,
+            4 => Type::Integer,
+            5 => Type::Decimal,
+            6 => Type::Rational,
+            7 => Type::Float,
+            8 => Type::String,
+            9 => Type::Tuple(self.many(Self::ty)?),
+            10 => Type::List(Box::new(self.ty()?)),
+            11 => Type::Map(Box::new(self.ty()?), Box::new(self.ty()?)),
+            12 => Type::Function(self.many(Self::ty)?, Box::new(self.ty()?)),
+            13 => Type::Record(self.string()?, self.many(Self::ty)?),
+            14 => Type::Struct(self.string()?),
+            15 => Type::Variant(self.string()?, self.many(Self::ty)?),
+            16 => Type::Enum(self.string()?),
+            17 => Type::Parameter(self.u32()? as usize, self.string()?),
+            _ => return None
+        })
+    }
+
+    fn slot(&mut self) -> Option<Slot> {
+        match self.u8()? {
+            0 => Some(Slot::Local(self.u16()?)),
+            1 => Some(Slot::Cell(self.u16()?)),
+            _ => None
+        }
+    }
+
+    fn patterns(&mut self) -> Option<Vec<Pattern>> {
+        self.many(Self::pattern)
+    }
+
+    fn pattern(&mut self) -> Option<Pattern> {
+        Some(match self.u8()? {
+            0 => Pattern::Wildcard,
+            1 => Pattern::Binding(self.slot()?),
+            2 => Pattern::Literal(self.form()?),
+            3 => Pattern::Range(self.form()?, self.form()?, self.flag()?),
+            4 => Pattern::Tuple(self.patterns()?),
+            5 => Pattern::List(self.patterns()?),
+            6 => Pattern::Rest(if self.flag()? { Some(self.slot()?) } else { None }),
+            7 => Pattern::Alternation(self.patterns()?),
+            8 => Pattern::Variant {
+                enumeration: self.string()?,
+                variant: self.string()?,
+                fields: if self.flag()? { Some(self.patterns()?) } else { None }
+            },
+            _ => return None
+        })
+    }
+}
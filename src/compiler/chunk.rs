@@ -0,0 +1,391 @@
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::analysis::types::Type;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::parser::token::TokenType;
+use crate::token;
+
+// One instruction. Operands follow the opcode in the code: slots, cells,
+// captures and counts are two bytes, constants and jump targets four, all
+// little endian. The comment on each says what it takes from the stack and
+// what it leaves there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Op {
+    // constant -> value
+    Constant,
+    Nil,
+    True,
+    False,
+    // value ->
+    Pop,
+
+    // slot -> value
+    GetLocal,
+    // slot: value ->
+    SetLocal,
+    // The cells of the running frame, holding locals that closures capture.
+    GetCell,
+    SetCell,
+    // The cells the running closure captured.
+    GetCapture,
+    SetCapture,
+    // name constant -> value
+    GetGlobal,
+    // name constant: value ->
+    DefineGlobal,
+    SetGlobal,
+
+    // left, right -> result
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    // operand -> result
+    Negate,
+    Not,
+    Complement,
+
+    // target: always
+    Jump,
+    // target: condition ->
+    JumpIfFalse,
+    // target: condition -> condition, if it jumps, and nothing otherwise
+    JumpIfFalseOrPop,
+    JumpIfTrueOrPop,
+
+    // count: elements.. -> tuple
+    Tuple,
+    // count: elements.. -> list
+    List,
+    // tuple -> list
+    ToList,
+    // tuple, value -> tuple with value at the end
+    Append,
+    // tuple, sequence -> tuple with the sequence's elements at the end
+    Extend,
+    // -> empty map
+    Map,
+    // map, key, value -> map with the entry
+    Insert,
+    // object, index -> element
+    Index,
+    // object, index -> object, index, after checking a map could take the
+    // index as a key, so that error points at the index alone
+    Key,
+    // value, object, index ->
+    StoreIndex,
+    // name constant: object -> attribute
+    Attribute,
+    // name constant: value, object ->
+    StoreAttribute,
+    // count, rest: sequence -> its parts, last first
+    Unpack,
+    // cast constant: value -> value, after checking it has the type
+    Cast,
+
+    // count: callee, arguments.. -> result
+    Call,
+    // callee, tuple of arguments -> result
+    CallSpread,
+    // function constant -> closure
+    Closure,
+    // struct constant: methods.. -> struct
+    Struct,
+    // enum constant -> enum
+    Enum,
+    // construction constant: struct, field values.. -> record
+    Construct,
+    // pattern constant: value -> whether it matched, binding its names if it did
+    Match,
+    // value -> never returns
+    NoMatch,
+    // value ->
+    Return
+}
+
+// Every opcode in order, for decoding.
+const OPS: &[Op] = &[
+    Op::Constant, Op::Nil, Op::True, Op::False, Op::Pop,
+    Op::GetLocal, Op::SetLocal, Op::GetCell, Op::SetCell, Op::GetCapture, Op::SetCapture,
+    Op::GetGlobal, Op::DefineGlobal, Op::SetGlobal,
+    Op::Add, Op::Subtract, Op::Multiply, Op::Divide, Op::Modulo, Op::Power,
+    Op::BitAnd, Op::BitOr, Op::BitXor, Op::ShiftLeft, Op::ShiftRight,
+    Op::Equal, Op::NotEqual, Op::Less, Op::LessEqual, Op::Greater, Op::GreaterEqual,
+    Op::Negate, Op::Not, Op::Complement,
+    Op::Jump, Op::JumpIfFalse, Op::JumpIfFalseOrPop, Op::JumpIfTrueOrPop,
+    Op::Tuple, Op::List, Op::ToList, Op::Append, Op::Extend, Op::Map, Op::Insert,
+    Op::Index, Op::Key, Op::StoreIndex, Op::Attribute, Op::StoreAttribute, Op::Unpack, Op::Cast,
+    Op::Call, Op::CallSpread, Op::Closure, Op::Struct, Op::Enum, Op::Construct,
+    Op::Match, Op::NoMatch, Op::Return
+];
+
+// The kinds of operand an instruction can have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    Short,
+    Constant,
+    Jump
+}
+
+impl Operand {
+    pub fn size(self) -> usize {
+        match self {
+            Operand::Short => 2,
+            Operand::Constant | Operand::Jump => 4
+        }
+    }
+}
+
+impl Op {
+    pub fn decode(byte: u8) -> Option<Op> {
+        OPS.get(byte as usize).copied()
+    }
+
+    pub fn operands(self) -> &'static [Operand] {
+        match self {
+            Op::Constant | Op::GetGlobal | Op::DefineGlobal | Op::SetGlobal | Op::Attribute | Op::StoreAttribute
+                | Op::Closure | Op::Struct | Op::Enum | Op::Construct | Op::Match | Op::Cast => &[Operand::Constant],
+            Op::GetLocal | Op::SetLocal | Op::GetCell | Op::SetCell | Op::GetCapture | Op::SetCapture
+                | Op::Tuple | Op::List | Op::Call => &[Operand::Short],
+            Op::Unpack => &[Operand::Short, Operand::Short],
+            Op::Jump | Op::JumpIfFalse | Op::JumpIfFalseOrPop | Op::JumpIfTrueOrPop => &[Operand::Jump],
+            _ => &[]
+        }
+    }
+
+    // The number of bytes the instruction takes, opcode included.
+    pub fn size(self) -> usize {
+        1 + self.operands().iter().map(|operand| operand.size()).sum::<usize>()
+    }
+
+    pub fn binary(operator: TokenType) -> Op {
+        match operator {
+            token!(+) => Op::Add,
+            token!(-) => Op::Subtract,
+            token!(*) => Op::Multiply,
+            token!(/) => Op::Divide,
+            token!(%) => Op::Modulo,
+            token!(^) => Op::Power,
+            token!(&) => Op::BitAnd,
+            token!(|) => Op::BitOr,
+            token!(@) => Op::BitXor,
+            token!(<<) => Op::ShiftLeft,
+            token!(>>) => Op::ShiftRight,
+            token!(==) => Op::Equal,
+            token!(!=) => Op::NotEqual,
+            token!(<) => Op::Less,
+            token!(<=) => Op::LessEqual,
+            token!(>) => Op::Greater,
+            token!(>=) => Op::GreaterEqual,
+            _ => unreachable!("the parser only makes binary operations of binary operators")
+        }
+    }
+
+    pub fn unary(operator: TokenType) -> Op {
+        match operator {
+            token!(-) => Op::Negate,
+            token!(not) => Op::Not,
+            token!(~) => Op::Complement,
+            _ => unreachable!("the parser only makes unary operations of unary operators")
+        }
+    }
+
+    // The operator an arithmetic, comparison or unary instruction performs.
+    pub fn operator(self) -> Option<TokenType> {
+        Some(match self {
+            Op::Add => token!(+),
+            Op::Subtract | Op::Negate => token!(-),
+            Op::Multiply => token!(*),
+            Op::Divide => token!(/),
+            Op::Modulo => token!(%),
+            Op::Power => token!(^),
+            Op::BitAnd => token!(&),
+            Op::BitOr => token!(|),
+            Op::BitXor => token!(@),
+            Op::ShiftLeft => token!(<<),
+            Op::ShiftRight => token!(>>),
+            Op::Equal => token!(==),
+            Op::NotEqual => token!(!=),
+            Op::Less => token!(<),
+            Op::LessEqual => token!(<=),
+            Op::Greater => token!(>),
+            Op::GreaterEqual => token!(>=),
+            Op::Not => token!(not),
+            Op::Complement => token!(~),
+            _ => return None
+        })
+    }
+}
+
+// Everything an instruction can refer to that doesn't fit in an operand.
+#[derive(Debug)]
+pub enum Constant {
+    Literal(Form),
+    // A global, attribute or field name.
+    Name(String),
+    Function(Rc<Function>),
+    Struct(StructLayout),
+    Enum(EnumLayout),
+    Construction(ConstructionLayout),
+    Pattern(Pattern),
+    Cast(CastLayout)
+}
+
+// A `struct` declaration. Its methods are on the stack as closures, in the
+// same order as their names here.
+#[derive(Debug)]
+pub struct StructLayout {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: Vec<String>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct EnumLayout {
+    pub name: String,
+    pub variants: Vec<(String, Vec<String>)>,
+    pub position: Position
+}
+
+// The names in `Name { field: value, .. }`, with their spans for errors.
+#[derive(Debug)]
+pub struct ConstructionLayout {
+    pub name: String,
+    pub name_position: Position,
+    pub fields: Vec<(String, Position)>
+}
+
+// A runtime check the type checker put where a value without a known type
+// meets code that expects `ty`, which `declared` asked for. `origin` is where
+// the variable being cast got its value, if it is one.
+#[derive(Debug)]
+pub struct CastLayout {
+    pub ty: Type,
+    pub declared: Position,
+    pub origin: Option<Position>
+}
+
+// A compiled `match` pattern. Names are already resolved to where their
+// values go.
+#[derive(Debug)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Slot),
+    Literal(Form),
+    Range(Form, Form, bool),
+    Tuple(Vec<Pattern>),
+    List(Vec<Pattern>),
+    Rest(Option<Slot>),
+    Alternation(Vec<Pattern>),
+    Variant {
+        enumeration: String,
+        variant: String,
+        fields: Option<Vec<Pattern>>
+    }
+}
+
+// Where a pattern puts a value it binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slot {
+    Local(u16),
+    Cell(u16)
+}
+
+// Where a closure gets each variable it captures from, in the frame that
+// makes it: one of that frame's cells, or one of its own captures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capture {
+    Cell(u16),
+    Capture(u16)
+}
+
+// A compiled function, or a whole file. Running it needs `locals` slots and
+// `cells` fresh cells, and the first slots hold the arguments.
+#[derive(Debug)]
+pub struct Function {
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub chunk: Chunk,
+    pub locals: u16,
+    pub cells: u16,
+    pub captures: Vec<Capture>,
+    pub position: Position
+}
+
+// Bytecode with its constants. The line table maps each instruction back to
+// the span of source it came from: an entry is added whenever the span
+// changes, and an instruction belongs to the last entry at or before it.
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<u8>,
+    pub constants: Vec<Constant>,
+    pub lines: Vec<(u32, Position)>
+}
+
+impl Chunk {
+    pub fn new() -> Chunk {
+        Chunk::default()
+    }
+
+    pub fn emit(&mut self, op: Op, position: Position) {
+        let offset = self.offset();
+        if self.lines.last().is_none_or(|&(_, last)| last != position) {
+            self.lines.push((offset, position));
+        }
+        self.code.push(op as u8);
+    }
+
+    pub fn short(&mut self, operand: u16) {
+        self.code.extend_from_slice(&operand.to_le_bytes());
+    }
+
+    pub fn long(&mut self, operand: u32) {
+        self.code.extend_from_slice(&operand.to_le_bytes());
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> u32 {
+        self.constants.push(constant);
+        u32::try_from(self.constants.len() - 1).expect("a chunk has fewer than 2^32 constants")
+    }
+
+    pub fn offset(&self) -> u32 {
+        u32::try_from(self.code.len()).expect("a chunk is shorter than 4GB")
+    }
+
+    // Points the jump whose operand starts at `operand` at `target`.
+    pub fn patch(&mut self, operand: u32, target: u32) {
+        let operand = operand as usize;
+        self.code[operand..operand + 4].copy_from_slice(&target.to_le_bytes());
+    }
+
+    pub fn read_short(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    pub fn read_long(&self, offset: usize) -> u32 {
+        u32::from_le_bytes([self.code[offset], self.code[offset + 1], self.code[offset + 2], self.code[offset + 3]])
+    }
+
+    // The span the instruction at `offset` was compiled from.
+    pub fn position(&self, offset: usize) -> Position {
+        let entry = self.lines.partition_point(|&(start, _)| start as usize <= offset);
+        self.lines[entry.saturating_sub(1)].1
+    }
+}
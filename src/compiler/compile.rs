@@ -0,0 +1,755 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+
+use crate::compiler::chunk::*;
+use crate::error::diagnostic::{Error, ErrorForm, Errors};
+use crate::interpreter::access;
+use crate::message;
+use crate::parser::ast::{self, AST, Assignment, Comparison, Declaration, Expression, Identifier, Match, PatternSequence, Statement, Target};
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::token;
+
+// Where a name's value lives while a function runs.
+#[derive(Debug, Clone, Copy)]
+enum Place {
+    Slot(Slot),
+    Capture(u16),
+    Global
+}
+
+// A function being compiled. The script a file compiles to is one too, with
+// no scopes at the start: names declared outside every block are globals.
+struct State {
+    name: Option<String>,
+    parameters: Vec<String>,
+    position: Position,
+    chunk: Chunk,
+    scopes: Vec<HashMap<String, Slot>>,
+    locals: usize,
+    cells: usize,
+    captures: Vec<(String, Capture)>,
+    names: HashMap<String, u32>,
+    // Names used inside the functions nested in this one. Locals with these
+    // names are kept in cells, since a closure might capture them.
+    captured: HashSet<String>,
+    // Whether the function needs more slots, captures or unpacked names than
+    // an instruction can number.
+    overflowed: bool
+}
+
+// Lowers a resolved program to bytecode for the VM. Names are resolved to
+// slots, cells and captures here, so the VM only looks names up for globals.
+//
+// Locals live in numbered slots in their frame. A local that an inner
+// function uses lives in a cell instead, which closures share with the frame,
+// so closures still capture variables by reference. Declaring a name again
+// in the same scope reuses its slot, like redefining it in an environment.
+pub struct Compiler {
+    functions: Vec<State>,
+    pub error: Errors
+}
+
+impl Default for Compiler {
+    fn default() -> Compiler {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    pub fn new() -> Compiler {
+        Compiler {
+            functions: Vec::new(),
+            error: Errors::new()
+        }
+    }
+
+    // Compiles a file to a function of no parameters that runs it, giving
+    // back the value of its last statement.
+    pub fn compile(&mut self, code: &[AST]) -> Rc<Function> {
+        let position = match (code.first(), code.last()) {
+            (Some(first), Some(last)) => first.position().merge(last.position()),
+            _ => Position::default()
+        };
+
+        self.functions.push(State::new(None, vec![], position, captured(code)));
+        self.block(code, position);
+        self.emit(Op::Return, position);
+        Rc::new(self.finish(position))
+    }
+
+    // Finishes the innermost function, reporting it if it's too big for the
+    // VM's instructions to number everything in it.
+    fn finish(&mut self, position: Position) -> Function {
+        let (function, fits) = self.functions.pop().expect("a function is being compiled").finish();
+        if !fits {
+            let label = Label::primary(position.file, position.start..position.end).with_message(message!("E0012-too-big.label"));
+            self.error.register_error(Error::new(ErrorForm::Limit, message!("E0012-too-big", limit = u16::MAX), vec![label], vec![]));
+        }
+        function
+    }
+
+    fn state(&mut self) -> &mut State {
+        self.functions.last_mut().expect("something is being compiled")
+    }
+
+    fn emit(&mut self, op: Op, position: Position) {
+        self.state().chunk.emit(op, position);
+    }
+
+    fn emit_short(&mut self, op: Op, operand: usize, position: Position) {
+        self.emit(op, position);
+        let operand = self.state().short(operand);
+        self.state().chunk.short(operand);
+    }
+
+    fn emit_constant(&mut self, op: Op, constant: Constant, position: Position) {
+        let index = self.state().chunk.add_constant(constant);
+        self.emit(op, position);
+        self.state().chunk.long(index);
+    }
+
+    fn emit_name(&mut self, op: Op, name: &str, position: Position) {
+        let state = self.state();
+        let index = match state.names.get(name) {
+            Some(&index) => index,
+            None => {
+                let index = state.chunk.add_constant(Constant::Name(name.to_string()));
+                state.names.insert(name.to_string(), index);
+                index
+            }
+        };
+        self.emit(op, position);
+        self.state().chunk.long(index);
+    }
+
+    // Emits a jump to be pointed somewhere later with `patch`.
+    fn emit_jump(&mut self, op: Op, position: Position) -> u32 {
+        self.emit(op, position);
+        let operand = self.state().chunk.offset();
+        self.state().chunk.long(u32::MAX);
+        operand
+    }
+
+    fn patch(&mut self, jump: u32) {
+        let target = self.state().chunk.offset();
+        self.state().chunk.patch(jump, target);
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.state().scopes.pop();
+    }
+
+    // A slot for a value the compiler needs to keep for a while.
+    fn temporary(&mut self) -> usize {
+        let state = self.state();
+        state.locals += 1;
+        state.locals - 1
+    }
+
+    fn declare(&mut self, name: &str) -> Place {
+        let state = self.state();
+        let captured = state.captured.contains(name);
+        match state.scopes.last().map(|scope| scope.get(name)) {
+            Some(Some(&slot)) => return Place::Slot(slot),
+            Some(None) => {},
+            None => return Place::Global
+        }
+
+        let slot = if captured {
+            state.cells += 1;
+            Slot::Cell(state.short(state.cells - 1))
+        } else {
+            state.locals += 1;
+            Slot::Local(state.short(state.locals - 1))
+        };
+        state.scopes.last_mut().expect("there is a scope to declare it in").insert(name.to_string(), slot);
+        Place::Slot(slot)
+    }
+
+    fn resolve(&mut self, name: &str) -> Place {
+        self.resolve_in(self.functions.len() - 1, name)
+    }
+
+    // Looks for a name in the function at `depth` and then the ones around
+    // it, capturing it into each function in between.
+    fn resolve_in(&mut self, depth: usize, name: &str) -> Place {
+        let state = &self.functions[depth];
+        if let Some(&slot) = state.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Place::Slot(slot);
+        }
+        if let Some(index) = state.captures.iter().position(|(captured, _)| captured == name) {
+            return Place::Capture(self.functions[depth].short(index));
+        }
+        if depth == 0 {
+            return Place::Global;
+        }
+
+        let capture = match self.resolve_in(depth - 1, name) {
+            Place::Slot(Slot::Cell(cell)) => Capture::Cell(cell),
+            Place::Capture(index) => Capture::Capture(index),
+            Place::Slot(Slot::Local(_)) => unreachable!("locals that inner functions use are kept in cells"),
+            Place::Global => return Place::Global
+        };
+        let state = &mut self.functions[depth];
+        state.captures.push((name.to_string(), capture));
+        Place::Capture(state.short(state.captures.len() - 1))
+    }
+
+    fn load(&mut self, place: Place, name: &str, position: Position) {
+        match place {
+            Place::Slot(Slot::Local(slot)) => self.emit_short(Op::GetLocal, slot as usize, position),
+            Place::Slot(Slot::Cell(cell)) => self.emit_short(Op::GetCell, cell as usize, position),
+            Place::Capture(index) => self.emit_short(Op::GetCapture, index as usize, position),
+            Place::Global => self.emit_name(Op::GetGlobal, name, position)
+        }
+    }
+
+    fn store(&mut self, place: Place, name: &str, declare: bool, position: Position) {
+        match place {
+            Place::Slot(Slot::Local(slot)) => self.emit_short(Op::SetLocal, slot as usize, position),
+            Place::Slot(Slot::Cell(cell)) => self.emit_short(Op::SetCell, cell as usize, position),
+            Place::Capture(index) => self.emit_short(Op::SetCapture, index as usize, position),
+            Place::Global if declare => self.emit_name(Op::DefineGlobal, name, position),
+            Place::Global => self.emit_name(Op::SetGlobal, name, position)
+        }
+    }
+
+    // Compiles statements so they leave the value of the last one.
+    fn block(&mut self, code: &[AST], position: Position) {
+        if code.is_empty() {
+            self.emit(Op::Nil, position);
+        }
+        // Functions, structs and enums get their slots first, so the ones
+        // before them in the block can refer to them.
+        for ast in code {
+            match ast {
+                AST::Statement(Statement::Function(ast::Function { name: Some(name), .. })) => { self.declare(&name.name); },
+                AST::Statement(Statement::Struct(structure)) => { self.declare(&structure.name.name); },
+                AST::Statement(Statement::Enum(enumeration)) => { self.declare(&enumeration.name.name); },
+                _ => {}
+            }
+        }
+        for (i, ast) in code.iter().enumerate() {
+            let last = i + 1 == code.len();
+            match ast {
+                AST::Statement(statement) => self.statement(statement, last),
+                AST::Expression(expression) => {
+                    self.expression(expression);
+                    if !last {
+                        self.emit(Op::Pop, expression.position());
+                    }
+                }
+            }
+        }
+    }
+
+    // Compiles a statement, leaving its value only if `keep` is set. Only
+    // blocks have a value other than nil.
+    fn statement(&mut self, statement: &Statement, keep: bool) {
+        let position = statement.position();
+        match statement {
+            Statement::Declaration(declaration) => {
+                self.expression(&declaration.value);
+                self.bind(&declaration.target, true);
+            },
+            Statement::Assignment(assignment) => {
+                self.expression(&assignment.value);
+                self.bind(&assignment.target, false);
+            },
+            Statement::Function(function) => {
+                let place = function.name.as_ref().map(|name| self.declare(&name.name));
+                self.function(function);
+                match (place, &function.name) {
+                    (Some(place), Some(name)) => self.store(place, &name.name, true, name.position),
+                    _ => self.emit(Op::Pop, function.position)
+                }
+            },
+            Statement::Struct(structure) => {
+                let place = self.declare(&structure.name.name);
+                for method in &structure.methods {
+                    self.function(method);
+                }
+                let layout = StructLayout {
+                    name: structure.name.name.clone(),
+                    fields: structure.fields.iter().map(|f| f.name.name.clone()).collect(),
+                    methods: structure.methods.iter().map(|m| m.name.as_ref().map(|n| n.name.clone()).unwrap_or_default()).collect(),
+                    position: structure.position
+                };
+                self.emit_constant(Op::Struct, Constant::Struct(layout), structure.position);
+                self.store(place, &structure.name.name, true, structure.name.position);
+            },
+            Statement::Enum(enumeration) => {
+                let layout = EnumLayout {
+                    name: enumeration.name.name.clone(),
+                    variants: enumeration.variants.iter()
+                        .map(|v| (v.name.name.clone(), v.fields.iter().map(|f| f.name.name.clone()).collect()))
+                        .collect(),
+                    position: enumeration.position
+                };
+                self.emit_constant(Op::Enum, Constant::Enum(layout), enumeration.position);
+                let place = self.declare(&enumeration.name.name);
+                self.store(place, &enumeration.name.name, true, enumeration.name.position);
+            },
+            Statement::Return(ret) => {
+                match &ret.value {
+                    Some(value) => self.expression(value),
+                    None => self.emit(Op::Nil, ret.position)
+                }
+                self.emit(Op::Return, ret.position);
+            },
+            Statement::Body(body) => {
+                self.begin_scope();
+                self.block(&body.code, body.position);
+                self.end_scope();
+                if !keep {
+                    self.emit(Op::Pop, position);
+                }
+                return;
+            }
+        }
+
+        // A `return` never gets this far when it runs.
+        if keep && !matches!(statement, Statement::Return(_)) {
+            self.emit(Op::Nil, position);
+        }
+    }
+
+    fn function(&mut self, function: &ast::Function) {
+        let parameters = function.parameters.iter().map(|p| p.name.name.clone()).collect::<Vec<_>>();
+        let name = function.name.as_ref().map(|n| n.name.clone());
+        self.functions.push(State::new(name, parameters, function.position, captured(&function.body.code)));
+        self.begin_scope();
+
+        // Arguments arrive in the first slots. Captured parameters are moved
+        // into cells before anything else runs.
+        self.state().locals = function.parameters.len();
+        for (i, parameter) in function.parameters.iter().map(|p| &p.name).enumerate() {
+            let slot = if self.state().captured.contains(&parameter.name) {
+                self.state().cells += 1;
+                let cell = self.state().cells - 1;
+                self.emit_short(Op::GetLocal, i, parameter.position);
+                self.emit_short(Op::SetCell, cell, parameter.position);
+                Slot::Cell(self.state().short(cell))
+            } else {
+                Slot::Local(self.state().short(i))
+            };
+            let scope = self.state().scopes.last_mut().expect("the parameters' scope was just opened");
+            scope.insert(parameter.name.clone(), slot);
+        }
+
+        self.block(&function.body.code, function.body.position);
+        self.emit(Op::Return, function.position);
+        let compiled = self.finish(function.position);
+        self.emit_constant(Op::Closure, Constant::Function(Rc::new(compiled)), function.position);
+    }
+
+    // Stores the value on top of the stack into `target`.
+    fn bind(&mut self, target: &Target, declare: bool) {
+        match target {
+            Target::Identifier(identifier) => self.bind_name(identifier, declare),
+            Target::Rest(rest) => self.bind_name(&rest.name, declare),
+            Target::Tuple(sequence) | Target::List(sequence) => {
+                let targets = &sequence.targets;
+                let rest = targets.iter().position(|target| matches!(target, Target::Rest(_)));
+                self.emit_short(Op::Unpack, targets.len(), sequence.position);
+                let rest = rest.map_or(u16::MAX, |rest| self.state().short(rest));
+                self.state().chunk.short(rest);
+                for target in targets {
+                    self.bind(target, declare);
+                }
+            },
+            Target::Attribute(a) => {
+                self.expression(&a.object);
+                self.emit_name(Op::StoreAttribute, &a.name.name, a.name.position);
+            },
+            Target::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+                self.emit(Op::Key, i.index.position());
+                self.emit(Op::StoreIndex, i.position);
+            }
+        }
+    }
+
+    fn bind_name(&mut self, identifier: &Identifier, declare: bool) {
+        let place = if declare { self.declare(&identifier.name) } else { self.resolve(&identifier.name) };
+        self.store(place, &identifier.name, declare, identifier.position);
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.compile_expression(expression))
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(literal) => match literal.form {
+                Form::Boolean(true) => self.emit(Op::True, literal.position),
+                Form::Boolean(false) => self.emit(Op::False, literal.position),
+                _ => self.emit_constant(Op::Constant, Constant::Literal(literal.form.clone()), literal.position)
+            },
+            Expression::Identifier(identifier) => {
+                let place = self.resolve(&identifier.name);
+                self.load(place, &identifier.name, identifier.position);
+            },
+            Expression::BinaryOperation(b) => match b.operator {
+                // `and` and `or` leave whichever operand decided the result.
+                token!(and) | token!(or) => {
+                    self.expression(&b.loperand);
+                    let op = if b.operator == token!(and) { Op::JumpIfFalseOrPop } else { Op::JumpIfTrueOrPop };
+                    let jump = self.emit_jump(op, b.position);
+                    self.expression(&b.roperand);
+                    self.patch(jump);
+                },
+                operator => {
+                    self.expression(&b.loperand);
+                    self.expression(&b.roperand);
+                    self.emit(Op::binary(operator), b.position);
+                }
+            },
+            Expression::UnaryOperation(u) => {
+                self.expression(&u.operand);
+                self.emit(Op::unary(u.operator), u.position);
+            },
+            Expression::Comparison(c) => self.comparison(c),
+            Expression::Tuple(t) => {
+                if !self.sequence(&t.elements, t.position) {
+                    self.emit_short(Op::Tuple, t.elements.len(), t.position);
+                }
+            },
+            Expression::List(l) => {
+                if self.sequence(&l.elements, l.position) {
+                    self.emit(Op::ToList, l.position);
+                } else {
+                    self.emit_short(Op::List, l.elements.len(), l.position);
+                }
+            },
+            Expression::Spread(s) => {
+                let error = access::error(ErrorForm::SyntaxError, message!("E0001-bare-spread"), s.position);
+                self.error.register_error(error);
+                self.emit(Op::Nil, s.position);
+            },
+            Expression::Cast(c) => {
+                self.expression(&c.value);
+                let layout = CastLayout { ty: c.ty.clone(), declared: c.declared, origin: c.origin };
+                self.emit_constant(Op::Cast, Constant::Cast(layout), c.value.position());
+            },
+            Expression::Attribute(a) => {
+                self.expression(&a.object);
+                self.emit_name(Op::Attribute, &a.name.name, a.name.position);
+            },
+            Expression::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+                self.emit(Op::Index, i.position);
+            },
+            Expression::Call(c) => {
+                self.expression(&c.callee);
+                if self.sequence(&c.arguments, c.position) {
+                    self.emit(Op::CallSpread, c.position);
+                } else {
+                    self.emit_short(Op::Call, c.arguments.len(), c.position);
+                }
+            },
+            Expression::Lambda(l) => self.function(l),
+            Expression::Match(m) => self.match_expression(m),
+            Expression::Construction(c) => {
+                let place = self.resolve(&c.name.name);
+                self.load(place, &c.name.name, c.name.position);
+                for field in &c.fields {
+                    self.expression(&field.value);
+                }
+                let layout = ConstructionLayout {
+                    name: c.name.name.clone(),
+                    name_position: c.name.position,
+                    fields: c.fields.iter().map(|f| (f.name.name.clone(), f.name.position)).collect()
+                };
+                self.emit_constant(Op::Construct, Constant::Construction(layout), c.position);
+            },
+            Expression::Map(m) => {
+                self.emit(Op::Map, m.position);
+                for entry in &m.entries {
+                    self.expression(&entry.key);
+                    self.expression(&entry.value);
+                    self.emit(Op::Insert, entry.key.position());
+                }
+            }
+        }
+    }
+
+    // Pushes the elements of a tuple, list or argument list. Without spreads
+    // they're left as they are, for one instruction to collect; with them, or
+    // more than one instruction can count, they're gathered into a tuple as
+    // they go, and this returns true.
+    fn sequence(&mut self, elements: &[Expression], position: Position) -> bool {
+        let spread = elements.iter().any(|element| matches!(element, Expression::Spread(_)));
+        if !spread && elements.len() <= usize::from(u16::MAX) {
+            for element in elements {
+                self.expression(element);
+            }
+            return false;
+        }
+
+        self.emit_short(Op::Tuple, 0, position);
+        for element in elements {
+            match element {
+                Expression::Spread(s) => {
+                    self.expression(&s.value);
+                    self.emit(Op::Extend, s.position);
+                },
+                _ => {
+                    self.expression(element);
+                    self.emit(Op::Append, element.position());
+                }
+            }
+        }
+        true
+    }
+
+    // `a < b < c` keeps `b` in a slot so it's evaluated once, and stops at
+    // the first comparison that fails.
+    fn comparison(&mut self, c: &Comparison) {
+        self.expression(&c.operands[0]);
+        let operand = self.temporary();
+        let mut exits = Vec::new();
+
+        for (i, (operator, right)) in c.operators.iter().zip(c.operands.iter().skip(1)).enumerate() {
+            self.expression(right);
+            if i + 1 == c.operators.len() {
+                self.emit(Op::binary(*operator), c.position);
+                break;
+            }
+            self.emit_short(Op::SetLocal, operand, c.position);
+            self.emit_short(Op::GetLocal, operand, c.position);
+            self.emit(Op::binary(*operator), c.position);
+            exits.push(self.emit_jump(Op::JumpIfFalseOrPop, c.position));
+            self.emit_short(Op::GetLocal, operand, c.position);
+        }
+
+        for exit in exits {
+            self.patch(exit);
+        }
+    }
+
+    // The scrutinee is kept in a slot and tried against each arm in turn.
+    // An arm's bindings, guard and body share one scope.
+    fn match_expression(&mut self, m: &Match) {
+        self.expression(&m.scrutinee);
+        let scrutinee = self.temporary();
+        self.emit_short(Op::SetLocal, scrutinee, m.position);
+
+        let mut exits = Vec::new();
+        for arm in &m.arms {
+            self.begin_scope();
+            let pattern = self.pattern(&arm.pattern);
+            self.emit_short(Op::GetLocal, scrutinee, arm.position);
+            self.emit_constant(Op::Match, Constant::Pattern(pattern), arm.pattern.position());
+            let mut misses = vec![self.emit_jump(Op::JumpIfFalse, arm.position)];
+
+            if let Some(guard) = &arm.guard {
+                self.expression(guard);
+                misses.push(self.emit_jump(Op::JumpIfFalse, guard.position()));
+            }
+
+            self.block(&arm.body.code, arm.body.position);
+            self.end_scope();
+            exits.push(self.emit_jump(Op::Jump, arm.position));
+            for miss in misses {
+                self.patch(miss);
+            }
+        }
+
+        self.emit_short(Op::GetLocal, scrutinee, m.position);
+        self.emit(Op::NoMatch, m.position);
+        for exit in exits {
+            self.patch(exit);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &ast::Pattern) -> Pattern {
+        let patterns = |sequence: &PatternSequence, compiler: &mut Compiler| {
+            sequence.patterns.iter().map(|p| compiler.pattern(p)).collect::<Vec<_>>()
+        };
+
+        match pattern {
+            ast::Pattern::Wildcard(_) => Pattern::Wildcard,
+            ast::Pattern::Binding(identifier) => Pattern::Binding(self.binding(identifier)),
+            ast::Pattern::Literal(literal) => Pattern::Literal(literal.form.clone()),
+            ast::Pattern::Range(range) => Pattern::Range(range.start.form.clone(), range.end.form.clone(), range.inclusive),
+            ast::Pattern::Tuple(sequence) => Pattern::Tuple(patterns(sequence, self)),
+            ast::Pattern::List(sequence) => Pattern::List(patterns(sequence, self)),
+            ast::Pattern::Alternation(sequence) => Pattern::Alternation(patterns(sequence, self)),
+            ast::Pattern::Rest(rest) => Pattern::Rest(rest.name.as_ref().map(|name| self.binding(name))),
+            ast::Pattern::Variant(variant) => Pattern::Variant {
+                enumeration: variant.enumeration.name.clone(),
+                variant: variant.variant.name.clone(),
+                fields: variant.fields.as_ref().map(|fields| patterns(fields, self))
+            }
+        }
+    }
+
+    fn binding(&mut self, identifier: &Identifier) -> Slot {
+        match self.declare(&identifier.name) {
+            Place::Slot(slot) => slot,
+            _ => unreachable!("match arms always have their own scope")
+        }
+    }
+}
+
+impl State {
+    fn new(name: Option<String>, parameters: Vec<String>, position: Position, captured: HashSet<String>) -> State {
+        State {
+            name,
+            parameters,
+            position,
+            chunk: Chunk::new(),
+            scopes: Vec::new(),
+            locals: 0,
+            cells: 0,
+            captures: Vec::new(),
+            names: HashMap::new(),
+            captured,
+            overflowed: false
+        }
+    }
+
+    // The operand for `n`, noting when it doesn't fit in one.
+    fn short(&mut self, n: usize) -> u16 {
+        u16::try_from(n).unwrap_or_else(|_| {
+            self.overflowed = true;
+            u16::MAX
+        })
+    }
+
+    // The compiled function, and whether every operand in it fit.
+    fn finish(mut self) -> (Function, bool) {
+        let locals = self.short(self.locals);
+        let cells = self.short(self.cells);
+        let function = Function {
+            name: self.name,
+            parameters: self.parameters,
+            chunk: self.chunk,
+            locals,
+            cells,
+            captures: self.captures.into_iter().map(|(_, capture)| capture).collect(),
+            position: self.position
+        };
+        (function, !self.overflowed)
+    }
+}
+
+// Every name used inside a function nested somewhere in `code`, whether it's
+// read or assigned.
+fn captured(code: &[AST]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for ast in code {
+        Names { names: &mut names, nested: false }.ast(ast);
+    }
+    names
+}
+
+struct Names<'a> {
+    names: &'a mut HashSet<String>,
+    nested: bool
+}
+
+impl Names<'_> {
+    fn use_name(&mut self, name: &str) {
+        if self.nested {
+            self.names.insert(name.to_string());
+        }
+    }
+
+    fn ast(&mut self, ast: &AST) {
+        match ast {
+            AST::Statement(statement) => self.statement(statement),
+            AST::Expression(expression) => self.expression(expression)
+        }
+    }
+
+    fn function(&mut self, function: &ast::Function) {
+        let mut names = Names { names: &mut *self.names, nested: true };
+        function.body.code.iter().for_each(|ast| names.ast(ast));
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Declaration(Declaration { target, value, .. }) | Statement::Assignment(Assignment { target, value, .. }) => {
+                self.expression(value);
+                self.target(target);
+            },
+            Statement::Function(function) => self.function(function),
+            Statement::Struct(structure) => structure.methods.iter().for_each(|method| self.function(method)),
+            Statement::Enum(_) => {},
+            Statement::Return(ret) => ret.value.iter().for_each(|value| self.expression(value)),
+            Statement::Body(body) => body.code.iter().for_each(|ast| self.ast(ast))
+        }
+    }
+
+    fn target(&mut self, target: &Target) {
+        match target {
+            Target::Identifier(identifier) => self.use_name(&identifier.name),
+            Target::Rest(rest) => self.use_name(&rest.name.name),
+            Target::Tuple(sequence) | Target::List(sequence) => sequence.targets.iter().for_each(|target| self.target(target)),
+            Target::Attribute(a) => self.expression(&a.object),
+            Target::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.visit(expression))
+    }
+
+    fn visit(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Literal(_) => {},
+            Expression::Identifier(identifier) => self.use_name(&identifier.name),
+            Expression::BinaryOperation(b) => {
+                self.expression(&b.loperand);
+                self.expression(&b.roperand);
+            },
+            Expression::UnaryOperation(u) => self.expression(&u.operand),
+            Expression::Comparison(c) => c.operands.iter().for_each(|operand| self.expression(operand)),
+            Expression::Tuple(s) | Expression::List(s) => s.elements.iter().for_each(|element| self.expression(element)),
+            Expression::Spread(s) => self.expression(&s.value),
+            Expression::Cast(c) => self.expression(&c.value),
+            Expression::Attribute(a) => self.expression(&a.object),
+            Expression::Index(i) => {
+                self.expression(&i.object);
+                self.expression(&i.index);
+            },
+            Expression::Call(c) => {
+                self.expression(&c.callee);
+                c.arguments.iter().for_each(|argument| self.expression(argument));
+            },
+            Expression::Lambda(l) => self.function(l),
+            Expression::Match(m) => {
+                self.expression(&m.scrutinee);
+                for arm in &m.arms {
+                    arm.guard.iter().for_each(|guard| self.expression(guard));
+                    arm.body.code.iter().for_each(|ast| self.ast(ast));
+                }
+            },
+            Expression::Construction(c) => {
+                self.use_name(&c.name.name);
+                c.fields.iter().for_each(|field| self.expression(&field.value));
+            },
+            Expression::Map(m) => {
+                for entry in &m.entries {
+                    self.expression(&entry.key);
+                    self.expression(&entry.value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::{self, Write};
+
+use crate::compiler::chunk::{Capture, Chunk, Constant, Function, Op, Operand, Pattern, Slot};
+use crate::interpreter::value::Value;
+
+// A listing of a compiled function for debugging, followed by the listings
+// of the functions declared inside it. Each instruction shows its offset,
+// the line and column it came from, and what its operands refer to:
+//
+//     == <script>() ==
+//     locals 0, cells 0, captures []
+//     0000     1:9  Constant        0  ; 1
+//     0005       |  DefineGlobal    1  ; x
+pub fn disassemble(function: &Function) -> String {
+    let mut output = String::new();
+    write_function(&mut output, function, "<script>").expect("writing to a string can't fail");
+    output
+}
+
+// `unnamed` is what to call the function if it has no name.
+fn write_function(output: &mut String, function: &Function, unnamed: &str) -> fmt::Result {
+    let name = function.name.as_deref().map_or_else(|| String::from(unnamed), |name| format!("fn {}", name));
+    writeln!(output, "== {}({}) ==", name, function.parameters.join(", "))?;
+    writeln!(output, "locals {}, cells {}, captures [{}]", function.locals, function.cells, captures(function))?;
+
+    let chunk = &function.chunk;
+    let mut offset = 0;
+    let mut line = None;
+    while offset < chunk.code.len() {
+        offset = write_instruction(output, chunk, offset, &mut line)?;
+    }
+
+    for constant in &chunk.constants {
+        if let Constant::Function(inner) = constant {
+            writeln!(output)?;
+            write_function(output, inner, "fn")?;
+        }
+    }
+    Ok(())
+}
+
+fn captures(function: &Function) -> String {
+    function.captures.iter().map(|capture| match capture {
+        Capture::Cell(cell) => format!("cell {}", cell),
+        Capture::Capture(index) => format!("capture {}", index)
+    }).collect::<Vec<_>>().join(", ")
+}
+
+// Writes the instruction at `offset`, returning where the next one starts.
+// The position is only written when it differs from the last one's line.
+fn write_instruction(output: &mut String, chunk: &Chunk, offset: usize, line: &mut Option<usize>) -> Result<usize, fmt::Error> {
+    let op = Op::decode(chunk.code[offset]).expect("the compiler only writes valid opcodes");
+    let position = chunk.position(offset);
+    let location = if *line == Some(position.line_number) {
+        String::from("|")
+    } else {
+        format!("{}:{}", position.line_number, position.column_number)
+    };
+    *line = Some(position.line_number);
+
+    let mut operands = Vec::new();
+    let mut at = offset + 1;
+    for operand in op.operands() {
+        operands.push(match operand {
+            Operand::Short => chunk.read_short(at) as u32,
+            Operand::Constant | Operand::Jump => chunk.read_long(at)
+        });
+        at += operand.size();
+    }
+
+    let shown = match (op, operands.as_slice()) {
+        (Op::Unpack, [count, rest]) if *rest == u16::MAX as u32 => format!("{}", count),
+        (Op::Unpack, [count, rest]) => format!("{}  ; rest {}", count, rest),
+        (_, [jump]) if op.operands() == [Operand::Jump] => format!("-> {:04}", jump),
+        (_, [constant]) if op.operands() == [Operand::Constant] => {
+            format!("{}  ; {}", constant, describe(&chunk.constants[*constant as usize]))
+        },
+        _ => operands.iter().map(u32::to_string).collect::<Vec<_>>().join(", ")
+    };
+    let line = format!("{:04} {:>7}  {:<16}{}", offset, location, format!("{:?}", op), shown);
+    writeln!(output, "{}", line.trim_end())?;
+    Ok(at)
+}
+
+fn describe(constant: &Constant) -> String {
+    match constant {
+        Constant::Literal(form) => Value::from_form(form).to_string(),
+        Constant::Name(name) => name.clone(),
+        Constant::Function(function) => match &function.name {
+            Some(name) => format!("<fn {}>", name),
+            None => String::from("<fn>")
+        },
+        Constant::Struct(layout) => format!("struct {} ({}) methods ({})", layout.name, layout.fields.join(", "), layout.methods.join(", ")),
+        Constant::Enum(layout) => {
+            let variants = layout.variants.iter().map(|(variant, _)| variant.as_str()).collect::<Vec<_>>();
+            format!("enum {} ({})", layout.name, variants.join(", "))
+        },
+        Constant::Construction(layout) => {
+            let fields = layout.fields.iter().map(|(field, _)| field.as_str()).collect::<Vec<_>>();
+            format!("{} {{{}}}", layout.name, fields.join(", "))
+        },
+        Constant::Pattern(pattern) => pattern_str(pattern),
+        Constant::Cast(layout) => format!("as {}", layout.ty)
+    }
+}
+
+// A pattern as it was written, with each binding replaced by where it goes.
+fn pattern_str(pattern: &Pattern) -> String {
+    let all = |patterns: &[Pattern], separator: &str| patterns.iter().map(pattern_str).collect::<Vec<_>>().join(separator);
+    match pattern {
+        Pattern::Wildcard => String::from("_"),
+        Pattern::Binding(slot) => slot_str(*slot),
+        Pattern::Literal(form) => Value::from_form(form).to_string(),
+        Pattern::Range(start, end, inclusive) => {
+            format!("{}{}{}", Value::from_form(start), if *inclusive { "..=" } else { ".." }, Value::from_form(end))
+        },
+        Pattern::Tuple(patterns) if patterns.len() == 1 => format!("({},)", pattern_str(&patterns[0])),
+        Pattern::Tuple(patterns) => format!("({})", all(patterns, ", ")),
+        Pattern::List(patterns) => format!("[{}]", all(patterns, ", ")),
+        Pattern::Rest(slot) => format!("..{}", slot.map(slot_str).unwrap_or_default()),
+        Pattern::Alternation(patterns) => all(patterns, " | "),
+        Pattern::Variant { enumeration, variant, fields: Some(fields) } => format!("{}.{}({})", enumeration, variant, all(fields, ", ")),
+        Pattern::Variant { enumeration, variant, fields: None } => format!("{}.{}", enumeration, variant)
+    }
+}
+
+fn slot_str(slot: Slot) -> String {
+    match slot {
+        Slot::Local(slot) => format!("local {}", slot),
+        Slot::Cell(cell) => format!("cell {}", cell)
+    }
+}
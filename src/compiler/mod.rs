@@ -0,0 +1,5 @@
+pub mod cache;
+pub mod chunk;
+pub mod compile;
+pub mod disassemble;
+pub mod vm;
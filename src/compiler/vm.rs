@@ -0,0 +1,463 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::compiler::chunk::{Capture, Constant, Function, Op, Pattern, Slot};
+use crate::error::diagnostic::{Error, ErrorForm};
+use crate::interpreter::access::{self, Call, Shape};
+use crate::interpreter::builtins;
+use crate::interpreter::gc;
+use crate::interpreter::interpret::MAX_CALL_DEPTH;
+use crate::interpreter::ops;
+use crate::interpreter::value::{Closure, Code, Enumeration, Guard, Map, Structure, Value};
+use crate::message;
+use crate::parser::position::Position;
+
+// One running call. Arguments and locals live in numbered slots, and locals
+// that closures capture in cells.
+struct Frame {
+    closure: Rc<Closure>,
+    function: Rc<Function>,
+    ip: usize,
+    locals: Vec<Value>,
+    cells: Vec<Rc<RefCell<Value>>>,
+    // Guarded functions that called this one, innermost first, which check
+    // what it returns and where they were called from.
+    guards: Vec<(Rc<Guard>, Position)>
+}
+
+// A stack machine that runs what `Compiler` makes. It gives the same results
+// and the same errors as the interpreter, so a program can run on either;
+// errors find their spans through each chunk's line table.
+pub struct VM {
+    globals: HashMap<String, Value>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    // Whether `/` between two integers gives an exact rational rather than
+    // a float.
+    pub exact_division: bool
+}
+
+impl Default for VM {
+    fn default() -> VM {
+        VM::new()
+    }
+}
+
+impl VM {
+    pub fn new() -> VM {
+        VM {
+            globals: builtins::BUILTINS.iter().map(|builtin| (builtin.name.to_string(), Value::Builtin(builtin))).collect(),
+            stack: Vec::new(),
+            frames: Vec::new(),
+            exact_division: false
+        }
+    }
+
+    // Runs a compiled file. Globals are kept between runs, so later files
+    // see what earlier ones declared.
+    pub fn run(&mut self, script: Rc<Function>) -> Result<Value, Error> {
+        let closure = Value::function(Closure {
+            name: None,
+            parameters: vec![],
+            position: script.position,
+            code: Code::Compiled { function: script, captures: vec![] }
+        });
+        self.enter(closure, vec![]);
+
+        let result = self.execute();
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+        }
+        gc::collect_if_due();
+        result
+    }
+
+    // Frees every reference cycle nothing can reach any more, returning how
+    // many objects were in them. This also happens on its own as the heap grows.
+    pub fn collect_garbage(&self) -> usize {
+        gc::collect()
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("something is running")
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("the compiler keeps the stack balanced")
+    }
+
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - count)
+    }
+
+    fn execute(&mut self) -> Result<Value, Error> {
+        loop {
+            let frame = self.frame();
+            let function = frame.function.clone();
+            let chunk = &function.chunk;
+            let start = frame.ip;
+            let op = Op::decode(chunk.code[start]).expect("the compiler only writes valid opcodes");
+            frame.ip += op.size();
+
+            let short = || chunk.read_short(start + 1) as usize;
+            let long = || chunk.read_long(start + 1) as usize;
+            let position = || chunk.position(start);
+            let name = || match &chunk.constants[long()] {
+                Constant::Name(name) => name.as_str(),
+                _ => unreachable!("the compiler gives names as name constants")
+            };
+
+            match op {
+                Op::Constant => match &chunk.constants[long()] {
+                    Constant::Literal(form) => self.stack.push(Value::from_form(form)),
+                    _ => unreachable!("only literals are pushed as constants")
+                },
+                Op::Nil => self.stack.push(Value::Nil),
+                Op::True => self.stack.push(Value::Boolean(true)),
+                Op::False => self.stack.push(Value::Boolean(false)),
+                Op::Pop => {
+                    self.pop();
+                },
+
+                Op::GetLocal => {
+                    let value = self.frame().locals[short()].clone();
+                    self.stack.push(value);
+                },
+                Op::SetLocal => {
+                    let value = self.pop();
+                    self.frame().locals[short()] = value;
+                },
+                Op::GetCell => {
+                    let value = self.frame().cells[short()].borrow().clone();
+                    self.stack.push(value);
+                },
+                Op::SetCell => {
+                    let value = self.pop();
+                    *self.frame().cells[short()].borrow_mut() = value;
+                },
+                Op::GetCapture => {
+                    let value = captures(&self.frame().closure)[short()].borrow().clone();
+                    self.stack.push(value);
+                },
+                Op::SetCapture => {
+                    let value = self.pop();
+                    *captures(&self.frame().closure)[short()].borrow_mut() = value;
+                },
+                Op::GetGlobal => match self.globals.get(name()) {
+                    Some(value) => self.stack.push(value.clone()),
+                    None => return Err(access::error(ErrorForm::NameError, message!("E0003-undeclared-variable", name = name()), position()))
+                },
+                Op::DefineGlobal => {
+                    let value = self.pop();
+                    self.globals.insert(name().to_string(), value);
+                },
+                Op::SetGlobal => {
+                    let value = self.pop();
+                    match self.globals.get_mut(name()) {
+                        Some(global) => *global = value,
+                        None => return Err(access::error(ErrorForm::NameError, message!("E0003-undeclared-assignment", name = name()), position()))
+                    }
+                },
+
+                Op::Negate | Op::Not | Op::Complement => {
+                    let operand = self.pop();
+                    let operator = op.operator().expect("unary instructions have an operator");
+                    match ops::unary(operator, &operand) {
+                        Ok(value) => self.stack.push(value),
+                        Err((kind, message)) => return Err(access::error(kind, message, position()))
+                    }
+                },
+                Op::Add | Op::Subtract | Op::Multiply | Op::Divide | Op::Modulo | Op::Power | Op::BitAnd | Op::BitOr
+                    | Op::BitXor | Op::ShiftLeft | Op::ShiftRight | Op::Equal | Op::NotEqual | Op::Less
+                    | Op::LessEqual | Op::Greater | Op::GreaterEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let result = if op == Op::Divide && self.exact_division {
+                        ops::divide_exactly(&left, &right)
+                    } else {
+                        ops::binary(op.operator().expect("binary instructions have an operator"), &left, &right)
+                    };
+                    match result {
+                        Ok(value) => self.stack.push(value),
+                        Err((kind, message)) => return Err(access::error(kind, message, position()))
+                    }
+                },
+
+                Op::Jump => self.frame().ip = long(),
+                Op::JumpIfFalse => {
+                    if !self.pop().is_truthy() {
+                        self.frame().ip = long();
+                    }
+                },
+                Op::JumpIfFalseOrPop | Op::JumpIfTrueOrPop => {
+                    let truthy = self.stack.last().expect("a condition is on the stack").is_truthy();
+                    if truthy == (op == Op::JumpIfTrueOrPop) {
+                        self.frame().ip = long();
+                    } else {
+                        self.pop();
+                    }
+                },
+
+                Op::Tuple => {
+                    let elements = self.pop_many(short());
+                    self.stack.push(Value::Tuple(elements));
+                },
+                Op::List => {
+                    let elements = self.pop_many(short());
+                    self.stack.push(Value::list(elements));
+                },
+                Op::ToList => match self.pop() {
+                    Value::Tuple(elements) => self.stack.push(Value::list(elements)),
+                    _ => unreachable!("lists with spreads are gathered into a tuple first")
+                },
+                Op::Append => {
+                    let value = self.pop();
+                    if let Some(Value::Tuple(elements)) = self.stack.last_mut() {
+                        elements.push(value);
+                    }
+                },
+                Op::Extend => {
+                    let value = self.pop();
+                    let spread = access::spread(&value, position())?;
+                    if let Some(Value::Tuple(elements)) = self.stack.last_mut() {
+                        elements.extend(spread);
+                    }
+                },
+                Op::Map => self.stack.push(Value::map(Map::new())),
+                Op::Insert => {
+                    let value = self.pop();
+                    let key = self.pop();
+                    if let Some(Value::Map(map)) = self.stack.last() {
+                        let inserted = map.borrow_mut().insert(key, value);
+                        inserted.map_err(|key| access::unhashable(&key, position()))?;
+                    }
+                },
+                Op::Index => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let element = access::index(&object, &index, position())?;
+                    self.stack.push(element);
+                },
+                Op::Key => {
+                    let index = &self.stack[self.stack.len() - 1];
+                    if let Value::Map(_) = &self.stack[self.stack.len() - 2] {
+                        if !ops::hashable(index) {
+                            return Err(access::unhashable(index, position()));
+                        }
+                    }
+                },
+                Op::StoreIndex => {
+                    let index = self.pop();
+                    let object = self.pop();
+                    let value = self.pop();
+                    access::store_index(&object, index, value, position(), position())?;
+                },
+                Op::Attribute => {
+                    let object = self.pop();
+                    let attribute = access::attribute(&object, name(), position())?;
+                    self.stack.push(attribute);
+                },
+                Op::StoreAttribute => {
+                    let object = self.pop();
+                    let value = self.pop();
+                    access::store_attribute(&object, name(), value, position())?;
+                },
+                Op::Unpack => {
+                    let value = self.pop();
+                    let rest = chunk.read_short(start + 3);
+                    let rest = if rest == u16::MAX { None } else { Some(rest as usize) };
+                    let parts = access::unpack(&value, short(), rest, position())?;
+                    self.stack.extend(parts.into_iter().rev());
+                },
+                Op::Cast => {
+                    let layout = match &chunk.constants[long()] {
+                        Constant::Cast(layout) => layout,
+                        _ => unreachable!("casts take cast constants")
+                    };
+                    let value = self.pop();
+                    let value = access::cast(value, &layout.ty, position(), layout.declared, layout.origin)?;
+                    self.stack.push(value);
+                },
+
+                Op::Call => {
+                    let arguments = self.pop_many(short());
+                    let callee = self.pop();
+                    self.call(callee, arguments, position())?;
+                },
+                Op::CallSpread => {
+                    let arguments = match self.pop() {
+                        Value::Tuple(arguments) => arguments,
+                        _ => unreachable!("spread arguments are gathered into a tuple")
+                    };
+                    let callee = self.pop();
+                    self.call(callee, arguments, position())?;
+                },
+                Op::Closure => {
+                    let inner = match &chunk.constants[long()] {
+                        Constant::Function(inner) => inner.clone(),
+                        _ => unreachable!("closures are made from function constants")
+                    };
+                    let frame = self.frame();
+                    let captured = inner.captures.iter().map(|capture| match capture {
+                        Capture::Cell(cell) => frame.cells[*cell as usize].clone(),
+                        Capture::Capture(index) => captures(&frame.closure)[*index as usize].clone()
+                    }).collect();
+                    let closure = Value::function(Closure {
+                        name: inner.name.clone(),
+                        parameters: inner.parameters.clone(),
+                        position: inner.position,
+                        code: Code::Compiled { function: inner, captures: captured }
+                    });
+                    self.stack.push(Value::Function(closure));
+                },
+                Op::Struct => {
+                    let layout = match &chunk.constants[long()] {
+                        Constant::Struct(layout) => layout,
+                        _ => unreachable!("structs are made from struct constants")
+                    };
+                    let methods = self.pop_many(layout.methods.len()).into_iter().zip(&layout.methods)
+                        .map(|(method, name)| match method {
+                            Value::Function(closure) => (name.clone(), closure),
+                            _ => unreachable!("methods are compiled to closures")
+                        })
+                        .collect();
+                    self.stack.push(Value::structure(Structure {
+                        name: layout.name.clone(),
+                        fields: layout.fields.clone(),
+                        methods,
+                        position: layout.position
+                    }));
+                },
+                Op::Enum => match &chunk.constants[long()] {
+                    Constant::Enum(layout) => self.stack.push(Value::Enum(Rc::new(Enumeration {
+                        name: layout.name.clone(),
+                        variants: layout.variants.clone(),
+                        position: layout.position
+                    }))),
+                    _ => unreachable!("enums are made from enum constants")
+                },
+                Op::Construct => {
+                    let layout = match &chunk.constants[long()] {
+                        Constant::Construction(layout) => layout,
+                        _ => unreachable!("records are made from construction constants")
+                    };
+                    let values = self.pop_many(layout.fields.len());
+                    let structure = self.pop();
+                    let fields = layout.fields.iter().zip(values).map(|((field, at), value)| (field.as_str(), *at, value)).collect();
+                    let record = access::record(structure, &layout.name, layout.name_position, fields, position())
+                        ?;
+                    self.stack.push(record);
+                },
+                Op::Match => {
+                    let pattern = match &chunk.constants[long()] {
+                        Constant::Pattern(pattern) => pattern,
+                        _ => unreachable!("patterns are pattern constants")
+                    };
+                    let value = self.pop();
+                    let mut bindings = Vec::new();
+                    let matched = access::matches(pattern, &value, &mut bindings);
+                    if matched {
+                        let frame = self.frame();
+                        for (slot, value) in bindings {
+                            match slot {
+                                Slot::Local(slot) => frame.locals[slot as usize] = value,
+                                Slot::Cell(cell) => *frame.cells[cell as usize].borrow_mut() = value
+                            }
+                        }
+                    }
+                    self.stack.push(Value::Boolean(matched));
+                },
+                Op::NoMatch => {
+                    let value = self.pop();
+                    return Err(access::error(ErrorForm::RuntimeError, message!("E0007-no-matching-arm", value = value), position()));
+                },
+                Op::Return => {
+                    let mut value = self.pop();
+                    let frame = self.frames.pop().expect("something is running");
+                    for (guard, position) in &frame.guards {
+                        value = access::guard_result(guard, value, *position)?;
+                    }
+                    if self.frames.is_empty() {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+            }
+        }
+    }
+
+    // Calls a function by pushing a frame for it, or calls anything else
+    // right away and pushes its result.
+    fn call(&mut self, callee: Value, arguments: Vec<Value>, position: Position) -> Result<(), Error> {
+        let (closure, arguments) = match access::call(&callee, arguments, position)? {
+            Call::Enter(closure, arguments) => (closure, arguments),
+            // What it returns is checked when the frame it called returns,
+            // or now if it didn't need one.
+            Call::Guarded(guard, arguments) => {
+                let depth = self.frames.len();
+                self.call(guard.function.clone(), arguments, position)?;
+                if self.frames.len() > depth {
+                    self.frame().guards.push((guard, position));
+                } else {
+                    let value = self.pop();
+                    self.stack.push(access::guard_result(&guard, value, position)?);
+                }
+                return Ok(());
+            },
+            Call::Returned(value) => {
+                self.stack.push(value);
+                return Ok(());
+            }
+        };
+
+        // The script's own frame isn't a call.
+        if self.frames.len() > MAX_CALL_DEPTH {
+            return Err(access::error(ErrorForm::RuntimeError, message!("E0007-call-depth", depth = MAX_CALL_DEPTH), position));
+        }
+
+        gc::collect_if_due();
+        self.enter(closure, arguments);
+        Ok(())
+    }
+
+    fn enter(&mut self, closure: Rc<Closure>, mut arguments: Vec<Value>) {
+        let function = match &closure.code {
+            Code::Compiled { function, .. } => function.clone(),
+            Code::Tree { .. } => unreachable!("the interpreter's functions only run in the interpreter")
+        };
+        arguments.resize(function.locals as usize, Value::Nil);
+        let cells = (0..function.cells).map(|_| Value::cell(Value::Nil)).collect();
+        self.frames.push(Frame { closure, function, ip: 0, locals: arguments, cells, guards: vec![] });
+    }
+}
+
+fn captures(closure: &Closure) -> &[Rc<RefCell<Value>>] {
+    match &closure.code {
+        Code::Compiled { captures, .. } => captures,
+        Code::Tree { .. } => unreachable!("the interpreter's functions only run in the interpreter")
+    }
+}
+
+impl access::Matchable for Pattern {
+    type Target = Slot;
+
+    fn shape(&self) -> Shape<'_, Pattern> {
+        match self {
+            Pattern::Wildcard => Shape::Wildcard,
+            Pattern::Binding(slot) => Shape::Binding(slot),
+            Pattern::Literal(form) => Shape::Literal(form),
+            Pattern::Range(start, end, inclusive) => Shape::Range(start, end, *inclusive),
+            Pattern::Tuple(patterns) => Shape::Tuple(patterns),
+            Pattern::List(patterns) => Shape::List(patterns),
+            Pattern::Rest(slot) => Shape::Rest(slot.as_ref()),
+            Pattern::Alternation(alternatives) => Shape::Alternation(alternatives),
+            Pattern::Variant { enumeration, variant, fields } => Shape::Variant {
+                enumeration,
+                variant,
+                fields: fields.as_deref()
+            }
+        }
+    }
+}
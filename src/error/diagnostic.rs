@@ -1,21 +1,150 @@
-use std::process;
+use std::collections::HashSet;
+use std::fmt;
+use std::slice;
 
 use anyhow;
+use codespan_reporting::diagnostic;
 use codespan_reporting::diagnostic::{Diagnostic, Label};
-use codespan_reporting::files::SimpleFile;
-use codespan_reporting::term;
-use codespan_reporting::term::Config;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use codespan_reporting::files::SimpleFiles;
+
+use crate::error::emitter::Emitter;
+use crate::error::fix::Fix;
+use crate::message;
+
+// Every source file is added to one shared `Sources`, and positions refer to
+// it by the id it hands out. A diagnostic can then point into several files.
+pub type FileId = usize;
+pub type Sources = SimpleFiles<String, String>;
 
 #[derive(Debug, Clone)]
 pub enum ErrorForm {
     SyntaxError,
-    EOFError
+    EOFError,
+    NameError,
+    ConstantReassignment,
+    Shadowing,
+    TypeError,
+    RuntimeError,
+    NonExhaustiveMatch,
+    UnreachableArm,
+    // An operation worked out before the program runs that will fail when it does.
+    ConstantEvaluation,
+    // A valid program too big for the VM to number everything in it.
+    Limit,
+    // Something went wrong inside the implementation rather than the program.
+    InternalError
 }
 
-#[derive(Debug, Clone)]
+impl ErrorForm {
+    pub const ALL: [ErrorForm; 12] = [
+        ErrorForm::SyntaxError,
+        ErrorForm::EOFError,
+        ErrorForm::NameError,
+        ErrorForm::ConstantReassignment,
+        ErrorForm::Shadowing,
+        ErrorForm::TypeError,
+        ErrorForm::RuntimeError,
+        ErrorForm::NonExhaustiveMatch,
+        ErrorForm::UnreachableArm,
+        ErrorForm::ConstantEvaluation,
+        ErrorForm::Limit,
+        ErrorForm::InternalError
+    ];
+
+    // Every form has a stable code, so it can be allowed or denied by name.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorForm::SyntaxError => "E0001",
+            ErrorForm::EOFError => "E0002",
+            ErrorForm::NameError => "E0003",
+            ErrorForm::ConstantReassignment => "E0004",
+            ErrorForm::Shadowing => "E0005",
+            ErrorForm::TypeError => "E0006",
+            ErrorForm::RuntimeError => "E0007",
+            ErrorForm::NonExhaustiveMatch => "E0008",
+            ErrorForm::UnreachableArm => "E0009",
+            ErrorForm::InternalError => "E0010",
+            ErrorForm::ConstantEvaluation => "E0011",
+            ErrorForm::Limit => "E0012"
+        }
+    }
+
+    // How a code someone typed is written, however they wrote it, so `e0005`
+    // names the same diagnostics as `E0005`.
+    pub fn normalise_code(code: &str) -> String {
+        code.trim().to_ascii_uppercase()
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            ErrorForm::Shadowing | ErrorForm::UnreachableArm | ErrorForm::ConstantEvaluation => Severity::Warning,
+            ErrorForm::InternalError => Severity::Bug,
+            _ => Severity::Error
+        }
+    }
+}
+
+// Ordered from least to most serious. Anything at `Error` or above stops the
+// program from running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Help,
+    Note,
+    Warning,
+    Error,
+    Bug
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Help => write!(f, "help"),
+            Severity::Note => write!(f, "note"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+            Severity::Bug => write!(f, "bug")
+        }
+    }
+}
+
+impl From<Severity> for diagnostic::Severity {
+    fn from(severity: Severity) -> diagnostic::Severity {
+        match severity {
+            Severity::Help => diagnostic::Severity::Help,
+            Severity::Note => diagnostic::Severity::Note,
+            Severity::Warning => diagnostic::Severity::Warning,
+            Severity::Error => diagnostic::Severity::Error,
+            Severity::Bug => diagnostic::Severity::Bug
+        }
+    }
+}
+
+// Which diagnostics to hide and which warnings to treat as errors, usually
+// chosen on the command line. Only diagnostics below `Error` can be allowed.
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    pub deny_warnings: bool,
+    deny: HashSet<String>,
+    allow: HashSet<String>
+}
+
+impl LintLevels {
+    pub fn new() -> LintLevels {
+        LintLevels::default()
+    }
+
+    pub fn deny(&mut self, code: &str) {
+        self.deny.insert(ErrorForm::normalise_code(code));
+    }
+
+    pub fn allow(&mut self, code: &str) {
+        self.allow.insert(ErrorForm::normalise_code(code));
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct Errors {
-    errors: Vec<Error>
+    pub errors: Vec<Error>
 }
 
 impl Errors {
@@ -31,51 +160,113 @@ impl Errors {
         self.errors.append(errors)
     }
 
-    pub fn emit_errors(&mut self, file: SimpleFile<String, String>) -> Option<anyhow::Result<()>> {
-        let writer = StandardStream::stderr(ColorChoice::Always);
-        let config = Config::default();
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
 
-        if self.errors.len() == 0 {
-            return None;
-        }
+    // Whether anything serious enough to stop the program was registered.
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.iter().filter(|error| error.severity >= Severity::Error).count()
+    }
+
+    // Drops allowed diagnostics and promotes denied warnings to errors.
+    pub fn apply(&mut self, levels: &LintLevels) {
+        self.errors.retain(|error| error.severity >= Severity::Error || !levels.allow.contains(error.kind.code()));
 
-        for mut error in self.errors.clone() {
-            let error = error.as_diagnostic();
-            term::emit(&mut writer.lock(), &config, &file, &error)
-                .expect("Oh no");
+        for error in &mut self.errors {
+            if error.severity != Severity::Warning {
+                continue;
+            }
+
+            let code = error.kind.code();
+            let reason = if levels.deny.contains(code) {
+                message!("lint-denied", code = code)
+            } else if levels.deny_warnings {
+                message!("lint-denied.warnings")
+            } else {
+                continue;
+            };
+
+            error.severity = Severity::Error;
+            error.notes.push(reason);
         }
+    }
 
-        process::exit(1)
+    pub fn iter(&self) -> slice::Iter<'_, Error> {
+        self.errors.iter()
+    }
+
+    // Forgets everything registered so far, so the same `Errors` can be used
+    // for the next compilation unit.
+    pub fn clear(&mut self) {
+        self.errors.clear()
+    }
+
+    // Writes every diagnostic to stderr with the default emitter settings and
+    // returns how many of them were errors. See `Emitter` for the others.
+    pub fn emit_errors(&self, files: &Sources) -> anyhow::Result<usize> {
+        Emitter::new().emit(self, files)
+    }
+}
+
+impl<'a> IntoIterator for &'a Errors {
+    type Item = &'a Error;
+    type IntoIter = slice::Iter<'a, Error>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.errors.iter()
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct Error {
     pub kind: ErrorForm,
+    pub severity: Severity,
     pub message: String,
-    pub labels: Vec<Label<()>>,
+    pub labels: Vec<Label<FileId>>,
     pub notes: Vec<String>,
+    pub fixes: Vec<Fix>,
 }
 
 impl Error {
     pub fn new(
         kind: ErrorForm,
         message: String,
-        labels: Vec<Label<()>>,
+        labels: Vec<Label<FileId>>,
         notes: Vec<String>,
     ) -> Error {
         Error {
+            severity: kind.severity(),
             kind,
             message,
             labels,
             notes,
+            fixes: Vec::new(),
         }
     }
 
-    pub fn as_diagnostic(&mut self) -> Diagnostic<()> {
-        Diagnostic::error()
+    pub fn with_severity(mut self, severity: Severity) -> Error {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_fix(mut self, fix: Fix) -> Error {
+        self.fixes.push(fix);
+        self
+    }
+
+    // Fixes are shown as help notes, since the terminal can't apply them.
+    pub fn as_diagnostic(&self) -> Diagnostic<FileId> {
+        let help = self.fixes.iter().map(|fix| format!("help: {}", fix.message));
+
+        Diagnostic::new(self.severity.into())
+            .with_code(self.kind.code())
             .with_message(&self.message)
             .with_labels(self.labels.clone())
-            .with_notes(self.notes.clone())
+            .with_notes(self.notes.iter().cloned().chain(help).collect())
     }
 }
\ No newline at end of file
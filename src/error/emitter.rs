@@ -0,0 +1,118 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use codespan_reporting::term;
+use codespan_reporting::term::{Config, DisplayStyle};
+use codespan_reporting::term::termcolor::{ColorChoice, StandardStream, WriteColor};
+
+use crate::error::diagnostic::{Errors, Sources};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Auto,
+    Always,
+    Never
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    // The source snippet with every label underlined and every note.
+    Rich,
+    // The header and the notes, without the source.
+    Medium,
+    // One `file:line:column: severity[code]: message` line per diagnostic.
+    Short
+}
+
+// How diagnostics are written out for people to read.
+#[derive(Debug, Clone)]
+pub struct Emitter {
+    pub colour: Colour,
+    pub style: Style,
+    pub tab_width: usize
+}
+
+impl Colour {
+    pub fn from_name(name: &str) -> Option<Colour> {
+        match name {
+            "auto" => Some(Colour::Auto),
+            "always" => Some(Colour::Always),
+            "never" => Some(Colour::Never),
+            _ => None
+        }
+    }
+
+    // Whether diagnostics are coloured, given whether `NO_COLOR` is set and
+    // whether they go to a terminal. `auto` only colours a terminal, and
+    // never when `NO_COLOR` is set to anything (https://no-color.org).
+    pub fn enabled(self, no_colour: bool, terminal: bool) -> bool {
+        match self {
+            Colour::Always => true,
+            Colour::Never => false,
+            Colour::Auto => !no_colour && terminal
+        }
+    }
+
+    fn choice(self) -> ColorChoice {
+        let no_colour = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        match self {
+            Colour::Always => ColorChoice::Always,
+            Colour::Auto if self.enabled(no_colour, io::stderr().is_terminal()) => ColorChoice::Auto,
+            _ => ColorChoice::Never
+        }
+    }
+}
+
+impl Style {
+    pub fn from_name(name: &str) -> Option<Style> {
+        match name {
+            "rich" => Some(Style::Rich),
+            "medium" => Some(Style::Medium),
+            "short" => Some(Style::Short),
+            _ => None
+        }
+    }
+}
+
+impl From<Style> for DisplayStyle {
+    fn from(style: Style) -> DisplayStyle {
+        match style {
+            Style::Rich => DisplayStyle::Rich,
+            Style::Medium => DisplayStyle::Medium,
+            Style::Short => DisplayStyle::Short
+        }
+    }
+}
+
+impl Emitter {
+    pub fn new() -> Emitter {
+        Emitter { colour: Colour::Auto, style: Style::Rich, tab_width: 4 }
+    }
+
+    fn config(&self) -> Config {
+        Config { display_style: self.style.into(), tab_width: self.tab_width, ..Config::default() }
+    }
+
+    // Writes every diagnostic to stderr and returns how many of them were
+    // errors. Deciding what to do about them is up to the caller.
+    pub fn emit(&self, errors: &Errors, files: &Sources) -> anyhow::Result<usize> {
+        let writer = StandardStream::stderr(self.colour.choice());
+        let mut lock = writer.lock();
+        self.emit_to(&mut lock, errors, files)
+    }
+
+    // The same, but to any writer. The colour setting is up to the writer.
+    pub fn emit_to(&self, writer: &mut dyn WriteColor, errors: &Errors, files: &Sources) -> anyhow::Result<usize> {
+        let config = self.config();
+        for error in errors {
+            term::emit(writer, &config, files, &error.as_diagnostic())?;
+        }
+        Ok(errors.error_count())
+    }
+}
+
+impl Default for Emitter {
+    fn default() -> Emitter {
+        Emitter::new()
+    }
+}
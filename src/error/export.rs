@@ -0,0 +1,154 @@
+use codespan_reporting::diagnostic::{Label, LabelStyle};
+use codespan_reporting::files::Files;
+use serde_json::{json, Value};
+
+use crate::error::diagnostic::{Error, ErrorForm, Errors, FileId, Severity, Sources};
+use crate::error::fix::Fix;
+
+// Diagnostics for tools rather than people: one JSON object per line, or a
+// whole SARIF 2.1.0 log for code scanning services. Ranges are given both as
+// byte offsets and as one-based lines and columns.
+
+pub fn json_lines(errors: &Errors, files: &Sources) -> anyhow::Result<String> {
+    let mut output = String::new();
+    for error in errors {
+        output.push_str(&json(error, files)?.to_string());
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+pub fn json(error: &Error, files: &Sources) -> anyhow::Result<Value> {
+    let labels = error.labels.iter().map(|label| {
+        Ok(json!({
+            "style": match label.style {
+                LabelStyle::Primary => "primary",
+                LabelStyle::Secondary => "secondary"
+            },
+            "file": files.name(label.file_id)?,
+            "message": label.message,
+            "start": point(files, label.file_id, label.range.start)?,
+            "end": point(files, label.file_id, label.range.end)?
+        }))
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+
+    let fixes = error.fixes.iter().map(|fix| {
+        Ok(json!({
+            "message": fix.message,
+            "applicability": format!("{:?}", fix.applicability),
+            "file": files.name(fix.file)?,
+            "start": point(files, fix.file, fix.range.start)?,
+            "end": point(files, fix.file, fix.range.end)?,
+            "replacement": fix.replacement
+        }))
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(json!({
+        "kind": format!("{:?}", error.kind),
+        "code": error.kind.code(),
+        "severity": error.severity.to_string(),
+        "message": error.message,
+        "labels": labels,
+        "notes": error.notes,
+        "fixes": fixes
+    }))
+}
+
+pub fn sarif(errors: &Errors, files: &Sources) -> anyhow::Result<String> {
+    let rules = ErrorForm::ALL.iter().map(|form| json!({
+        "id": form.code(),
+        "name": format!("{:?}", form),
+        "defaultConfiguration": { "level": level(form.severity()) }
+    })).collect::<Vec<_>>();
+
+    let results = errors.iter().map(|error| {
+        let (primary, secondary): (Vec<&Label<FileId>>, Vec<&Label<FileId>>) = error.labels.iter()
+            .partition(|label| label.style == LabelStyle::Primary);
+
+        let mut text = error.message.clone();
+        for note in &error.notes {
+            text.push('\n');
+            text.push_str(note);
+        }
+
+        Ok(json!({
+            "ruleId": error.kind.code(),
+            "level": level(error.severity),
+            "message": { "text": text },
+            "locations": primary.iter().map(|label| location(files, label)).collect::<anyhow::Result<Vec<_>>>()?,
+            "relatedLocations": secondary.iter().enumerate().map(|(id, label)| {
+                let mut related = location(files, label)?;
+                related["id"] = json!(id);
+                Ok(related)
+            }).collect::<anyhow::Result<Vec<_>>>()?,
+            "fixes": error.fixes.iter().map(|fix| sarif_fix(files, fix)).collect::<anyhow::Result<Vec<_>>>()?
+        }))
+    }).collect::<anyhow::Result<Vec<_>>>()?;
+
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules
+                }
+            },
+            "results": results
+        }]
+    });
+
+    Ok(serde_json::to_string_pretty(&log)?)
+}
+
+fn point(files: &Sources, file: FileId, byte: usize) -> anyhow::Result<Value> {
+    let location = files.location(file, byte)?;
+    Ok(json!({ "byte": byte, "line": location.line_number, "column": location.column_number }))
+}
+
+// SARIF has no help or bug levels, so those become a note and an error.
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Help | Severity::Note => "note",
+        Severity::Warning => "warning",
+        Severity::Error | Severity::Bug => "error"
+    }
+}
+
+fn location(files: &Sources, label: &Label<FileId>) -> anyhow::Result<Value> {
+    let start = files.location(label.file_id, label.range.start)?;
+    let end = files.location(label.file_id, label.range.end)?;
+
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": files.name(label.file_id)? },
+            "region": {
+                "startLine": start.line_number,
+                "startColumn": start.column_number,
+                "endLine": end.line_number,
+                "endColumn": end.column_number,
+                "byteOffset": label.range.start,
+                "byteLength": label.range.end.saturating_sub(label.range.start)
+            }
+        }
+    });
+    if !label.message.is_empty() {
+        location["message"] = json!({ "text": label.message });
+    }
+    Ok(location)
+}
+
+fn sarif_fix(files: &Sources, fix: &Fix) -> anyhow::Result<Value> {
+    Ok(json!({
+        "description": { "text": fix.message },
+        "artifactChanges": [{
+            "artifactLocation": { "uri": files.name(fix.file)? },
+            "replacements": [{
+                "deletedRegion": { "byteOffset": fix.range.start, "byteLength": fix.range.end - fix.range.start },
+                "insertedContent": { "text": fix.replacement }
+            }]
+        }]
+    }))
+}
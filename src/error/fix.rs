@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+use crate::error::diagnostic::FileId;
+
+// How sure we are that a fix does what the person meant. Only machine
+// applicable fixes are made by `--fix`; the others are shown as help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    // Applying it always gives the code the person meant.
+    MachineApplicable,
+    // It is probably right, but worth reading first.
+    MaybeIncorrect,
+    // The replacement has parts like `...` that still need filling in.
+    HasPlaceholders
+}
+
+// A suggested edit: replace the bytes in `range` of `file` with `replacement`.
+// An empty range is an insertion.
+#[derive(Debug, Clone)]
+pub struct Fix {
+    pub message: String,
+    pub file: FileId,
+    pub range: Range<usize>,
+    pub replacement: String,
+    pub applicability: Applicability
+}
+
+impl Fix {
+    pub fn new(message: String, file: FileId, range: Range<usize>, replacement: &str, applicability: Applicability) -> Fix {
+        Fix { message, file, range, replacement: String::from(replacement), applicability }
+    }
+
+    pub fn is_safe(&self) -> bool {
+        self.applicability == Applicability::MachineApplicable
+    }
+}
+
+// Applies the fixes to the source, skipping any that overlap one applied
+// before it, and returns the new source with the indices of the fixes that
+// were made, in the order they were given.
+pub fn apply<'a>(source: &str, fixes: impl IntoIterator<Item = &'a Fix>) -> (String, Vec<usize>) {
+    let mut fixes = fixes.into_iter().enumerate().collect::<Vec<_>>();
+    fixes.sort_by_key(|(_, fix)| (fix.range.start, fix.range.end));
+
+    let mut output = String::new();
+    let mut copied = 0;
+    let mut applied = Vec::new();
+    for (i, fix) in fixes {
+        if fix.range.start < copied || fix.range.end > source.len() {
+            continue;
+        }
+        output.push_str(&source[copied..fix.range.start]);
+        output.push_str(&fix.replacement);
+        copied = fix.range.end;
+        applied.push(i);
+    }
+    output.push_str(&source[copied..]);
+
+    applied.sort_unstable();
+    (output, applied)
+}
@@ -0,0 +1,142 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use crate::error::diagnostic::ErrorForm;
+
+// Every diagnostic's text lives in a catalogue instead of the code that
+// reports it, so it can be translated. Keys start with the error code, like
+// `E0001-lone-bang`, and its notes, labels and fixes hang off it as
+// `E0001-lone-bang.note`. Texts refer to parameters by name, as in
+// `I found '{found}'`, and `{{` or `}}` stand for a literal brace.
+//
+// A catalogue file has one `key = text` per line, with `\n` for a line break.
+// Blank lines and lines starting with `#` are skipped.
+
+const ENGLISH: &str = include_str!("messages/en.messages");
+
+#[derive(Debug, Clone, Default)]
+pub struct Catalogue {
+    messages: HashMap<String, String>
+}
+
+thread_local! {
+    // Translations installed with `install`, consulted before the English.
+    static INSTALLED: RefCell<Catalogue> = RefCell::new(Catalogue::default());
+    static DEFAULT: Catalogue = Catalogue::parse(ENGLISH).expect("the English catalogue is well formed");
+}
+
+impl Catalogue {
+    pub fn parse(text: &str) -> Result<Catalogue, String> {
+        let mut messages = HashMap::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, message) = line.split_once('=')
+                .map(|(key, message)| (key.trim(), message.trim()))
+                .filter(|(key, _)| !key.is_empty() && !key.contains(char::is_whitespace))
+                .ok_or_else(|| format!("line {} should be a key, a '=' and the message", index + 1))?;
+            messages.insert(key.to_string(), message.replace("\\n", "\n"));
+        }
+        Ok(Catalogue { messages })
+    }
+
+    pub fn load(path: &str) -> io::Result<Catalogue> {
+        let text = fs::read_to_string(path)?;
+        Catalogue::parse(&text).map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.messages.get(key).map(String::as_str)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.messages.keys().map(String::as_str)
+    }
+}
+
+// Uses `catalogue` for every message from now on. Messages it lacks still
+// come out in English.
+pub fn install(catalogue: Catalogue) {
+    INSTALLED.with(|installed| *installed.borrow_mut() = catalogue);
+}
+
+// The English catalogue every other one is translated from.
+pub fn english() -> Catalogue {
+    DEFAULT.with(Catalogue::clone)
+}
+
+// Looks up `key` and fills in its parameters. Use the `message!` macro
+// rather than calling this directly.
+pub fn message(key: &str, arguments: &[(&str, String)]) -> String {
+    let template = INSTALLED.with(|installed| installed.borrow().get(key).map(String::from))
+        .or_else(|| DEFAULT.with(|default| default.get(key).map(String::from)))
+        .unwrap_or_else(|| panic!("there is no message called `{}`", key));
+    interpolate(&template, arguments)
+}
+
+fn interpolate(template: &str, arguments: &[(&str, String)]) -> String {
+    let mut output = String::new();
+    let mut characters = template.chars().peekable();
+    while let Some(c) = characters.next() {
+        match c {
+            '{' if characters.peek() == Some(&'{') => {
+                characters.next();
+                output.push('{');
+            },
+            '}' if characters.peek() == Some(&'}') => {
+                characters.next();
+                output.push('}');
+            },
+            '{' => {
+                let name = characters.by_ref().take_while(|c| *c != '}').collect::<String>();
+                match arguments.iter().find(|(parameter, _)| *parameter == name) {
+                    Some((_, value)) => output.push_str(value),
+                    // A translation naming a parameter that doesn't exist
+                    // shouldn't lose the whole message.
+                    None => output.push_str(&format!("{{{}}}", name))
+                }
+            },
+            _ => output.push(c)
+        }
+    }
+    output
+}
+
+impl ErrorForm {
+    pub fn from_code(code: &str) -> Option<ErrorForm> {
+        let code = ErrorForm::normalise_code(code);
+        ErrorForm::ALL.iter().find(|form| form.code() == code).cloned()
+    }
+
+    // The long explanation printed by `--explain`.
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ErrorForm::SyntaxError => include_str!("explain/E0001.md"),
+            ErrorForm::EOFError => include_str!("explain/E0002.md"),
+            ErrorForm::NameError => include_str!("explain/E0003.md"),
+            ErrorForm::ConstantReassignment => include_str!("explain/E0004.md"),
+            ErrorForm::Shadowing => include_str!("explain/E0005.md"),
+            ErrorForm::TypeError => include_str!("explain/E0006.md"),
+            ErrorForm::RuntimeError => include_str!("explain/E0007.md"),
+            ErrorForm::NonExhaustiveMatch => include_str!("explain/E0008.md"),
+            ErrorForm::UnreachableArm => include_str!("explain/E0009.md"),
+            ErrorForm::InternalError => include_str!("explain/E0010.md"),
+            ErrorForm::ConstantEvaluation => include_str!("explain/E0011.md"),
+            ErrorForm::Limit => include_str!("explain/E0012.md")
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! message {
+    ($key:expr) => {
+        $crate::error::messages::message($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::error::messages::message($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
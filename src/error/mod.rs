@@ -1 +1,6 @@
-pub mod diagnostic;
\ No newline at end of file
+pub mod diagnostic;
+pub mod emitter;
+pub mod export;
+pub mod fix;
+pub mod messages;
+pub mod spelling;
\ No newline at end of file
@@ -0,0 +1,55 @@
+// Finding what someone probably meant when they misspell a name.
+
+use crate::message;
+
+// The optimal string alignment distance between two names: how many
+// characters have to be inserted, removed or replaced, or neighbours swapped,
+// to turn one into the other.
+pub fn distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    let mut rows = vec![(0..=b.len()).collect::<Vec<_>>()];
+
+    for i in 1..=a.len() {
+        let mut current = vec![i];
+        for j in 1..=b.len() {
+            let substitution = rows[i - 1][j - 1] + if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut best = substitution.min(rows[i - 1][j] + 1).min(current[j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            current.push(best);
+        }
+        rows.push(current);
+    }
+
+    rows[a.len()][b.len()]
+}
+
+// The candidates close enough to `name` to be worth suggesting, closest first.
+// Longer names are allowed more mistakes, about one for every three characters.
+// A name of a character or two is one mistake from too much, so those need
+// to share a character with what's suggested.
+pub fn suggestions<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let length = name.chars().count();
+    let limit = (length / 3).max(1);
+    let mut close = candidates.into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter(|candidate| length > 2 || name.chars().any(|c| candidate.contains(c)))
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .collect::<Vec<_>>();
+
+    close.sort();
+    close.dedup();
+    close.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+// A note offering the suggestions, like "Did you mean `x` or `y`?".
+pub fn did_you_mean(suggestions: &[&str]) -> Option<String> {
+    let quoted = suggestions.iter().take(3).map(|s| format!("`{}`", s)).collect::<Vec<_>>();
+    match quoted.split_last() {
+        None => None,
+        Some((last, [])) => Some(message!("help-did-you-mean", name = last)),
+        Some((last, rest)) => Some(message!("help-did-you-mean.several", names = rest.join(", "), last = last))
+    }
+}
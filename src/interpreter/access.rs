@@ -0,0 +1,618 @@
+use std::fmt;
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+
+use crate::analysis::types::Type;
+use crate::error::diagnostic::{Error, ErrorForm};
+use crate::error::spelling;
+use crate::interpreter::builtins::Builtin;
+use crate::interpreter::numeric;
+use crate::interpreter::ops;
+use crate::interpreter::value::{Closure, Enumeration, Guard, Record, Structure, Tagged, Value};
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::message;
+
+// Reading and writing the parts of values, and making records and variants.
+// The interpreter and the VM both go through here, so they fail in the same
+// ways with the same messages. The callers pass the spans to blame, since
+// only they know which part of the source did what.
+
+pub fn index(object: &Value, index: &Value, position: Position) -> Result<Value, Error> {
+    match (object, index) {
+        (Value::Tuple(t), Value::Integer(i)) => element(t, *i, position),
+        (Value::List(l), Value::Integer(i)) => element(&l.borrow(), *i, position),
+        (Value::String(s), Value::Integer(i)) => {
+            let characters = s.chars().map(|c| Value::String(c.to_string())).collect::<Vec<_>>();
+            element(&characters, *i, position)
+        },
+        // No sequence is long enough to reach an index this big.
+        (Value::Tuple(t), Value::BigInteger(i)) => Err(out_of_range(i, t.len(), position)),
+        (Value::List(l), Value::BigInteger(i)) => Err(out_of_range(i, l.borrow().len(), position)),
+        (Value::String(s), Value::BigInteger(i)) => Err(out_of_range(i, s.chars().count(), position)),
+        (Value::Map(m), key) => m.borrow().get(key).cloned().ok_or_else(|| error(
+            ErrorForm::RuntimeError,
+            message!("E0007-missing-key", key = key),
+            position
+        )),
+        _ => Err(error(
+            ErrorForm::TypeError,
+            message!("E0006-index", type = object.type_name(), index = index.type_name()),
+            position
+        ))
+    }
+}
+
+// `object[index] = value`. An unhashable map key is blamed on the index
+// itself, everything else on the whole target.
+pub fn store_index(object: &Value, index: Value, value: Value, position: Position, index_position: Position) -> Result<(), Error> {
+    match (object, &index) {
+        (Value::List(l), Value::Integer(n)) => {
+            let mut elements = l.borrow_mut();
+            let slot = offset(elements.len(), *n).ok_or_else(|| out_of_range(*n, elements.len(), position))?;
+            elements[slot] = value;
+            Ok(())
+        },
+        (Value::List(l), Value::BigInteger(n)) => Err(out_of_range(n, l.borrow().len(), position)),
+        (Value::Map(m), _) => m.borrow_mut().insert(index, value).map_err(|key| unhashable(&key, index_position)),
+        (Value::List(_), _) => Err(error(
+            ErrorForm::TypeError,
+            message!("E0006-index", type = "list", index = index.type_name()),
+            position
+        )),
+        _ => Err(error(
+            ErrorForm::TypeError,
+            message!("E0006-index-assignment", type = object.type_name()),
+            position
+        ))
+    }
+}
+
+fn element(elements: &[Value], index: i64, position: Position) -> Result<Value, Error> {
+    offset(elements.len(), index)
+        .map(|i| elements[i].clone())
+        .ok_or_else(|| out_of_range(index, elements.len(), position))
+}
+
+// Negative indexes count back from the end.
+fn offset(length: usize, index: i64) -> Option<usize> {
+    let index = if index < 0 { length as i64 + index } else { index };
+    if index >= 0 && (index as usize) < length { Some(index as usize) } else { None }
+}
+
+fn out_of_range(index: impl fmt::Display, length: usize, position: Position) -> Error {
+    error(
+        ErrorForm::RuntimeError,
+        message!("E0007-index-out-of-range", index = index, length = length),
+        position
+    )
+}
+
+// Reads a field of a record, or looks up a method. Methods that take `self`
+// are bound to the record they were read from. `position` is the span of
+// the name.
+pub fn attribute(object: &Value, name: &str, position: Position) -> Result<Value, Error> {
+    match object {
+        Value::Record(record) => {
+            let borrowed = record.borrow();
+            if let Some(index) = borrowed.field(name) {
+                return Ok(borrowed.values[index].clone());
+            }
+            if let Some(function) = borrowed.structure.methods.get(name) {
+                if function.parameters.first().map(String::as_str) == Some("self") {
+                    return Ok(Value::method(object.clone(), function.clone()));
+                }
+                return Ok(Value::Function(function.clone()));
+            }
+        },
+        Value::Struct(structure) => {
+            if let Some(function) = structure.methods.get(name) {
+                return Ok(Value::Function(function.clone()));
+            }
+        },
+        // Variants without a payload are values already, the others are
+        // called like functions to make one.
+        Value::Enum(enumeration) => {
+            if let Some(index) = enumeration.variants.iter().position(|(variant, _)| variant == name) {
+                if enumeration.variants[index].1.is_empty() {
+                    return construct(enumeration, index, vec![], position);
+                }
+                return Ok(Value::Constructor(enumeration.clone(), index));
+            }
+        },
+        Value::Variant(tagged) => {
+            if let Some(index) = tagged.fields().iter().position(|field| field == name) {
+                return Ok(tagged.values[index].clone());
+            }
+        },
+        _ => {}
+    }
+
+    Err(no_field(object, name, position))
+}
+
+pub fn store_attribute(object: &Value, name: &str, value: Value, position: Position) -> Result<(), Error> {
+    if let Value::Record(record) = object {
+        let mut record = record.borrow_mut();
+        if let Some(index) = record.field(name) {
+            record.values[index] = value;
+            return Ok(());
+        }
+    }
+    Err(no_field(object, name, position))
+}
+
+pub fn construct(enumeration: &Rc<Enumeration>, index: usize, values: Vec<Value>, position: Position) -> Result<Value, Error> {
+    let (variant, fields) = &enumeration.variants[index];
+    if values.len() != fields.len() {
+        return Err(Error::new(
+            ErrorForm::RuntimeError,
+            message!(
+                if fields.len() == 1 { "E0007-variant-arity.one" } else { "E0007-variant-arity" },
+                enumeration = enumeration.name,
+                variant = variant,
+                arity = fields.len(),
+                given = values.len()
+            ),
+            vec![
+                Label::primary(position.file, position.start..position.end),
+                Label::secondary(enumeration.position.file, enumeration.position.start..enumeration.position.end)
+                    .with_message(message!("E0007-variant-arity.declaration"))
+            ],
+            vec![]
+        ));
+    }
+
+    Ok(Value::variant(Tagged { enumeration: enumeration.clone(), index, values }))
+}
+
+// `Name { field: value, .. }`, given the value `Name` refers to and each
+// field written with the span of its name.
+pub fn record(
+    structure: Value,
+    name: &str,
+    name_position: Position,
+    fields: Vec<(&str, Position, Value)>,
+    position: Position
+) -> Result<Value, Error> {
+    let structure = match structure {
+        Value::Struct(structure) => structure,
+        other => return Err(error(
+            ErrorForm::TypeError,
+            message!("E0006-construct-non-struct", name = name, type = other.type_name()),
+            name_position
+        ))
+    };
+
+    let mut values = vec![None; structure.fields.len()];
+    for (field, field_position, value) in fields {
+        match structure.fields.iter().position(|f| f == field) {
+            Some(index) => values[index] = Some(value),
+            None => return Err(unknown_field(&structure, field, field_position))
+        }
+    }
+
+    let missing = structure.fields.iter().zip(values.iter())
+        .filter(|(_, value)| value.is_none())
+        .map(|(field, _)| format!("`{}`", field))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        return Err(Error::new(
+            ErrorForm::RuntimeError,
+            message!(
+                if missing.len() == 1 { "E0007-missing-field-value.one" } else { "E0007-missing-field-value" },
+                name = structure.name,
+                fields = missing.join(", ")
+            ),
+            vec![
+                Label::primary(position.file, position.start..position.end),
+                Label::secondary(structure.position.file, structure.position.start..structure.position.end)
+                    .with_message(message!("E0007-missing-field-value.declaration"))
+            ],
+            vec![]
+        ));
+    }
+
+    Ok(Value::record(Record {
+        values: values.into_iter().flatten().collect(),
+        structure
+    }))
+}
+
+// Checks a value from code without annotations has the type the checker
+// assumed where it meets code with them. `position` is the value, `declared`
+// is what asked for the type, and `origin` is where the variable holding the
+// value got it, if it's a variable. Functions can only be checked as they're
+// called, so the value given back may be guarded.
+pub fn cast(value: Value, ty: &Type, position: Position, declared: Position, origin: Option<Position>) -> Result<Value, Error> {
+    let (part, expected) = match mismatch(&value, ty) {
+        Some(mismatch) => mismatch,
+        None => return Ok(guard(value, ty, declared, origin.or(Some(position))))
+    };
+
+    // No type contains itself, so a part that should have had the whole
+    // type is the value itself.
+    let message = if expected == *ty {
+        message!("E0006-mismatch", expected = ty, found = part)
+    } else {
+        message!("E0006-cast.inside", expected = ty, found = value.type_name(), part = part, wanted = expected)
+    };
+    let mut labels = vec![
+        Label::primary(position.file, position.start..position.end)
+            .with_message(message!("E0006-type.label", type = value.type_name())),
+        Label::secondary(declared.file, declared.start..declared.end)
+            .with_message(message!("E0006-cast.label", type = ty))
+    ];
+    if let Some(origin) = origin.filter(|&origin| origin != declared) {
+        labels.push(Label::secondary(origin.file, origin.start..origin.end).with_message(message!("E0006-cast.origin")));
+    }
+    Err(Error::new(ErrorForm::TypeError, message, labels, vec![message!("E0006-cast.note")]))
+}
+
+// Wraps the functions in a value that fits `ty` so the arguments they're
+// given and the results they give back are checked too. Tuples never change,
+// so the functions in them can be wrapped in a copy. Calls that go wrong
+// point to `origin`, where the function came from.
+fn guard(value: Value, ty: &Type, declared: Position, origin: Option<Position>) -> Value {
+    match (ty, value) {
+        (Type::Function(parameters, result), function) if !(parameters.iter().all(unchecked) && unchecked(result)) => {
+            Value::guarded(Guard {
+                function,
+                parameters: parameters.clone(),
+                result: (**result).clone(),
+                declared,
+                origin
+            })
+        },
+        (Type::Tuple(types), Value::Tuple(elements)) => Value::Tuple(
+            elements.into_iter().zip(types).map(|(element, ty)| guard(element, ty, declared, origin)).collect()
+        ),
+        (_, value) => value
+    }
+}
+
+// Whether every value fits `ty`.
+fn unchecked(ty: &Type) -> bool {
+    matches!(ty, Type::Dynamic | Type::Variable(_) | Type::Parameter(_, _))
+}
+
+// Checks the arguments of a call to a guarded function against the types of
+// its parameters. Any beyond those are left for the arity check to report.
+fn guard_arguments(guard: &Guard, arguments: Vec<Value>, position: Position) -> Result<Vec<Value>, Error> {
+    arguments.into_iter().enumerate().map(|(i, argument)| match guard.parameters.get(i) {
+        Some(ty) => cast(argument, ty, position, guard.declared, guard.origin),
+        None => Ok(argument)
+    }).collect()
+}
+
+// Checks what a guarded function gave back against the type of its result.
+pub fn guard_result(guard: &Guard, result: Value, position: Position) -> Result<Value, Error> {
+    cast(result, &guard.result, position, guard.declared, guard.origin)
+}
+
+// The type of the first part of `value` that doesn't fit `ty`, with the type
+// that part should have had.
+fn mismatch(value: &Value, ty: &Type) -> Option<(String, Type)> {
+    let fits = match (ty, value) {
+        (Type::Variable(_), _) | (Type::Dynamic, _) | (Type::Parameter(_, _), _) => true,
+        (Type::Nil, Value::Nil) | (Type::Boolean, Value::Boolean(_)) | (Type::String, Value::String(_)) => true,
+        // A number fits any rank it would be promoted to.
+        (_, _) if ty.rank().is_some() && numeric::rank(value).is_some() => {
+            numeric::rank(value).map(|rank| rank as u8) <= ty.rank()
+        },
+        (Type::Tuple(types), Value::Tuple(elements)) if types.len() == elements.len() => {
+            return elements.iter().zip(types).find_map(|(element, ty)| mismatch(element, ty));
+        },
+        (Type::List(element), Value::List(elements)) => {
+            return elements.borrow().iter().find_map(|value| mismatch(value, element));
+        },
+        (Type::Map(key, element), Value::Map(map)) => {
+            return map.borrow().iter().find_map(|(k, v)| mismatch(k, key).or_else(|| mismatch(v, element)));
+        },
+        (Type::Function(_, _), Value::Function(_)) | (Type::Function(_, _), Value::Builtin(_))
+            | (Type::Function(_, _), Value::Method(_)) | (Type::Function(_, _), Value::Guarded(_))
+            | (Type::Function(_, _), Value::Constructor(_, _)) => true,
+        // The types a generic struct or enum was given aren't kept at runtime.
+        (Type::Record(name, _), Value::Record(record)) => record.borrow().structure.name == *name,
+        (Type::Struct(name), Value::Struct(structure)) => structure.name == *name,
+        (Type::Variant(name, _), Value::Variant(tagged)) => tagged.enumeration.name == *name,
+        (Type::Enum(name), Value::Enum(enumeration)) => enumeration.name == *name,
+        _ => false
+    };
+    if fits { None } else { Some((value.type_name(), ty.clone())) }
+}
+
+// What calling a value comes down to, once the parts that are the same
+// however the function runs are done.
+pub enum Call {
+    // A function to run with these arguments, which fit its parameters.
+    Enter(Rc<Closure>, Vec<Value>),
+    // A guarded function to call with arguments that were checked, whose
+    // result still needs to be.
+    Guarded(Rc<Guard>, Vec<Value>),
+    // Something that isn't code gave its result right away.
+    Returned(Value)
+}
+
+pub fn call(callee: &Value, mut arguments: Vec<Value>, position: Position) -> Result<Call, Error> {
+    match callee {
+        Value::Function(function) => {
+            check_arity(function, arguments.len(), 0, position)?;
+            Ok(Call::Enter(function.clone(), arguments))
+        },
+        // A method's receiver is passed as its first argument, `self`, which
+        // doesn't count towards the arguments the caller wrote.
+        Value::Method(method) => {
+            arguments.insert(0, method.receiver.clone());
+            check_arity(&method.function, arguments.len(), 1, position)?;
+            Ok(Call::Enter(method.function.clone(), arguments))
+        },
+        Value::Guarded(guard) => Ok(Call::Guarded(guard.clone(), guard_arguments(guard, arguments, position)?)),
+        Value::Constructor(enumeration, index) => Ok(Call::Returned(construct(enumeration, *index, arguments, position)?)),
+        Value::Builtin(builtin) => Ok(Call::Returned(call_builtin(builtin, &arguments, position)?)),
+        _ => Err(not_callable(callee, position))
+    }
+}
+
+// Checks a call passes a function as many arguments as it has parameters.
+// `receivers` is one for a method, whose `self` the caller didn't write.
+fn check_arity(function: &Closure, given: usize, receivers: usize, position: Position) -> Result<(), Error> {
+    if given == function.parameters.len() {
+        return Ok(());
+    }
+
+    let expected = function.parameters.len() - receivers;
+    Err(Error::new(
+        ErrorForm::RuntimeError,
+        message!(
+            if expected == 1 { "E0007-call-arity.one" } else { "E0007-call-arity" },
+            expected = expected,
+            given = given - receivers
+        ),
+        vec![
+            Label::primary(position.file, position.start..position.end),
+            Label::secondary(function.position.file, function.position.start..function.position.end)
+                .with_message(message!("E0007-call-arity.definition"))
+        ],
+        vec![]
+    ))
+}
+
+fn call_builtin(builtin: &Builtin, arguments: &[Value], position: Position) -> Result<Value, Error> {
+    if !builtin.arity.contains(&arguments.len()) {
+        let (min, max) = (*builtin.arity.start(), *builtin.arity.end());
+        let message = match (min, max) {
+            (1, 1) => message!("E0007-call-arity.one", given = arguments.len()),
+            _ if min == max => message!("E0007-call-arity", expected = min, given = arguments.len()),
+            _ => message!("E0007-call-arity.between", min = min, max = max, given = arguments.len())
+        };
+        return Err(error(ErrorForm::RuntimeError, message, position));
+    }
+
+    (builtin.function)(arguments).map_err(|(kind, message)| error(kind, message, position))
+}
+
+// Calling anything that isn't a function, a method, a builtin or a variant.
+pub fn not_callable(callee: &Value, position: Position) -> Error {
+    match callee {
+        Value::Struct(structure) => error(
+            ErrorForm::TypeError,
+            message!("E0006-call-struct", name = structure.name),
+            position
+        ),
+        _ => error(
+            ErrorForm::TypeError,
+            message!("E0006-call-value", type = callee.type_name()),
+            position
+        )
+    }
+}
+
+// A pattern as `matches` sees it, whether it's from the tree the interpreter
+// runs or from a chunk. `Target` is where a binding puts its value.
+pub trait Matchable: Sized {
+    type Target: Clone;
+
+    fn shape(&self) -> Shape<'_, Self>;
+}
+
+pub enum Shape<'a, P: Matchable> {
+    Wildcard,
+    Binding(&'a P::Target),
+    Literal(&'a Form),
+    Range(&'a Form, &'a Form, bool),
+    Tuple(&'a [P]),
+    List(&'a [P]),
+    Rest(Option<&'a P::Target>),
+    Alternation(&'a [P]),
+    Variant { enumeration: &'a str, variant: &'a str, fields: Option<&'a [P]> }
+}
+
+// Tests `value` against `pattern`, collecting the values it binds on success.
+pub fn matches<P: Matchable>(pattern: &P, value: &Value, bindings: &mut Vec<(P::Target, Value)>) -> bool {
+    match pattern.shape() {
+        Shape::Wildcard => true,
+        Shape::Binding(target) => {
+            bindings.push((target.clone(), value.clone()));
+            true
+        },
+        Shape::Literal(form) => ops::equals(&Value::from_form(form), value),
+        Shape::Range(start, end, inclusive) => ops::in_range(value, &Value::from_form(start), &Value::from_form(end), inclusive),
+        Shape::Tuple(patterns) => match value {
+            Value::Tuple(elements) => matches_sequence(patterns, elements, true, bindings),
+            _ => false
+        },
+        Shape::List(patterns) => match value {
+            Value::List(elements) => {
+                let elements = elements.borrow().clone();
+                matches_sequence(patterns, &elements, false, bindings)
+            },
+            _ => false
+        },
+        Shape::Variant { enumeration, variant, fields } => match value {
+            Value::Variant(tagged) => {
+                tagged.enumeration.name == enumeration
+                    && tagged.variant() == variant
+                    && match fields {
+                        Some(fields) => matches_sequence(fields, &tagged.values, true, bindings),
+                        None => tagged.values.is_empty()
+                    }
+            },
+            _ => false
+        },
+        Shape::Alternation(alternatives) => {
+            for alternative in alternatives {
+                let mut attempt = Vec::new();
+                if matches(alternative, value, &mut attempt) {
+                    bindings.extend(attempt);
+                    return true;
+                }
+            }
+            false
+        },
+        // The parser only allows rests inside tuples and lists.
+        Shape::Rest(_) => true
+    }
+}
+
+// A named rest collects into a tuple when matching a tuple or a variant's
+// payload, and into a list otherwise.
+fn matches_sequence<P: Matchable>(patterns: &[P], elements: &[Value], tuple: bool, bindings: &mut Vec<(P::Target, Value)>) -> bool {
+    let rest = match patterns.iter().position(|pattern| matches!(pattern.shape(), Shape::Rest(_))) {
+        None => {
+            return patterns.len() == elements.len()
+                && patterns.iter().zip(elements).all(|(pattern, element)| matches(pattern, element, bindings));
+        },
+        Some(rest) => rest
+    };
+
+    let after = patterns.len() - rest - 1;
+    if elements.len() < patterns.len() - 1 {
+        return false;
+    }
+
+    let prefix = patterns[..rest].iter().zip(elements).all(|(pattern, element)| matches(pattern, element, bindings));
+    let suffix = patterns[rest + 1..].iter()
+        .zip(&elements[elements.len() - after..])
+        .all(|(pattern, element)| matches(pattern, element, bindings));
+
+    if let Shape::Rest(Some(target)) = patterns[rest].shape() {
+        let collected = elements[rest..elements.len() - after].to_vec();
+        let collected = if tuple { Value::Tuple(collected) } else { Value::list(collected) };
+        bindings.push((target.clone(), collected));
+    }
+
+    prefix && suffix
+}
+
+// The elements `..value` stands for.
+pub fn spread(value: &Value, position: Position) -> Result<Vec<Value>, Error> {
+    value.elements().ok_or_else(|| error(
+        ErrorForm::TypeError,
+        message!("E0006-spread", type = value.type_name()),
+        position
+    ))
+}
+
+// Splits `value` into one value per target of a destructuring assignment
+// with `targets` targets, the one at `rest` taking whatever the others
+// don't. The rest keeps the kind of sequence it came from.
+pub fn unpack(value: &Value, targets: usize, rest: Option<usize>, position: Position) -> Result<Vec<Value>, Error> {
+    let elements = value.elements().ok_or_else(|| error(
+        ErrorForm::TypeError,
+        message!("E0006-unpack", type = value.type_name()),
+        position
+    ))?;
+
+    let required = if rest.is_some() { targets - 1 } else { targets };
+    if elements.len() < required || (rest.is_none() && elements.len() > required) {
+        let key = if rest.is_some() { "E0007-unpack-count.at-least" } else { "E0007-unpack-count" };
+        return Err(error(
+            ErrorForm::RuntimeError,
+            message!(key, expected = required, found = elements.len()),
+            position
+        ));
+    }
+
+    let rest = match rest {
+        Some(rest) => rest,
+        None => return Ok(elements)
+    };
+
+    let after = targets - rest - 1;
+    let mut elements = elements;
+    let suffix = elements.split_off(elements.len() - after);
+    let collected = elements.split_off(rest);
+    elements.push(match value {
+        Value::Tuple(_) => Value::Tuple(collected),
+        _ => Value::list(collected)
+    });
+    elements.extend(suffix);
+    Ok(elements)
+}
+
+fn no_field(object: &Value, name: &str, position: Position) -> Error {
+    let members = match object {
+        Value::Record(record) => {
+            let structure = &record.borrow().structure;
+            structure.fields.iter().chain(structure.methods.keys()).cloned().collect()
+        },
+        Value::Struct(structure) => structure.methods.keys().cloned().collect(),
+        Value::Enum(enumeration) => {
+            let variants = enumeration.variants.iter().map(|(variant, _)| variant.clone()).collect();
+            return missing_member(object.type_name(), variants, name, position, "E0007-missing-variant");
+        },
+        Value::Variant(tagged) => tagged.fields().to_vec(),
+        _ => return error(
+            ErrorForm::TypeError,
+            message!("E0006-no-fields", type = object.type_name(), name = name),
+            position
+        )
+    };
+    missing_member(object.type_name(), members, name, position, "E0007-missing-field")
+}
+
+fn unknown_field(structure: &Structure, name: &str, position: Position) -> Error {
+    missing_member(structure.name.clone(), structure.fields.clone(), name, position, "E0007-missing-field")
+}
+
+// A record or struct was asked for a member it doesn't have. Close spellings
+// are suggested, otherwise every member is listed. `key` names the message,
+// which differs for fields and variants.
+fn missing_member(type_name: String, members: Vec<String>, name: &str, position: Position, key: &str) -> Error {
+    let suggestions = spelling::suggestions(name, members.iter().map(String::as_str));
+    let note = spelling::did_you_mean(&suggestions).or_else(|| {
+        if members.is_empty() {
+            return None;
+        }
+        let mut members = members.iter().map(|m| format!("`{}`", m)).collect::<Vec<_>>();
+        members.sort();
+        Some(message!(&format!("{}.members", key), type = type_name, members = members.join(", ")))
+    });
+
+    Error::new(
+        ErrorForm::RuntimeError,
+        message!(key, type = type_name, name = name),
+        vec![Label::primary(position.file, position.start..position.end).with_message(message!(&format!("{}.label", key)))],
+        note.into_iter().collect()
+    )
+}
+
+pub fn unhashable(key: &Value, position: Position) -> Error {
+    Error::new(
+        ErrorForm::TypeError,
+        message!("E0006-unhashable", type = key.type_name()),
+        vec![Label::primary(position.file, position.start..position.end)],
+        vec![message!("E0006-unhashable.note")]
+    )
+}
+
+pub fn error(kind: ErrorForm, message: String, position: Position) -> Error {
+    Error::new(
+        kind,
+        message,
+        vec![Label::primary(position.file, position.start..position.end)],
+        vec![]
+    )
+}
@@ -0,0 +1,103 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, Zero};
+
+use crate::error::diagnostic::ErrorForm;
+use crate::interpreter::numeric;
+use crate::interpreter::ops::OperationResult;
+use crate::interpreter::value::Value;
+use crate::message;
+
+// A function written in Rust rather than in the language. Builtins are
+// ordinary values in the global scope, so they can be passed around or
+// shadowed like anything else.
+#[derive(Debug)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: RangeInclusive<usize>,
+    pub function: fn(&[Value]) -> OperationResult
+}
+
+pub const BUILTINS: &[Builtin] = &[
+    Builtin { name: "int", arity: 1..=1, function: int },
+    Builtin { name: "decimal", arity: 1..=1, function: decimal },
+    Builtin { name: "rational", arity: 1..=2, function: rational },
+    Builtin { name: "float", arity: 1..=1, function: float }
+];
+
+pub fn get(name: &str) -> Option<&'static Builtin> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+// The conversions between kinds of number. Converting to a more general kind
+// never loses anything; converting to a narrower one either rounds towards
+// zero, for `int`, or fails if the value has no exact form in that kind.
+// Floats are converted by the digits they print as, so `decimal(0.1)` is
+// `0.1d` rather than the binary fraction the float really holds. Strings
+// are read as a literal of the kind would be.
+
+fn int(arguments: &[Value]) -> OperationResult {
+    match &arguments[0] {
+        Value::Integer(_) | Value::BigInteger(_) => Ok(arguments[0].clone()),
+        Value::Decimal(d) => Ok(Value::integer(d.with_scale(0).into_bigint_and_exponent().0)),
+        Value::Rational(r) => Ok(Value::integer(r.trunc().to_integer())),
+        Value::Float(f) => BigInt::from_f64(f.trunc()).map(Value::integer).ok_or_else(|| inexact(&arguments[0], "int")),
+        Value::String(s) => BigInt::from_str(s.trim()).map(Value::integer).map_err(|_| unreadable(s, "int")),
+        value => Err(unconvertible(value, "int"))
+    }
+}
+
+fn decimal(arguments: &[Value]) -> OperationResult {
+    match &arguments[0] {
+        Value::Float(f) => numeric::float_as_decimal(*f).map(Value::Decimal).ok_or_else(|| inexact(&arguments[0], "decimal")),
+        Value::String(s) => BigDecimal::from_str(s.trim()).map(Value::Decimal).map_err(|_| unreadable(s, "decimal")),
+        value if numeric::rank(value).is_some() => {
+            numeric::to_decimal(value).map(Value::Decimal).ok_or_else(|| inexact(value, "decimal"))
+        },
+        value => Err(unconvertible(value, "decimal"))
+    }
+}
+
+// `rational(x)` converts one number, and `rational(a, b)` divides two
+// exactly, which is the way to write a fraction.
+fn rational(arguments: &[Value]) -> OperationResult {
+    let exact = |argument: &Value| match argument {
+        Value::Float(f) => numeric::float_as_decimal(*f)
+            .map(|d| numeric::decimal_to_rational(&d))
+            .ok_or_else(|| inexact(argument, "rational")),
+        Value::String(s) => BigRational::from_str(s.trim()).ok()
+            .or_else(|| BigDecimal::from_str(s.trim()).ok().map(|d| numeric::decimal_to_rational(&d)))
+            .ok_or_else(|| unreadable(s, "rational")),
+        value => numeric::to_rational(value).ok_or_else(|| unconvertible(value, "rational"))
+    };
+
+    let numerator = exact(&arguments[0])?;
+    match arguments.get(1).map(exact).transpose()? {
+        Some(denominator) if denominator.is_zero() => Err((ErrorForm::RuntimeError, message!("E0007-division-by-zero"))),
+        Some(denominator) => Ok(Value::rational(numerator / denominator)),
+        None => Ok(Value::rational(numerator))
+    }
+}
+
+fn float(arguments: &[Value]) -> OperationResult {
+    match &arguments[0] {
+        Value::String(s) => f64::from_str(s.trim()).map(Value::Float).map_err(|_| unreadable(s, "float")),
+        value => numeric::to_float(value).map(Value::Float).ok_or_else(|| unconvertible(value, "float"))
+    }
+}
+
+fn unconvertible(value: &Value, kind: &str) -> (ErrorForm, String) {
+    (ErrorForm::TypeError, message!("E0006-convert", type = value.type_name(), kind = kind))
+}
+
+fn inexact(value: &Value, kind: &str) -> (ErrorForm, String) {
+    (ErrorForm::RuntimeError, message!("E0007-convert-inexact", value = value, kind = kind))
+}
+
+fn unreadable(text: &str, kind: &str) -> (ErrorForm, String) {
+    (ErrorForm::RuntimeError, message!("E0007-convert-string", text = format!("{:?}", text), kind = kind))
+}
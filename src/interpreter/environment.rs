@@ -0,0 +1,71 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::interpreter::gc;
+use crate::interpreter::value::Value;
+
+// One lexical scope. Every `Body` gets a fresh environment whose parent is
+// the environment it was opened in.
+#[derive(Debug, Default)]
+pub struct Environment {
+    values: HashMap<String, Value>,
+    parent: Option<Rc<RefCell<Environment>>>
+}
+
+impl Environment {
+    pub fn new() -> Environment {
+        Environment { values: HashMap::new(), parent: None }
+    }
+
+    pub fn with_parent(parent: Rc<RefCell<Environment>>) -> Environment {
+        Environment { values: HashMap::new(), parent: Some(parent) }
+    }
+
+    // Puts the environment where closures can share it, registered with `gc`
+    // since a closure stored in its own scope is a cycle.
+    pub fn shared(self) -> Rc<RefCell<Environment>> {
+        let environment = Rc::new(RefCell::new(self));
+        gc::track(gc::Object::Environment(Rc::downgrade(&environment)));
+        environment
+    }
+
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.values.insert(String::from(name), value);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.values.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.parent.as_ref().and_then(|parent| parent.borrow().get(name))
+        }
+    }
+
+    // Updates the closest existing binding, returning false if there is none.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        if let Some(slot) = self.values.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+
+        match &self.parent {
+            Some(parent) => parent.borrow_mut().assign(name, value),
+            None => false
+        }
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.values.values()
+    }
+
+    pub fn parent(&self) -> Option<&Rc<RefCell<Environment>>> {
+        self.parent.as_ref()
+    }
+
+    // Empties the scope and cuts it off from its parent, handing back the
+    // values it held.
+    pub fn take(&mut self) -> Vec<Value> {
+        self.parent = None;
+        self.values.drain().map(|(_, value)| value).collect()
+    }
+}
@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::interpreter::environment::Environment;
+use crate::interpreter::value::{Closure, Code, Guard, Map, Method, Record, Structure, Tagged, Value};
+
+// `Rc` frees a value as soon as nothing refers to it, but values that refer
+// to each other keep each other alive forever. A list can contain itself, and
+// every named function sits in the scope, or the cell, it captured. So every
+// object that can hold other values is registered here, and `collect` looks
+// for groups of objects that are only referred to by each other.
+//
+// It works like CPython's collector. Every object starts with its strong
+// count, and every reference from one tracked object to another is taken off
+// the target's count. Anything left above zero is referred to from outside
+// the heap, by a variable in Rust or a scope that's still running, and so is
+// everything it can reach. The rest is garbage, and emptying the mutable
+// objects among it breaks the cycles so `Rc` can free them.
+
+// How many objects can be tracked before the next collection, at least.
+const THRESHOLD: usize = 10_000;
+
+pub enum Object {
+    Environment(Weak<RefCell<Environment>>),
+    Cell(Weak<RefCell<Value>>),
+    List(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<Map>>),
+    Record(Weak<RefCell<Record>>),
+    Closure(Weak<Closure>),
+    Method(Weak<Method>),
+    Guard(Weak<Guard>),
+    Structure(Weak<Structure>),
+    Tagged(Weak<Tagged>)
+}
+
+enum Strong {
+    Environment(Rc<RefCell<Environment>>),
+    Cell(Rc<RefCell<Value>>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Record(Rc<RefCell<Record>>),
+    Closure(Rc<Closure>),
+    Method(Rc<Method>),
+    Guard(Rc<Guard>),
+    Structure(Rc<Structure>),
+    Tagged(Rc<Tagged>)
+}
+
+struct Heap {
+    objects: Vec<Object>,
+    threshold: usize
+}
+
+thread_local! {
+    static HEAP: RefCell<Heap> = const { RefCell::new(Heap { objects: Vec::new(), threshold: THRESHOLD }) };
+}
+
+pub fn track(object: Object) {
+    HEAP.with(|heap| heap.borrow_mut().objects.push(object));
+}
+
+// Identifies an object by where it lives, whichever kind of pointer refers to it.
+pub fn address<T>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const u8 as usize
+}
+
+// Collects if enough objects were made since the last collection. The next
+// collection waits until the heap has doubled, so big heaps aren't scanned
+// over and over.
+pub fn collect_if_due() {
+    let due = HEAP.with(|heap| {
+        let heap = heap.borrow();
+        heap.objects.len() >= heap.threshold
+    });
+    if due {
+        collect();
+        HEAP.with(|heap| {
+            let mut heap = heap.borrow_mut();
+            heap.threshold = (heap.objects.len() * 2).max(THRESHOLD);
+        });
+    }
+}
+
+// Frees every unreachable cycle, returning how many objects were in them.
+pub fn collect() -> usize {
+    let objects = HEAP.with(|heap| std::mem::take(&mut heap.borrow_mut().objects));
+    let live = objects.iter().filter_map(Object::upgrade).collect::<Vec<_>>();
+    let index = live.iter().enumerate().map(|(i, object)| (object.address(), i)).collect::<HashMap<_, _>>();
+
+    // Our own upgrade is one of the strong references, so it doesn't count.
+    let mut references = live.iter().map(|object| object.strong_count() - 1).collect::<Vec<_>>();
+    let mut scanned = vec![false; live.len()];
+    for (i, object) in live.iter().enumerate() {
+        scanned[i] = object.children(&mut |child| {
+            if let Some(&j) = index.get(&child) {
+                references[j] -= 1;
+            }
+        });
+    }
+
+    // Objects that are borrowed right now are in use, and so is everything
+    // reachable from outside the heap.
+    let mut reachable = vec![false; live.len()];
+    let mut pending = (0..live.len()).filter(|&i| references[i] > 0 || !scanned[i]).collect::<Vec<_>>();
+    while let Some(i) = pending.pop() {
+        if reachable[i] {
+            continue;
+        }
+        reachable[i] = true;
+        live[i].children(&mut |child| {
+            if let Some(&j) = index.get(&child) {
+                if !reachable[j] {
+                    pending.push(j);
+                }
+            }
+        });
+    }
+
+    let mut contents = Vec::new();
+    let mut survivors = Vec::new();
+    let mut freed = 0;
+    for (object, reachable) in live.iter().zip(reachable) {
+        if reachable {
+            survivors.push(object.downgrade());
+        } else {
+            object.empty(&mut contents);
+            freed += 1;
+        }
+    }
+
+    HEAP.with(|heap| heap.borrow_mut().objects.extend(survivors));
+    // Only now, with no borrows left, let the contents go.
+    drop(live);
+    drop(contents);
+    freed
+}
+
+impl Object {
+    fn upgrade(&self) -> Option<Strong> {
+        Some(match self {
+            Object::Environment(weak) => Strong::Environment(weak.upgrade()?),
+            Object::Cell(weak) => Strong::Cell(weak.upgrade()?),
+            Object::List(weak) => Strong::List(weak.upgrade()?),
+            Object::Map(weak) => Strong::Map(weak.upgrade()?),
+            Object::Record(weak) => Strong::Record(weak.upgrade()?),
+            Object::Closure(weak) => Strong::Closure(weak.upgrade()?),
+            Object::Method(weak) => Strong::Method(weak.upgrade()?),
+            Object::Guard(weak) => Strong::Guard(weak.upgrade()?),
+            Object::Structure(weak) => Strong::Structure(weak.upgrade()?),
+            Object::Tagged(weak) => Strong::Tagged(weak.upgrade()?)
+        })
+    }
+}
+
+impl Strong {
+    fn downgrade(&self) -> Object {
+        match self {
+            Strong::Environment(rc) => Object::Environment(Rc::downgrade(rc)),
+            Strong::Cell(rc) => Object::Cell(Rc::downgrade(rc)),
+            Strong::List(rc) => Object::List(Rc::downgrade(rc)),
+            Strong::Map(rc) => Object::Map(Rc::downgrade(rc)),
+            Strong::Record(rc) => Object::Record(Rc::downgrade(rc)),
+            Strong::Closure(rc) => Object::Closure(Rc::downgrade(rc)),
+            Strong::Method(rc) => Object::Method(Rc::downgrade(rc)),
+            Strong::Guard(rc) => Object::Guard(Rc::downgrade(rc)),
+            Strong::Structure(rc) => Object::Structure(Rc::downgrade(rc)),
+            Strong::Tagged(rc) => Object::Tagged(Rc::downgrade(rc))
+        }
+    }
+
+    fn address(&self) -> usize {
+        match self {
+            Strong::Environment(rc) => address(rc),
+            Strong::Cell(rc) => address(rc),
+            Strong::List(rc) => address(rc),
+            Strong::Map(rc) => address(rc),
+            Strong::Record(rc) => address(rc),
+            Strong::Closure(rc) => address(rc),
+            Strong::Method(rc) => address(rc),
+            Strong::Guard(rc) => address(rc),
+            Strong::Structure(rc) => address(rc),
+            Strong::Tagged(rc) => address(rc)
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Strong::Environment(rc) => Rc::strong_count(rc),
+            Strong::Cell(rc) => Rc::strong_count(rc),
+            Strong::List(rc) => Rc::strong_count(rc),
+            Strong::Map(rc) => Rc::strong_count(rc),
+            Strong::Record(rc) => Rc::strong_count(rc),
+            Strong::Closure(rc) => Rc::strong_count(rc),
+            Strong::Method(rc) => Rc::strong_count(rc),
+            Strong::Guard(rc) => Rc::strong_count(rc),
+            Strong::Structure(rc) => Rc::strong_count(rc),
+            Strong::Tagged(rc) => Rc::strong_count(rc)
+        }
+    }
+
+    // Calls `visit` with the address of every object this one refers to,
+    // once per reference. Returns false if the object is borrowed mutably
+    // and couldn't be looked into.
+    fn children(&self, visit: &mut dyn FnMut(usize)) -> bool {
+        match self {
+            Strong::Environment(rc) => match rc.try_borrow() {
+                Ok(environment) => {
+                    environment.values().for_each(|value| value_children(value, visit));
+                    if let Some(parent) = environment.parent() {
+                        visit(address(parent));
+                    }
+                },
+                Err(_) => return false
+            },
+            Strong::Cell(rc) => match rc.try_borrow() {
+                Ok(value) => value_children(&value, visit),
+                Err(_) => return false
+            },
+            Strong::List(rc) => match rc.try_borrow() {
+                Ok(elements) => elements.iter().for_each(|value| value_children(value, visit)),
+                Err(_) => return false
+            },
+            Strong::Map(rc) => match rc.try_borrow() {
+                Ok(map) => map.iter().for_each(|(key, value)| {
+                    value_children(key, visit);
+                    value_children(value, visit);
+                }),
+                Err(_) => return false
+            },
+            Strong::Record(rc) => match rc.try_borrow() {
+                Ok(record) => {
+                    visit(address(&record.structure));
+                    record.values.iter().for_each(|value| value_children(value, visit));
+                },
+                Err(_) => return false
+            },
+            Strong::Closure(closure) => match &closure.code {
+                Code::Tree { environment, .. } => visit(address(environment)),
+                Code::Compiled { captures, .. } => captures.iter().for_each(|cell| visit(address(cell)))
+            },
+            Strong::Method(method) => {
+                value_children(&method.receiver, visit);
+                visit(address(&method.function));
+            },
+            Strong::Guard(guard) => value_children(&guard.function, visit),
+            Strong::Structure(structure) => structure.methods.values().for_each(|method| visit(address(method))),
+            Strong::Tagged(tagged) => tagged.values.iter().for_each(|value| value_children(value, visit))
+        }
+        true
+    }
+
+    // Takes everything out of a mutable object. The rest can't be emptied,
+    // but every cycle goes through a mutable object, so that's enough.
+    fn empty(&self, contents: &mut Vec<Value>) {
+        match self {
+            Strong::Environment(rc) => {
+                let mut environment = rc.borrow_mut();
+                contents.extend(environment.take());
+            },
+            Strong::Cell(rc) => contents.push(rc.replace(Value::Nil)),
+            Strong::List(rc) => contents.append(&mut rc.borrow_mut()),
+            Strong::Map(rc) => {
+                for (key, value) in rc.borrow_mut().take() {
+                    contents.push(key);
+                    contents.push(value);
+                }
+            },
+            Strong::Record(rc) => contents.append(&mut rc.borrow_mut().values),
+            _ => {}
+        }
+    }
+}
+
+fn value_children(value: &Value, visit: &mut dyn FnMut(usize)) {
+    match value {
+        Value::List(rc) => visit(address(rc)),
+        Value::Map(rc) => visit(address(rc)),
+        Value::Record(rc) => visit(address(rc)),
+        Value::Function(rc) => visit(address(rc)),
+        Value::Method(rc) => visit(address(rc)),
+        Value::Guarded(rc) => visit(address(rc)),
+        Value::Struct(rc) => visit(address(rc)),
+        Value::Variant(rc) => visit(address(rc)),
+        Value::Tuple(values) => values.iter().for_each(|value| value_children(value, visit)),
+        _ => {}
+    }
+}
@@ -0,0 +1,446 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::error::diagnostic::Error;
+use crate::error::diagnostic::ErrorForm;
+use crate::interpreter::access::{self, Call, Shape};
+use crate::interpreter::builtins;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::ops;
+use crate::interpreter::gc;
+use crate::interpreter::value::{Closure, Code, Enumeration, Map, Structure, Value};
+use crate::parser::ast::*;
+use crate::parser::position::Position;
+use crate::message;
+use crate::token;
+
+// Every call grows the native stack on demand, so this only exists to turn
+// runaway recursion into an error instead of exhausting memory.
+pub const MAX_CALL_DEPTH: usize = 10_000;
+
+// Evaluation stops early either because something went wrong or because a
+// `return` is travelling back up to its function call.
+enum Unwind {
+    Error(Error),
+    Return(Value)
+}
+
+impl From<Error> for Unwind {
+    fn from(error: Error) -> Unwind {
+        Unwind::Error(error)
+    }
+}
+
+type EvalResult = Result<Value, Unwind>;
+
+// A tree-walking interpreter. Running a list of statements gives back the
+// value of the last one, so a block works as an expression.
+pub struct Interpreter {
+    environment: Rc<RefCell<Environment>>,
+    depth: usize,
+    // Whether `/` between two integers gives an exact rational rather than
+    // a float.
+    pub exact_division: bool
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let mut globals = Environment::new();
+        for builtin in builtins::BUILTINS {
+            globals.define(builtin.name, Value::Builtin(builtin));
+        }
+
+        Interpreter {
+            environment: globals.shared(),
+            depth: 0,
+            exact_division: false
+        }
+    }
+
+    pub fn interpret(&mut self, code: &[AST]) -> Result<Value, Error> {
+        let result = match self.run(code) {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(Unwind::Error(error)) => Err(error)
+        };
+        gc::collect_if_due();
+        result
+    }
+
+    // Frees every reference cycle nothing can reach any more, returning how
+    // many objects were in them. This also happens on its own as the heap grows.
+    pub fn collect_garbage(&self) -> usize {
+        gc::collect()
+    }
+
+    fn run(&mut self, code: &[AST]) -> EvalResult {
+        let mut value = Value::Nil;
+        for ast in code {
+            value = self.execute(ast)?;
+        }
+        Ok(value)
+    }
+
+    fn execute(&mut self, ast: &AST) -> EvalResult {
+        match ast {
+            AST::Statement(statement) => self.statement(statement),
+            AST::Expression(expression) => self.evaluate(expression)
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> EvalResult {
+        match statement {
+            Statement::Declaration(declaration) => {
+                let value = self.evaluate(&declaration.value)?;
+                self.bind(&declaration.target, value, true)?;
+                Ok(Value::Nil)
+            },
+            Statement::Assignment(assignment) => {
+                let value = self.evaluate(&assignment.value)?;
+                self.bind(&assignment.target, value, false)?;
+                Ok(Value::Nil)
+            },
+            Statement::Function(function) => {
+                let closure = self.closure(function);
+                if let Some(name) = &function.name {
+                    self.environment.borrow_mut().define(&name.name, Value::Function(closure));
+                }
+                Ok(Value::Nil)
+            },
+            Statement::Struct(structure) => {
+                let methods = structure.methods.iter()
+                    .map(|method| (method.name.as_ref().map(|n| n.name.clone()).unwrap_or_default(), self.closure(method)))
+                    .collect::<HashMap<_, _>>();
+                let value = Value::structure(Structure {
+                    name: structure.name.name.clone(),
+                    fields: structure.fields.iter().map(|f| f.name.name.clone()).collect(),
+                    methods,
+                    position: structure.position
+                });
+                self.environment.borrow_mut().define(&structure.name.name, value);
+                Ok(Value::Nil)
+            },
+            Statement::Enum(enumeration) => {
+                let value = Value::Enum(Rc::new(Enumeration {
+                    name: enumeration.name.name.clone(),
+                    variants: enumeration.variants.iter()
+                        .map(|v| (v.name.name.clone(), v.fields.iter().map(|f| f.name.name.clone()).collect()))
+                        .collect(),
+                    position: enumeration.position
+                }));
+                self.environment.borrow_mut().define(&enumeration.name.name, value);
+                Ok(Value::Nil)
+            },
+            Statement::Return(ret) => {
+                let value = match &ret.value {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Nil
+                };
+                Err(Unwind::Return(value))
+            },
+            Statement::Body(body) => {
+                let environment = Environment::with_parent(self.environment.clone());
+                self.run_in(environment, &body.code)
+            }
+        }
+    }
+
+    fn run_in(&mut self, environment: Environment, code: &[AST]) -> EvalResult {
+        let previous = std::mem::replace(&mut self.environment, environment.shared());
+        let result = self.run(code);
+        self.environment = previous;
+        result
+    }
+
+    fn closure(&self, function: &Function) -> Rc<Closure> {
+        Value::function(Closure {
+            name: function.name.as_ref().map(|n| n.name.clone()),
+            parameters: function.parameters.iter().map(|p| p.name.name.clone()).collect(),
+            code: Code::Tree { body: function.body.clone(), environment: self.environment.clone() },
+            position: function.position
+        })
+    }
+
+    fn call(&mut self, callee: &Value, arguments: Vec<Value>, position: Position) -> EvalResult {
+        let (function, arguments) = match access::call(callee, arguments, position)? {
+            Call::Enter(function, arguments) => (function, arguments),
+            Call::Guarded(guard, arguments) => {
+                let result = self.call(&guard.function, arguments, position)?;
+                return Ok(access::guard_result(&guard, result, position)?);
+            },
+            Call::Returned(value) => return Ok(value)
+        };
+
+        let (body, captured) = match &function.code {
+            Code::Tree { body, environment } => (body.clone(), environment.clone()),
+            Code::Compiled { .. } => unreachable!("compiled functions only run in the VM")
+        };
+
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(error(
+                ErrorForm::RuntimeError,
+                message!("E0007-call-depth", depth = MAX_CALL_DEPTH),
+                position
+            ));
+        }
+
+        gc::collect_if_due();
+
+        // Parameters live in a fresh scope whose parent is the captured one.
+        let mut environment = Environment::with_parent(captured);
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            environment.define(parameter, argument);
+        }
+
+        self.depth += 1;
+        let result = stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.run_in(environment, &body.code));
+        self.depth -= 1;
+
+        match result {
+            Ok(value) | Err(Unwind::Return(value)) => Ok(value),
+            Err(error) => Err(error)
+        }
+    }
+
+    // Operands nest as deep as the chain of operators they're in, so this can
+    // recurse far without any calls.
+    fn evaluate(&mut self, expression: &Expression) -> EvalResult {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.evaluate_expression(expression))
+    }
+
+    fn evaluate_expression(&mut self, expression: &Expression) -> EvalResult {
+        match expression {
+            Expression::Literal(literal) => Ok(Value::from_form(&literal.form)),
+            Expression::Identifier(identifier) => {
+                self.environment.borrow().get(&identifier.name).ok_or_else(|| error(
+                    ErrorForm::NameError,
+                    message!("E0003-undeclared-variable", name = identifier.name),
+                    identifier.position
+                ))
+            },
+            Expression::BinaryOperation(b) => match b.operator {
+                // `and` and `or` short-circuit, and give back whichever operand decided the result.
+                token!(and) => {
+                    let left = self.evaluate(&b.loperand)?;
+                    if !left.is_truthy() {
+                        return Ok(left);
+                    }
+                    self.evaluate(&b.roperand)
+                },
+                token!(or) => {
+                    let left = self.evaluate(&b.loperand)?;
+                    if left.is_truthy() {
+                        return Ok(left);
+                    }
+                    self.evaluate(&b.roperand)
+                },
+                operator => {
+                    let left = self.evaluate(&b.loperand)?;
+                    let right = self.evaluate(&b.roperand)?;
+                    let result = if operator == token!(/) && self.exact_division {
+                        ops::divide_exactly(&left, &right)
+                    } else {
+                        ops::binary(operator, &left, &right)
+                    };
+                    result.map_err(|(kind, message)| error(kind, message, b.position))
+                }
+            },
+            Expression::UnaryOperation(u) => {
+                let operand = self.evaluate(&u.operand)?;
+                ops::unary(u.operator, &operand).map_err(|(kind, message)| error(kind, message, u.position))
+            },
+            Expression::Comparison(c) => {
+                let mut left = self.evaluate(&c.operands[0])?;
+                for (operator, operand) in c.operators.iter().zip(c.operands.iter().skip(1)) {
+                    let right = self.evaluate(operand)?;
+                    let result = ops::binary(*operator, &left, &right)
+                        .map_err(|(kind, message)| error(kind, message, c.position))?;
+                    if !result.is_truthy() {
+                        return Ok(result);
+                    }
+                    left = right;
+                }
+                Ok(Value::Boolean(true))
+            },
+            Expression::Tuple(t) => Ok(Value::Tuple(self.sequence(&t.elements)?)),
+            Expression::List(l) => Ok(Value::list(self.sequence(&l.elements)?)),
+            Expression::Spread(s) => Err(error(
+                ErrorForm::SyntaxError,
+                message!("E0001-bare-spread"),
+                s.position
+            )),
+            Expression::Cast(c) => {
+                let value = self.evaluate(&c.value)?;
+                Ok(access::cast(value, &c.ty, c.value.position(), c.declared, c.origin)?)
+            },
+            Expression::Attribute(a) => {
+                let object = self.evaluate(&a.object)?;
+                Ok(access::attribute(&object, &a.name.name, a.name.position)?)
+            },
+            Expression::Index(i) => {
+                let object = self.evaluate(&i.object)?;
+                let index = self.evaluate(&i.index)?;
+                Ok(access::index(&object, &index, i.position)?)
+            },
+            Expression::Call(c) => {
+                let callee = self.evaluate(&c.callee)?;
+                let arguments = self.sequence(&c.arguments)?;
+                self.call(&callee, arguments, c.position)
+            },
+            Expression::Lambda(l) => Ok(Value::Function(self.closure(l))),
+            Expression::Match(m) => self.match_expression(m),
+            Expression::Construction(c) => self.construction(c),
+            Expression::Map(m) => {
+                let mut map = Map::new();
+                for entry in &m.entries {
+                    let key = self.evaluate(&entry.key)?;
+                    let value = self.evaluate(&entry.value)?;
+                    map.insert(key, value).map_err(|key| access::unhashable(&key, entry.key.position()))?;
+                }
+                Ok(Value::map(map))
+            }
+        }
+    }
+
+    fn construction(&mut self, construction: &Construction) -> EvalResult {
+        let name = &construction.name;
+        let structure = self.evaluate(&Expression::Identifier(name.clone()))?;
+        let mut fields = Vec::new();
+        for field in &construction.fields {
+            fields.push((field.name.name.as_str(), field.name.position, self.evaluate(&field.value)?));
+        }
+        Ok(access::record(structure, &name.name, name.position, fields, construction.position)?)
+    }
+
+    fn match_expression(&mut self, m: &Match) -> EvalResult {
+        let value = self.evaluate(&m.scrutinee)?;
+
+        for arm in &m.arms {
+            let mut bindings = Vec::new();
+            if !access::matches(&arm.pattern, &value, &mut bindings) {
+                continue;
+            }
+
+            // Bindings, the guard and the body all share one scope per arm.
+            let mut environment = Environment::with_parent(self.environment.clone());
+            for (name, value) in bindings {
+                environment.define(&name, value);
+            }
+            let previous = std::mem::replace(&mut self.environment, environment.shared());
+
+            let guarded = match &arm.guard {
+                Some(guard) => self.evaluate(guard).map(|value| value.is_truthy()),
+                None => Ok(true)
+            };
+            let result = match guarded {
+                Ok(true) => Some(self.run(&arm.body.code)),
+                Ok(false) => None,
+                Err(error) => Some(Err(error))
+            };
+
+            self.environment = previous;
+            if let Some(result) = result {
+                return result;
+            }
+        }
+
+        Err(error(
+            ErrorForm::RuntimeError,
+            message!("E0007-no-matching-arm", value = value),
+            m.position
+        ))
+    }
+
+    fn sequence(&mut self, elements: &[Expression]) -> Result<Vec<Value>, Unwind> {
+        let mut values = Vec::new();
+
+        for element in elements {
+            if let Expression::Spread(s) = element {
+                let value = self.evaluate(&s.value)?;
+                values.extend(access::spread(&value, s.position)?);
+            } else {
+                values.push(self.evaluate(element)?);
+            }
+        }
+
+        Ok(values)
+    }
+
+    // Stores `value` into `target`, either declaring new names or updating
+    // existing ones.
+    fn bind(&mut self, target: &Target, value: Value, declare: bool) -> Result<(), Unwind> {
+        match target {
+            Target::Identifier(identifier) => self.bind_name(identifier, value, declare),
+            Target::Rest(rest) => self.bind_name(&rest.name, value, declare),
+            Target::Tuple(sequence) | Target::List(sequence) => self.unpack(sequence, value, declare),
+            Target::Attribute(a) => {
+                let object = self.evaluate(&a.object)?;
+                Ok(access::store_attribute(&object, &a.name.name, value, a.name.position)?)
+            },
+            Target::Index(i) => {
+                let object = self.evaluate(&i.object)?;
+                let index = self.evaluate(&i.index)?;
+                Ok(access::store_index(&object, index, value, i.position, i.index.position())?)
+            }
+        }
+    }
+
+    fn bind_name(&mut self, identifier: &Identifier, value: Value, declare: bool) -> Result<(), Unwind> {
+        if declare {
+            self.environment.borrow_mut().define(&identifier.name, value);
+            return Ok(());
+        }
+
+        if self.environment.borrow_mut().assign(&identifier.name, value) {
+            Ok(())
+        } else {
+            Err(error(
+                ErrorForm::NameError,
+                message!("E0003-undeclared-assignment", name = identifier.name),
+                identifier.position
+            ))
+        }
+    }
+
+    fn unpack(&mut self, sequence: &TargetSequence, value: Value, declare: bool) -> Result<(), Unwind> {
+        let targets = &sequence.targets;
+        let rest = targets.iter().position(|target| matches!(target, Target::Rest(_)));
+        let values = access::unpack(&value, targets.len(), rest, sequence.position)?;
+        for (target, value) in targets.iter().zip(values) {
+            self.bind(target, value, declare)?;
+        }
+        Ok(())
+    }
+}
+
+impl access::Matchable for Pattern {
+    type Target = String;
+
+    fn shape(&self) -> Shape<'_, Pattern> {
+        match self {
+            Pattern::Wildcard(_) => Shape::Wildcard,
+            Pattern::Binding(identifier) => Shape::Binding(&identifier.name),
+            Pattern::Literal(literal) => Shape::Literal(&literal.form),
+            Pattern::Range(range) => Shape::Range(&range.start.form, &range.end.form, range.inclusive),
+            Pattern::Tuple(sequence) => Shape::Tuple(&sequence.patterns),
+            Pattern::List(sequence) => Shape::List(&sequence.patterns),
+            Pattern::Rest(rest) => Shape::Rest(rest.name.as_ref().map(|name| &name.name)),
+            Pattern::Alternation(alternatives) => Shape::Alternation(&alternatives.patterns),
+            Pattern::Variant(pattern) => Shape::Variant {
+                enumeration: &pattern.enumeration.name,
+                variant: &pattern.variant.name,
+                fields: pattern.fields.as_ref().map(|fields| &fields.patterns[..])
+            }
+        }
+    }
+}
+
+fn error(kind: ErrorForm, message: String, position: Position) -> Unwind {
+    Unwind::Error(access::error(kind, message, position))
+}
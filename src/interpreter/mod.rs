@@ -0,0 +1,8 @@
+pub mod value;
+pub mod environment;
+pub mod gc;
+pub mod numeric;
+pub mod ops;
+pub mod builtins;
+pub mod access;
+pub mod interpret;
\ No newline at end of file
@@ -0,0 +1,209 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, ToPrimitive, Zero};
+
+use crate::interpreter::value::Value;
+
+// The four kinds of number, ordered from the most restricted to the most
+// general. Integers are decimals with nothing after the point, and decimals
+// are rationals whose denominator is a power of ten, so the first three are
+// always exact. Floats come last because they can stand in for any of them,
+// at the cost of rounding.
+//
+// An operation on two different kinds works in the later of the two, so
+// `1 + 0.5d` is a decimal and `0.5d + 1.5` is a float.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rank {
+    Integer,
+    Decimal,
+    Rational,
+    Float
+}
+
+pub fn rank(value: &Value) -> Option<Rank> {
+    match value {
+        Value::Integer(_) | Value::BigInteger(_) => Some(Rank::Integer),
+        Value::Decimal(_) => Some(Rank::Decimal),
+        Value::Rational(_) => Some(Rank::Rational),
+        Value::Float(_) => Some(Rank::Float),
+        _ => None
+    }
+}
+
+pub fn to_integer(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Integer(i) => Some(BigInt::from(*i)),
+        Value::BigInteger(i) => Some(i.clone()),
+        _ => None
+    }
+}
+
+// The nearest float to any number, or an infinity past the largest one.
+pub fn to_float(value: &Value) -> Option<f64> {
+    let infinity = if is_negative(value) { f64::NEG_INFINITY } else { f64::INFINITY };
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::BigInteger(i) => Some(i.to_f64().unwrap_or(infinity)),
+        Value::Decimal(d) => Some(d.to_f64().unwrap_or(infinity)),
+        Value::Rational(r) => Some(r.to_f64().unwrap_or(infinity)),
+        Value::Float(f) => Some(*f),
+        _ => None
+    }
+}
+
+pub fn to_decimal(value: &Value) -> Option<BigDecimal> {
+    match value {
+        Value::Integer(i) => Some(BigDecimal::from(*i)),
+        Value::BigInteger(i) => Some(BigDecimal::new(i.clone(), 0)),
+        Value::Decimal(d) => Some(d.clone()),
+        Value::Rational(r) => terminating(r),
+        _ => None
+    }
+}
+
+// The exact value of any number. Floats keep every binary digit they have,
+// so `0.1` is not quite `1/10`. NaN and the infinities have no exact value.
+pub fn to_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::Integer(i) => Some(BigRational::from_integer(BigInt::from(*i))),
+        Value::BigInteger(i) => Some(BigRational::from_integer(i.clone())),
+        Value::Decimal(d) => Some(decimal_to_rational(d)),
+        Value::Rational(r) => Some(r.clone()),
+        Value::Float(f) => BigRational::from_float(*f),
+        _ => None
+    }
+}
+
+pub fn decimal_to_rational(d: &BigDecimal) -> BigRational {
+    let (digits, scale) = d.as_bigint_and_exponent();
+    let power = BigInt::from(10).pow(scale.unsigned_abs() as u32);
+    if scale >= 0 {
+        BigRational::new(digits, power)
+    } else {
+        BigRational::from_integer(digits * power)
+    }
+}
+
+// The decimal a fraction is equal to, if it has one: only fractions whose
+// denominator divides a power of ten end.
+pub fn terminating(r: &BigRational) -> Option<BigDecimal> {
+    let mut denominator = r.denom().clone();
+    let (two, five) = (BigInt::from(2), BigInt::from(5));
+    let (mut twos, mut fives) = (0, 0);
+    while (&denominator % &two).is_zero() {
+        denominator /= &two;
+        twos += 1;
+    }
+    while (&denominator % &five).is_zero() {
+        denominator /= &five;
+        fives += 1;
+    }
+    if !denominator.is_one() {
+        return None;
+    }
+
+    let scale = twos.max(fives);
+    let digits = r.numer() * BigInt::from(10).pow(scale) / r.denom();
+    Some(BigDecimal::new(digits, i64::from(scale)))
+}
+
+// A float written as it prints, so `decimal(0.1)` is `0.1d` rather than the
+// long binary value the float really holds.
+pub fn float_as_decimal(f: f64) -> Option<BigDecimal> {
+    if f.is_finite() { BigDecimal::from_str(&format!("{:?}", f)).ok() } else { None }
+}
+
+// Compares two numbers of any kinds exactly, rather than rounding one to the
+// other's kind first.
+pub fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    let infinite = |value: &Value| matches!(value, Value::Float(f) if f.is_infinite());
+    match (a, b) {
+        (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+        (Value::Float(f), _) if f.is_nan() => None,
+        (_, Value::Float(f)) if f.is_nan() => None,
+        (Value::Float(f), _) if infinite(a) => Some(if *f > 0.0 { Ordering::Greater } else { Ordering::Less }),
+        (_, Value::Float(f)) if infinite(b) => Some(if *f > 0.0 { Ordering::Less } else { Ordering::Greater }),
+        _ => Some(to_rational(a)?.cmp(&to_rational(b)?))
+    }
+}
+
+// Hashes a number so that numbers `compare` calls equal hash the same
+// whatever their kinds. Integers are hashed as they are; everything else is
+// hashed by its exact value, as an integer when it is a whole number.
+pub fn hash<H: Hasher>(value: &Value, state: &mut H) {
+    match value {
+        Value::Integer(i) => (2, i).hash(state),
+        Value::Float(f) if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 => (2, *f as i64).hash(state),
+        Value::Float(f) if !f.is_finite() => (3, f.to_bits()).hash(state),
+        _ => {
+            let r = to_rational(value).expect("only numbers are hashed as numbers");
+            if !r.is_integer() {
+                (12, r.numer(), r.denom()).hash(state);
+            } else if let Ok(i) = i64::try_from(r.numer()) {
+                (2, i).hash(state);
+            } else {
+                (11, r.numer()).hash(state);
+            }
+        }
+    }
+}
+
+// The most bits an exact result of `^` or `<<` can take, whatever kind of
+// number it is. Anything bigger takes too long to work out and too much
+// memory to hold.
+pub const MAX_BITS: u64 = 1 << 20;
+
+// Whether raising a base that takes `bits` bits to write out to `exponent`
+// stays within `MAX_BITS`. Bases of at most one bit, like 0, 1 and -1, stay
+// the same size.
+pub fn power_fits(bits: u64, exponent: &BigInt) -> bool {
+    power_within(bits, exponent, MAX_BITS)
+}
+
+pub fn power_within(bits: u64, exponent: &BigInt, limit: u64) -> bool {
+    bits <= 1 || exponent.to_u64().is_some_and(|exponent| bits.saturating_mul(exponent) <= limit)
+}
+
+// The bits it takes to write out an exact number.
+pub fn bits(value: &Value) -> Option<u64> {
+    match value {
+        Value::Integer(i) => Some(BigInt::from(*i).bits()),
+        Value::BigInteger(i) => Some(i.bits()),
+        Value::Decimal(d) => Some(decimal_bits(d)),
+        Value::Rational(r) => Some(rational_bits(r)),
+        _ => None
+    }
+}
+
+// The bits it takes to write out a decimal: its digits, and about four more
+// for every place after the point.
+pub fn decimal_bits(decimal: &BigDecimal) -> u64 {
+    let (digits, scale) = decimal.normalized().into_bigint_and_exponent();
+    digits.bits() + 4 * scale.max(0) as u64
+}
+
+pub fn rational_bits(rational: &BigRational) -> u64 {
+    rational.numer().bits() + rational.denom().bits() - 1
+}
+
+// Raises an exact number to an integer power by repeated multiplication.
+pub fn power<T: Clone + One + std::ops::Mul<Output = T>>(base: T, exponent: &BigInt) -> Option<T> {
+    exponent.to_usize().map(|exponent| num_traits::pow(base, exponent))
+}
+
+pub fn is_negative(value: &Value) -> bool {
+    match value {
+        Value::Integer(i) => *i < 0,
+        Value::BigInteger(i) => i.is_negative(),
+        Value::Decimal(d) => d.is_negative(),
+        Value::Rational(r) => r.is_negative(),
+        Value::Float(f) => *f < 0.0,
+        _ => false
+    }
+}
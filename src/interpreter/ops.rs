@@ -0,0 +1,445 @@
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::error::diagnostic::ErrorForm;
+use crate::interpreter::numeric::{self, Rank};
+use crate::interpreter::value::Value;
+use crate::parser::token::TokenType;
+use crate::message;
+use crate::token;
+
+// Operator semantics shared by everything that evaluates code. Failures only
+// carry a message; the caller knows the span to attach it to.
+pub type OperationResult = Result<Value, (ErrorForm, String)>;
+
+pub fn binary(operator: TokenType, left: &Value, right: &Value) -> OperationResult {
+    match operator {
+        token!(==) => Ok(Value::Boolean(equals(left, right))),
+        token!(!=) => Ok(Value::Boolean(!equals(left, right))),
+        token!(<) | token!(<=) | token!(>) | token!(>=) => {
+            let ordering = compare(left, right).ok_or_else(|| mismatch(operator, left, right))?;
+            Ok(Value::Boolean(match operator {
+                token!(<) => ordering == Ordering::Less,
+                token!(<=) => ordering != Ordering::Greater,
+                token!(>) => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less
+            }))
+        },
+        _ => arithmetic(operator, left, right)
+    }
+}
+
+pub fn unary(operator: TokenType, operand: &Value) -> OperationResult {
+    match (operator, operand) {
+        (token!(not), value) => Ok(Value::Boolean(!value.is_truthy())),
+        (token!(-), Value::Integer(i)) => Ok(i.checked_neg().map_or_else(|| Value::integer(-BigInt::from(*i)), Value::Integer)),
+        (token!(-), Value::BigInteger(i)) => Ok(Value::integer(-i)),
+        (token!(-), Value::Decimal(d)) => Ok(Value::Decimal(-d)),
+        (token!(-), Value::Rational(r)) => Ok(Value::rational(-r)),
+        (token!(-), Value::Float(f)) => Ok(Value::Float(-f)),
+        (token!(~), Value::Integer(i)) => Ok(Value::Integer(!i)),
+        (token!(~), Value::BigInteger(i)) => Ok(Value::integer(!i)),
+        (_, value) => Err((
+            ErrorForm::TypeError,
+            message!("E0006-unary-operand", operator = operator, type = value.type_name())
+        ))
+    }
+}
+
+fn arithmetic(operator: TokenType, left: &Value, right: &Value) -> OperationResult {
+    if let (Some(a), Some(b)) = (numeric::rank(left), numeric::rank(right)) {
+        let exact = "numbers of a lower rank are exact";
+        return match a.max(b) {
+            Rank::Integer => match (left, right) {
+                (Value::Integer(a), Value::Integer(b)) => integer(operator, *a, *b),
+                _ => big_integer(operator, numeric::to_integer(left).expect(exact), numeric::to_integer(right).expect(exact))
+            },
+            Rank::Decimal => decimal(operator, numeric::to_decimal(left).expect(exact), numeric::to_decimal(right).expect(exact)),
+            Rank::Rational => rational(operator, numeric::to_rational(left).expect(exact), numeric::to_rational(right).expect(exact)),
+            Rank::Float => float(operator, as_float(left), as_float(right))
+        };
+    }
+
+    match (left, right) {
+        (Value::Boolean(a), Value::Boolean(b)) => match operator {
+            token!(&) => Ok(Value::Boolean(a & b)),
+            token!(|) => Ok(Value::Boolean(a | b)),
+            token!(@) => Ok(Value::Boolean(a ^ b)),
+            _ => Err(mismatch(operator, left, right))
+        },
+        (Value::String(a), Value::String(b)) if operator == token!(+) => Ok(Value::String(format!("{}{}", a, b))),
+        (Value::Tuple(a), Value::Tuple(b)) if operator == token!(+) => {
+            Ok(Value::Tuple(a.iter().chain(b.iter()).cloned().collect()))
+        },
+        (Value::List(a), Value::List(b)) if operator == token!(+) => {
+            let elements = a.borrow().iter().chain(b.borrow().iter()).cloned().collect();
+            Ok(Value::list(elements))
+        },
+        _ => Err(mismatch(operator, left, right))
+    }
+}
+
+// Division that keeps integers exact, giving a rational when they don't
+// divide evenly. It is only used when exact division has been asked for;
+// otherwise `/` between integers gives a float.
+pub fn divide_exactly(left: &Value, right: &Value) -> OperationResult {
+    match (numeric::to_integer(left), numeric::to_integer(right)) {
+        (Some(a), Some(b)) => rational(token!(/), BigRational::from_integer(a), BigRational::from_integer(b)),
+        _ => binary(token!(/), left, right)
+    }
+}
+
+fn integer(operator: TokenType, a: i64, b: i64) -> OperationResult {
+    let result = match operator {
+        token!(+) => a.checked_add(b),
+        token!(-) => a.checked_sub(b),
+        token!(*) => a.checked_mul(b),
+        token!(/) => {
+            if b == 0 {
+                return Err(division_by_zero());
+            }
+            return Ok(Value::Float(a as f64 / b as f64));
+        },
+        token!(%) => {
+            if b == 0 {
+                return Err(division_by_zero());
+            }
+            a.checked_rem(b).map(|r| if r != 0 && (r < 0) != (b < 0) { r + b } else { r })
+        },
+        token!(^) => {
+            if b < 0 {
+                return Ok(Value::Float((a as f64).powf(b as f64)));
+            }
+            u32::try_from(b).ok().and_then(|b| a.checked_pow(b))
+        },
+        token!(&) => Some(a & b),
+        token!(|) => Some(a | b),
+        token!(@) => Some(a ^ b),
+        token!(<<) | token!(>>) => {
+            if b < 0 {
+                return Err((ErrorForm::RuntimeError, message!("E0007-negative-shift", amount = b)));
+            }
+            if operator == token!(>>) {
+                Some(if b >= 64 { if a < 0 { -1 } else { 0 } } else { a >> b })
+            } else if b >= 64 {
+                if a == 0 { Some(0) } else { None }
+            } else {
+                let shifted = a << b;
+                if shifted >> b == a { Some(shifted) } else { None }
+            }
+        },
+        _ => return Err(mismatch(operator, &Value::Integer(a), &Value::Integer(b)))
+    };
+
+    // Anything too big for an `i64` is worked out again with no limit.
+    match result {
+        Some(result) => Ok(Value::Integer(result)),
+        None => big_integer(operator, BigInt::from(a), BigInt::from(b))
+    }
+}
+
+// Integer arithmetic that can't overflow. The result goes back to being an
+// `Integer` as soon as it fits in one again.
+fn big_integer(operator: TokenType, a: BigInt, b: BigInt) -> OperationResult {
+    let result = match operator {
+        token!(+) => a + b,
+        token!(-) => a - b,
+        token!(*) => a * b,
+        token!(/) => {
+            if b.is_zero() {
+                return Err(division_by_zero());
+            }
+            return Ok(Value::Float(as_float(&Value::BigInteger(a)) / as_float(&Value::BigInteger(b))));
+        },
+        token!(%) => {
+            if b.is_zero() {
+                return Err(division_by_zero());
+            }
+            a.mod_floor(&b)
+        },
+        token!(^) => {
+            if b.is_negative() {
+                return Ok(Value::Float(as_float(&Value::integer(a)).powf(as_float(&Value::integer(b)))));
+            }
+            if a.abs() <= BigInt::from(1) {
+                return Ok(Value::integer(if a.is_negative() && b.is_even() { a.abs() } else if b.is_zero() { BigInt::from(1) } else { a }));
+            }
+            if !numeric::power_fits(a.bits(), &b) {
+                return Err(too_big(operator));
+            }
+            a.pow(b.to_u32().expect("exponents that fit are small"))
+        },
+        token!(&) => a & b,
+        token!(|) => a | b,
+        token!(@) => a ^ b,
+        token!(<<) | token!(>>) => {
+            if b.is_negative() {
+                return Err((ErrorForm::RuntimeError, message!("E0007-negative-shift", amount = b)));
+            }
+            match (operator, b.to_u32()) {
+                (token!(<<), _) if a.is_zero() => a,
+                (token!(<<), Some(b)) if a.bits() + b as u64 <= numeric::MAX_BITS => a << b,
+                (token!(<<), _) => return Err(too_big(operator)),
+                (_, Some(b)) => a >> b,
+                (_, None) => if a.is_negative() { BigInt::from(-1) } else { BigInt::zero() }
+            }
+        },
+        _ => return Err(mismatch(operator, &Value::integer(a), &Value::integer(b)))
+    };
+
+    Ok(Value::integer(result))
+}
+
+fn as_float(number: &Value) -> f64 {
+    numeric::to_float(number).expect("only numbers are converted")
+}
+
+// Decimals stay exact, so dividing two of them gives a rational when the
+// result never ends, like `1d / 3`.
+fn decimal(operator: TokenType, a: BigDecimal, b: BigDecimal) -> OperationResult {
+    let result = match operator {
+        token!(+) => a + b,
+        token!(-) => a - b,
+        token!(*) => a * b,
+        token!(/) => return exact_quotient(rational(operator, numeric::decimal_to_rational(&a), numeric::decimal_to_rational(&b))?),
+        token!(^) if b.is_integer() && !b.is_negative() => {
+            let (exponent, _) = b.with_scale(0).into_bigint_and_exponent();
+            if !numeric::power_fits(numeric::decimal_bits(&a), &exponent) {
+                return Err(too_big(operator));
+            }
+            numeric::power(a, &exponent).ok_or_else(|| overflow(operator))?
+        },
+        token!(^) if b.is_integer() => {
+            return exact_quotient(rational(operator, numeric::decimal_to_rational(&a), numeric::decimal_to_rational(&b))?)
+        },
+        token!(^) => return float(operator, as_float(&Value::Decimal(a)), as_float(&Value::Decimal(b))),
+        token!(%) => {
+            if b.is_zero() {
+                return Err(division_by_zero());
+            }
+            let r = &a % &b;
+            if !r.is_zero() && r.is_negative() != b.is_negative() { r + b } else { r }
+        },
+        _ => return Err(mismatch(operator, &Value::Decimal(a), &Value::Decimal(b)))
+    };
+
+    Ok(Value::Decimal(result))
+}
+
+// A quotient of decimals as a decimal, if it can be written as one.
+fn exact_quotient(quotient: Value) -> OperationResult {
+    Ok(match quotient {
+        Value::Rational(r) => numeric::terminating(&r).map_or(Value::Rational(r), Value::Decimal),
+        value => numeric::to_decimal(&value).map_or(value, Value::Decimal)
+    })
+}
+
+fn rational(operator: TokenType, a: BigRational, b: BigRational) -> OperationResult {
+    let result = match operator {
+        token!(+) => a + b,
+        token!(-) => a - b,
+        token!(*) => a * b,
+        token!(/) => {
+            if b.is_zero() {
+                return Err(division_by_zero());
+            }
+            a / b
+        },
+        token!(%) => {
+            if b.is_zero() {
+                return Err(division_by_zero());
+            }
+            let quotient = (&a / &b).floor();
+            a - b * quotient
+        },
+        token!(^) => {
+            if !b.is_integer() {
+                return float(operator, as_float(&Value::Rational(a)), as_float(&Value::Rational(b)));
+            }
+            if !numeric::power_fits(numeric::rational_bits(&a), &b.to_integer().abs()) {
+                return Err(too_big(operator));
+            }
+            let exponent = i32::try_from(b.to_integer()).map_err(|_| overflow(operator))?;
+            if a.is_zero() && exponent < 0 {
+                return Err(division_by_zero());
+            }
+            a.pow(exponent)
+        },
+        _ => return Err(mismatch(operator, &Value::rational(a), &Value::rational(b)))
+    };
+
+    Ok(Value::rational(result))
+}
+
+fn float(operator: TokenType, a: f64, b: f64) -> OperationResult {
+    let result = match operator {
+        token!(+) => a + b,
+        token!(-) => a - b,
+        token!(*) => a * b,
+        token!(/) => {
+            if b == 0.0 {
+                return Err(division_by_zero());
+            }
+            a / b
+        },
+        token!(%) => {
+            if b == 0.0 {
+                return Err(division_by_zero());
+            }
+            let r = a % b;
+            if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+        },
+        token!(^) => a.powf(b),
+        _ => return Err(mismatch(operator, &Value::Float(a), &Value::Float(b)))
+    };
+
+    Ok(Value::Float(result))
+}
+
+pub fn equals(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Nil, Value::Nil) => true,
+        (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        (Value::Integer(a), Value::Integer(b)) => a == b,
+        (Value::Float(a), Value::Float(b)) => a == b,
+        _ if numeric::rank(left).is_some() && numeric::rank(right).is_some() => {
+            numeric::compare(left, right) == Some(Ordering::Equal)
+        },
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Tuple(a), Value::Tuple(b)) => sequence_equals(a, b),
+        // Containers are equal to themselves without looking inside, which
+        // also keeps a list that contains itself from recursing forever.
+        (Value::List(a), Value::List(b)) => Rc::ptr_eq(a, b) || sequence_equals(&a.borrow(), &b.borrow()),
+        (Value::Map(a), Value::Map(b)) => Rc::ptr_eq(a, b) || {
+            let (a, b) = (a.borrow(), b.borrow());
+            a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).is_some_and(|other| equals(value, other)))
+        },
+        (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+        (Value::Builtin(a), Value::Builtin(b)) => a.name == b.name,
+        (Value::Method(a), Value::Method(b)) => Rc::ptr_eq(&a.function, &b.function) && equals(&a.receiver, &b.receiver),
+        // A checked function is still the function it checks.
+        (Value::Guarded(a), _) => equals(&a.function, right),
+        (_, Value::Guarded(b)) => equals(left, &b.function),
+        (Value::Struct(a), Value::Struct(b)) => Rc::ptr_eq(a, b),
+        (Value::Record(a), Value::Record(b)) => Rc::ptr_eq(a, b) || {
+            let (a, b) = (a.borrow(), b.borrow());
+            Rc::ptr_eq(&a.structure, &b.structure) && sequence_equals(&a.values, &b.values)
+        },
+        (Value::Enum(a), Value::Enum(b)) => Rc::ptr_eq(a, b),
+        (Value::Constructor(a, i), Value::Constructor(b, j)) => Rc::ptr_eq(a, b) && i == j,
+        (Value::Variant(a), Value::Variant(b)) => {
+            Rc::ptr_eq(&a.enumeration, &b.enumeration) && a.index == b.index && sequence_equals(&a.values, &b.values)
+        },
+        _ => false
+    }
+}
+
+fn sequence_equals(a: &[Value], b: &[Value]) -> bool {
+    a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| equals(a, b))
+}
+
+pub fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+        _ if numeric::rank(left).is_some() && numeric::rank(right).is_some() => numeric::compare(left, right),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Tuple(a), Value::Tuple(b)) => compare_sequences(a, b),
+        (Value::List(a), Value::List(b)) if Rc::ptr_eq(a, b) => Some(Ordering::Equal),
+        (Value::List(a), Value::List(b)) => compare_sequences(&a.borrow(), &b.borrow()),
+        _ => None
+    }
+}
+
+// Only values that can't change can be map keys, or a key could end up in
+// the wrong bucket. Functions, structs and enums are compared by identity, so
+// they can't change either.
+pub fn hashable(value: &Value) -> bool {
+    match value {
+        Value::Nil | Value::Boolean(_) | Value::String(_) => true,
+        Value::Integer(_) | Value::BigInteger(_) | Value::Decimal(_) | Value::Rational(_) | Value::Float(_) => true,
+        Value::Function(_) | Value::Builtin(_) | Value::Struct(_) | Value::Enum(_) | Value::Constructor(_, _) => true,
+        Value::Tuple(values) => values.iter().all(hashable),
+        Value::Variant(tagged) => tagged.values.iter().all(hashable),
+        Value::Guarded(guard) => hashable(&guard.function),
+        Value::List(_) | Value::Map(_) | Value::Record(_) | Value::Method(_) => false
+    }
+}
+
+// Hashes a hashable value so that values `equals` calls equal hash the same.
+// Numbers are hashed by `numeric`, so `1`, `1.0d` and `1.0` hash alike.
+pub fn hash<H: Hasher>(value: &Value, state: &mut H) {
+    match value {
+        Value::Nil => 0.hash(state),
+        Value::Boolean(b) => (1, b).hash(state),
+        Value::Integer(_) | Value::BigInteger(_) | Value::Decimal(_) | Value::Rational(_) | Value::Float(_) => {
+            numeric::hash(value, state)
+        },
+        Value::String(s) => (4, s).hash(state),
+        Value::Tuple(values) => {
+            (5, values.len()).hash(state);
+            values.iter().for_each(|value| hash(value, state));
+        },
+        Value::Variant(tagged) => {
+            (6, Rc::as_ptr(&tagged.enumeration), tagged.index).hash(state);
+            tagged.values.iter().for_each(|value| hash(value, state));
+        },
+        Value::Function(function) => (7, Rc::as_ptr(function)).hash(state),
+        Value::Struct(structure) => (8, Rc::as_ptr(structure)).hash(state),
+        Value::Enum(enumeration) => (9, Rc::as_ptr(enumeration)).hash(state),
+        Value::Constructor(enumeration, index) => (10, Rc::as_ptr(enumeration), index).hash(state),
+        Value::Builtin(builtin) => (13, builtin.name).hash(state),
+        Value::Guarded(guard) => hash(&guard.function, state),
+        Value::List(_) | Value::Map(_) | Value::Record(_) | Value::Method(_) => {
+            unreachable!("only hashable values are hashed")
+        }
+    }
+}
+
+// Whether `value` falls between `start` and `end`, as in a range pattern.
+pub fn in_range(value: &Value, start: &Value, end: &Value, inclusive: bool) -> bool {
+    let above = matches!(compare(start, value), Some(Ordering::Less) | Some(Ordering::Equal));
+    let below = match compare(value, end) {
+        Some(Ordering::Less) => true,
+        Some(Ordering::Equal) => inclusive,
+        _ => false
+    };
+    above && below
+}
+
+fn compare_sequences(a: &[Value], b: &[Value]) -> Option<Ordering> {
+    for (a, b) in a.iter().zip(b.iter()) {
+        match compare(a, b)? {
+            Ordering::Equal => continue,
+            ordering => return Some(ordering)
+        }
+    }
+
+    Some(a.len().cmp(&b.len()))
+}
+
+fn mismatch(operator: TokenType, left: &Value, right: &Value) -> (ErrorForm, String) {
+    (
+        ErrorForm::TypeError,
+        message!("E0006-binary-operands", operator = operator, left = left.type_name(), right = right.type_name())
+    )
+}
+
+fn overflow(operator: TokenType) -> (ErrorForm, String) {
+    (ErrorForm::RuntimeError, message!("E0007-overflow", operator = operator))
+}
+
+fn too_big(operator: TokenType) -> (ErrorForm, String) {
+    (ErrorForm::RuntimeError, message!("E0007-too-big", operator = operator, limit = numeric::MAX_BITS))
+}
+
+fn division_by_zero() -> (ErrorForm, String) {
+    (ErrorForm::RuntimeError, message!("E0007-division-by-zero"))
+}
@@ -0,0 +1,448 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::Zero;
+
+use crate::analysis::types::Type;
+use crate::compiler::chunk::Function;
+use crate::interpreter::builtins::Builtin;
+use crate::interpreter::environment::Environment;
+use crate::interpreter::gc;
+use crate::interpreter::ops;
+use crate::parser::ast::Body;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Integer(i64),
+    BigInteger(BigInt),
+    Decimal(BigDecimal),
+    Rational(BigRational),
+    Float(f64),
+    String(String),
+    Tuple(Vec<Value>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Function(Rc<Closure>),
+    Builtin(&'static Builtin),
+    Method(Rc<Method>),
+    Guarded(Rc<Guard>),
+    Struct(Rc<Structure>),
+    Record(Rc<RefCell<Record>>),
+    Enum(Rc<Enumeration>),
+    Constructor(Rc<Enumeration>, usize),
+    Variant(Rc<Tagged>)
+}
+
+// Values share their contents through `Rc`: lists, maps and records are
+// mutable and every copy sees changes made through the others, while the rest
+// never change once made. Anything that can hold other values is registered
+// with `gc`, which breaks the reference cycles `Rc` can't free on its own.
+
+// A function value together with the variables it captured.
+//
+// Closures capture variables by reference: a captured variable that is
+// reassigned after the closure was created is seen with its new value when
+// the closure runs, and assignments the closure makes are visible to
+// everything else sharing the variable. Captured variables stay alive for as
+// long as any closure refers to them, so closures returned from functions
+// keep working after the function that created them has returned.
+#[derive(Debug)]
+pub struct Closure {
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub code: Code,
+    pub position: Position
+}
+
+// What runs when a closure is called, which depends on who made it.
+#[derive(Debug)]
+pub enum Code {
+    // The interpreter's closures keep the very scope they were declared in.
+    Tree {
+        body: Rc<Body>,
+        environment: Rc<RefCell<Environment>>
+    },
+    // The VM's closures keep only the variables they use, each in a cell
+    // shared with the frame that declared it.
+    Compiled {
+        function: Rc<Function>,
+        captures: Vec<Rc<RefCell<Value>>>
+    }
+}
+
+// A method looked up on a record, remembering the record it becomes `self` for.
+#[derive(Debug)]
+pub struct Method {
+    pub receiver: Value,
+    pub function: Rc<Closure>
+}
+
+// A function from code without annotations that was passed where a function
+// type was expected. Its arguments and result are checked against that type
+// each time it's called, since a function can't be checked before it runs.
+#[derive(Debug)]
+pub struct Guard {
+    pub function: Value,
+    pub parameters: Vec<Type>,
+    pub result: Type,
+    pub declared: Position,
+    pub origin: Option<Position>
+}
+
+// The type made by a `struct` declaration. Calling it isn't possible; records
+// are made with `Name { field: value }` instead.
+#[derive(Debug)]
+pub struct Structure {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: HashMap<String, Rc<Closure>>,
+    pub position: Position
+}
+
+// Records are shared like lists, so a change to a field through one variable
+// is seen through every other.
+#[derive(Debug)]
+pub struct Record {
+    pub structure: Rc<Structure>,
+    pub values: Vec<Value>
+}
+
+impl Record {
+    pub fn field(&self, name: &str) -> Option<usize> {
+        self.structure.fields.iter().position(|field| field == name)
+    }
+}
+
+// The type made by an `enum` declaration, with the field names of each variant.
+#[derive(Debug)]
+pub struct Enumeration {
+    pub name: String,
+    pub variants: Vec<(String, Vec<String>)>,
+    pub position: Position
+}
+
+// A value of an enum: which variant it is and what it carries. Unlike records
+// these never change once made.
+#[derive(Debug)]
+pub struct Tagged {
+    pub enumeration: Rc<Enumeration>,
+    pub index: usize,
+    pub values: Vec<Value>
+}
+
+// A map keeps its entries in insertion order. Keys have to be hashable, see
+// `ops::hashable`, so that a key can't change while it's in the map.
+#[derive(Debug, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<Key, usize>
+}
+
+// A hashable value, compared and hashed the way `==` compares it.
+#[derive(Debug, Clone)]
+struct Key(Value);
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Key) -> bool {
+        ops::equals(&self.0, &other.0)
+    }
+}
+
+impl Eq for Key {}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ops::hash(&self.0, state)
+    }
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        if !ops::hashable(key) {
+            return None;
+        }
+        self.index.get(&Key(key.clone())).map(|&i| &self.entries[i].1)
+    }
+
+    // Adds or replaces an entry. An unhashable key is handed back.
+    pub fn insert(&mut self, key: Value, value: Value) -> Result<(), Value> {
+        if !ops::hashable(&key) {
+            return Err(key);
+        }
+        match self.index.get(&Key(key.clone())) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(Key(key.clone()), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Value, Value)> {
+        self.entries.iter()
+    }
+
+    // Empties the map, handing back everything it held.
+    pub fn take(&mut self) -> Vec<(Value, Value)> {
+        self.index.clear();
+        std::mem::take(&mut self.entries)
+    }
+}
+
+impl Tagged {
+    pub fn variant(&self) -> &str {
+        &self.enumeration.variants[self.index].0
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.enumeration.variants[self.index].1
+    }
+}
+
+impl Value {
+    pub fn from_form(form: &Form) -> Value {
+        match form {
+            Form::Integer(i) => Value::Integer(*i),
+            Form::BigInteger(i) => Value::BigInteger(i.clone()),
+            Form::Decimal(d) => Value::Decimal(d.clone()),
+            Form::Float(f) => Value::Float(*f),
+            Form::String(s) => Value::String(s.clone()),
+            Form::Boolean(b) => Value::Boolean(*b)
+        }
+    }
+
+    // An integer of any size, kept as an `Integer` whenever it fits.
+    pub fn integer(i: BigInt) -> Value {
+        match i64::try_from(&i) {
+            Ok(small) => Value::Integer(small),
+            Err(_) => Value::BigInteger(i)
+        }
+    }
+
+    // A fraction, kept as an integer when it is a whole number.
+    pub fn rational(r: BigRational) -> Value {
+        if r.is_integer() { Value::integer(r.to_integer()) } else { Value::Rational(r) }
+    }
+
+    pub fn list(elements: Vec<Value>) -> Value {
+        let list = Rc::new(RefCell::new(elements));
+        gc::track(gc::Object::List(Rc::downgrade(&list)));
+        Value::List(list)
+    }
+
+    pub fn map(map: Map) -> Value {
+        let map = Rc::new(RefCell::new(map));
+        gc::track(gc::Object::Map(Rc::downgrade(&map)));
+        Value::Map(map)
+    }
+
+    pub fn record(record: Record) -> Value {
+        let record = Rc::new(RefCell::new(record));
+        gc::track(gc::Object::Record(Rc::downgrade(&record)));
+        Value::Record(record)
+    }
+
+    pub fn function(closure: Closure) -> Rc<Closure> {
+        let closure = Rc::new(closure);
+        gc::track(gc::Object::Closure(Rc::downgrade(&closure)));
+        closure
+    }
+
+    // A variable that outlives its frame because a closure captured it.
+    pub fn cell(value: Value) -> Rc<RefCell<Value>> {
+        let cell = Rc::new(RefCell::new(value));
+        gc::track(gc::Object::Cell(Rc::downgrade(&cell)));
+        cell
+    }
+
+    pub fn method(receiver: Value, function: Rc<Closure>) -> Value {
+        let method = Rc::new(Method { receiver, function });
+        gc::track(gc::Object::Method(Rc::downgrade(&method)));
+        Value::Method(method)
+    }
+
+    pub fn guarded(guard: Guard) -> Value {
+        let guard = Rc::new(guard);
+        gc::track(gc::Object::Guard(Rc::downgrade(&guard)));
+        Value::Guarded(guard)
+    }
+
+    pub fn structure(structure: Structure) -> Value {
+        let structure = Rc::new(structure);
+        gc::track(gc::Object::Structure(Rc::downgrade(&structure)));
+        Value::Struct(structure)
+    }
+
+    pub fn variant(tagged: Tagged) -> Value {
+        let tagged = Rc::new(tagged);
+        gc::track(gc::Object::Tagged(Rc::downgrade(&tagged)));
+        Value::Variant(tagged)
+    }
+
+    pub fn type_name(&self) -> String {
+        String::from(match self {
+            Value::Nil => "nil",
+            Value::Boolean(_) => "bool",
+            Value::Integer(_) | Value::BigInteger(_) => "int",
+            Value::Decimal(_) => "decimal",
+            Value::Rational(_) => "rational",
+            Value::Float(_) => "float",
+            Value::String(_) => "str",
+            Value::Tuple(_) => "tuple",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Function(_) | Value::Builtin(_) | Value::Method(_) | Value::Guarded(_) | Value::Constructor(_, _) => "function",
+            Value::Struct(_) => "struct",
+            Value::Enum(_) => "enum",
+            Value::Record(record) => return record.borrow().structure.name.clone(),
+            Value::Variant(tagged) => return tagged.enumeration.name.clone()
+        })
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Nil => false,
+            Value::Boolean(b) => *b,
+            Value::Integer(i) => *i != 0,
+            Value::BigInteger(i) => !i.is_zero(),
+            Value::Decimal(d) => !d.is_zero(),
+            Value::Rational(r) => !r.is_zero(),
+            Value::Float(f) => *f != 0.0,
+            Value::String(s) => !s.is_empty(),
+            Value::Tuple(t) => !t.is_empty(),
+            Value::List(l) => !l.borrow().is_empty(),
+            Value::Map(m) => !m.borrow().is_empty(),
+            Value::Function(_) | Value::Builtin(_) | Value::Method(_) | Value::Guarded(_) => true,
+            Value::Struct(_) | Value::Record(_) => true,
+            Value::Enum(_) | Value::Constructor(_, _) | Value::Variant(_) => true
+        }
+    }
+
+    // The elements of a tuple or list, for destructuring and spreading.
+    pub fn elements(&self) -> Option<Vec<Value>> {
+        match self {
+            Value::Tuple(t) => Some(t.clone()),
+            Value::List(l) => Some(l.borrow().clone()),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    // Values can be nested as deeply as the code that built them.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.show(f))
+    }
+}
+
+impl Value {
+    fn show(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::BigInteger(i) => write!(f, "{}", i),
+            Value::Decimal(d) => write!(f, "{}d", d.to_plain_string()),
+            Value::Rational(r) => write!(f, "{}/{}", r.numer(), r.denom()),
+            Value::Float(n) => write!(f, "{:?}", n),
+            Value::String(s) => write!(f, "{:?}", s),
+            Value::Tuple(t) => {
+                let elements = t.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                if elements.len() == 1 {
+                    write!(f, "({},)", elements[0])
+                } else {
+                    write!(f, "({})", elements.join(", "))
+                }
+            },
+            Value::List(l) => guarded(f, gc::address(l), "[...]", |f| {
+                let elements = l.borrow().iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "[{}]", elements.join(", "))
+            }),
+            Value::Map(m) => guarded(f, gc::address(m), "{...}", |f| {
+                let entries = m.borrow().iter().map(|(k, v)| format!("{}: {}", k, v)).collect::<Vec<_>>();
+                write!(f, "{{{}}}", entries.join(", "))
+            }),
+            Value::Function(function) => match &function.name {
+                Some(name) => write!(f, "<fn {}>", name),
+                None => write!(f, "<fn>")
+            },
+            Value::Builtin(builtin) => write!(f, "<fn {}>", builtin.name),
+            Value::Method(method) => write!(
+                f,
+                "<method {}.{}>",
+                method.receiver.type_name(),
+                method.function.name.as_deref().unwrap_or_default()
+            ),
+            Value::Guarded(guard) => write!(f, "{}", guard.function),
+            Value::Struct(structure) => write!(f, "<struct {}>", structure.name),
+            Value::Record(rc) => guarded(f, gc::address(rc), "...", |f| {
+                let record = rc.borrow();
+                let fields = record.structure.fields.iter().zip(record.values.iter())
+                    .map(|(name, value)| format!("{}: {}", name, value))
+                    .collect::<Vec<_>>();
+                if fields.is_empty() {
+                    write!(f, "{} {{}}", record.structure.name)
+                } else {
+                    write!(f, "{} {{ {} }}", record.structure.name, fields.join(", "))
+                }
+            }),
+            Value::Enum(enumeration) => write!(f, "<enum {}>", enumeration.name),
+            Value::Constructor(enumeration, index) => {
+                write!(f, "<variant {}.{}>", enumeration.name, enumeration.variants[*index].0)
+            },
+            Value::Variant(tagged) => {
+                write!(f, "{}.{}", tagged.enumeration.name, tagged.variant())?;
+                if !tagged.values.is_empty() {
+                    let values = tagged.values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                    write!(f, "({})", values.join(", "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+thread_local! {
+    // The lists, maps and records being displayed right now.
+    static DISPLAYING: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+// Displays a container that may contain itself. A container met again while
+// it is still being displayed is shown as `placeholder`, like `[1, [...]]`.
+fn guarded(
+    f: &mut fmt::Formatter<'_>,
+    address: usize,
+    placeholder: &str,
+    display: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result
+) -> fmt::Result {
+    if !DISPLAYING.with(|displaying| displaying.borrow_mut().insert(address)) {
+        return write!(f, "{}", placeholder);
+    }
+    let result = display(f);
+    DISPLAYING.with(|displaying| displaying.borrow_mut().remove(&address));
+    result
+}
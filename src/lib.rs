@@ -1,2 +1,5 @@
 pub mod parser;
 pub mod error;
+pub mod analysis;
+pub mod interpreter;
+pub mod compiler;
@@ -1,28 +1,284 @@
-use codespan_reporting::files::SimpleFile;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use language::analysis::checker::Checker;
+use language::analysis::fold::Folder;
+use language::analysis::resolver::Resolver;
+use language::compiler::cache;
+use language::compiler::compile::Compiler;
+use language::compiler::disassemble::disassemble;
+use language::compiler::vm::VM;
+use language::error::diagnostic::{ErrorForm, Errors, FileId, LintLevels, Sources};
+use language::error::emitter::{Colour, Emitter, Style};
+use language::error::export;
+use language::error::fix;
+use language::error::messages::{self, Catalogue};
+use language::interpreter::interpret::Interpreter;
+use language::parser::ast::AST;
 use language::parser::lexer::*;
-use language::parser::token::Token;
+use language::parser::parse::Parser;
 
-fn main() {
-    fn parse(input: &str) -> Vec<Token> {
-        let file = SimpleFile::new(
-            String::from("main.language"),
-            String::from(input),
-        );
-        let mut lexer = Lexer::from_text(input, file);
-        lexer.tokenise();
-        let nfile = SimpleFile::new(
-            String::from("main.language"),
-            String::from(input),
-        );
-        lexer.error.emit_errors(nfile);
-        lexer.tokens
-    }
-
-    let statement = parse(
-        "
+const USAGE: &str = "usage: language [--deny warnings | --deny CODE] [--allow CODE] [--format human|json|sarif]
+                [--color auto|always|never] [--style rich|medium|short] [--tab-width N] [--fix] [--messages FILE]
+                [--exact-division] [--vm] [--disassemble] [--dump-ast] [--no-cache] [FILE...]
+       language --explain CODE";
+
+const DEMO: &str = "
 1 + \"str\" + 34567.8
-a = 45 + 222 - \"6yh645bh65v58\"",
-    );
+let a = 45 + 222 - \"6yh645bh65v58\"
+let b = a
+a, b = b, a
+let [first, ..rest] = [a, b]";
+
+enum Format {
+    Human,
+    Json,
+    Sarif
+}
+
+struct Options {
+    files: Vec<String>,
+    levels: LintLevels,
+    format: Format,
+    emitter: Emitter,
+    fix: bool,
+    exact_division: bool,
+    run: Run,
+    cache: bool,
+    dump_ast: bool
+}
+
+// How programs are run once they've been checked.
+#[derive(PartialEq)]
+enum Run {
+    Interpret,
+    Compile,
+    Disassemble
+}
+
+fn options() -> Result<Options, String> {
+    let mut options = Options { files: Vec::new(), levels: LintLevels::new(), format: Format::Human, emitter: Emitter::new(), fix: false, exact_division: false, run: Run::Interpret, cache: true, dump_ast: false };
+    let mut args = env::args().skip(1);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            },
+            "-D" | "--deny" => match args.next() {
+                Some(lint) if lint == "warnings" => options.levels.deny_warnings = true,
+                Some(code) => options.levels.deny(&code),
+                None => return Err(format!("`{}` needs `warnings` or an error code after it.", arg))
+            },
+            "-A" | "--allow" => match args.next() {
+                Some(code) => options.levels.allow(&code),
+                None => return Err(format!("`{}` needs an error code after it.", arg))
+            },
+            "--format" => {
+                options.format = match args.next().as_deref() {
+                    Some("human") => Format::Human,
+                    Some("json") => Format::Json,
+                    Some("sarif") => Format::Sarif,
+                    _ => return Err(String::from("`--format` needs one of `human`, `json` or `sarif` after it."))
+                }
+            },
+            "--color" | "--colour" => {
+                options.emitter.colour = args.next().as_deref().and_then(Colour::from_name)
+                    .ok_or_else(|| format!("`{}` needs one of `auto`, `always` or `never` after it.", arg))?
+            },
+            "--style" => {
+                options.emitter.style = args.next().as_deref().and_then(Style::from_name)
+                    .ok_or_else(|| String::from("`--style` needs one of `rich`, `medium` or `short` after it."))?
+            },
+            "--tab-width" => {
+                options.emitter.tab_width = args.next().and_then(|width| width.parse().ok())
+                    .ok_or_else(|| String::from("`--tab-width` needs a number after it."))?
+            },
+            "--fix" => options.fix = true,
+            "--exact-division" => options.exact_division = true,
+            "--vm" => options.run = Run::Compile,
+            "--disassemble" => options.run = Run::Disassemble,
+            "--no-cache" => options.cache = false,
+            "--dump-ast" => options.dump_ast = true,
+            "--explain" => match args.next().as_deref().map(ErrorForm::from_code) {
+                Some(Some(form)) => {
+                    println!("{} {:?}\n\n{}", form.code(), form, form.explanation().trim_end());
+                    process::exit(0);
+                },
+                Some(None) => return Err(String::from("I don't know that error code. They look like `E0001`.")),
+                None => return Err(String::from("`--explain` needs an error code after it."))
+            },
+            "--messages" => match args.next() {
+                Some(path) => {
+                    let catalogue = Catalogue::load(&path).map_err(|error| format!("I couldn't read the messages in `{}`: {}", path, error))?;
+                    messages::install(catalogue);
+                },
+                None => return Err(String::from("`--messages` needs a file after it."))
+            },
+            _ if arg.starts_with('-') => return Err(format!("I don't know the option `{}`.", arg)),
+            _ => options.files.push(arg)
+        }
+    }
+
+    Ok(options)
+}
+
+// Lexes, parses, resolves, type checks and folds one file. Files share the
+// resolver, so names declared in an earlier file are visible in later ones,
+// and the checker and folder for the same reason.
+fn check(mut lexer: Lexer, resolver: &mut Resolver, checker: &mut Checker, folder: &mut Folder, errors: &mut Errors) -> Vec<AST> {
+    lexer.tokenise();
+    let mut parser = Parser::new(lexer.tokens);
+    let mut code = parser.parse();
+    resolver.resolve(&code);
+    errors.extend(&mut lexer.error.errors);
+    errors.extend(&mut parser.error.errors);
+    errors.extend(&mut resolver.error.errors);
+    checker.check(&mut code);
+    errors.extend(&mut checker.error.errors);
+    folder.fold(&mut code);
+    errors.extend(&mut folder.error.errors);
+    code
+}
+
+// Makes every machine applicable fix in place, and forgets the diagnostics it
+// fixed so only the ones left over are shown.
+fn fix_files(paths: &[(&String, FileId)], files: &Sources, errors: &mut Errors) {
+    let mut fixed = HashSet::new();
+    for &(path, file) in paths {
+        let source = files.get(file).map(|file| file.source().as_str()).unwrap_or_default();
+        let (owners, fixes): (Vec<_>, Vec<_>) = errors.iter().enumerate()
+            .flat_map(|(i, error)| error.fixes.iter().map(move |fix| (i, fix)))
+            .filter(|(_, fix)| fix.file == file && fix.is_safe())
+            .unzip();
+        let (source, applied) = fix::apply(source, fixes);
+        if applied.is_empty() {
+            continue;
+        }
+
+        if let Err(error) = fs::write(path, source) {
+            eprintln!("I couldn't write the fixes to `{}`: {}", path, error);
+            process::exit(2)
+        }
+        let count = applied.len();
+        eprintln!("Fixed {} problem{} in `{}`.", count, if count == 1 { "" } else { "s" }, path);
+        fixed.extend(applied.into_iter().map(|i| owners[i]));
+    }
+
+    errors.errors = std::mem::take(&mut errors.errors).into_iter().enumerate()
+        .filter(|(i, _)| !fixed.contains(i))
+        .map(|(_, error)| error)
+        .collect();
+}
+
+// Programs from files are compiled through the cache in `__langcache__`,
+// and only kept there if they compiled cleanly. `paths` lines up with the
+// start of `programs`.
+fn run(programs: &[Vec<AST>], paths: &[(&String, FileId)], files: &Sources, options: &Options, errors: &mut Errors) {
+    let result = if options.run == Run::Interpret {
+        let mut interpreter = Interpreter::new();
+        interpreter.exact_division = options.exact_division;
+        programs.iter().try_fold(None, |_, code| interpreter.interpret(code).map(Some))
+    } else {
+        let mut compiler = Compiler::new();
+        let scripts = programs.iter().enumerate().map(|(i, code)| {
+            let (path, file) = match paths.get(i) {
+                // Folding `/` depends on exact division, which the cache doesn't record.
+                Some(&(path, file)) if options.cache && !options.exact_division => (Path::new(path), file),
+                _ => return compiler.compile(code)
+            };
+            // Files are added in the order they're checked, so these are
+            // the sources of the file and of every file before it.
+            let sources = (0..=file).map(|id| files.get(id).map(|file| file.source().as_str()).unwrap_or_default()).collect::<Vec<_>>();
+            if let Some(script) = cache::load(path, &sources, file) {
+                return script;
+            }
 
-    println!("{:?}", statement);
-}
\ No newline at end of file
+            let before = compiler.error.errors.len();
+            let script = compiler.compile(code);
+            if compiler.error.errors.len() == before {
+                cache::store(path, &sources, file, &script);
+            }
+            script
+        }).collect::<Vec<_>>();
+        errors.extend(&mut compiler.error.errors);
+        if errors.has_errors() {
+            return;
+        }
+        if options.run == Run::Disassemble {
+            scripts.iter().for_each(|script| print!("{}", disassemble(script)));
+            return;
+        }
+
+        let mut vm = VM::new();
+        vm.exact_division = options.exact_division;
+        scripts.into_iter().try_fold(None, |_, script| vm.run(script).map(Some))
+    };
+
+    match result {
+        Ok(Some(value)) => println!("=> {}", value),
+        Ok(None) => {},
+        Err(error) => errors.register_error(error)
+    }
+}
+
+fn main() {
+    let options = options().unwrap_or_else(|message| {
+        eprintln!("{}\n{}", message, USAGE);
+        process::exit(2)
+    });
+
+    let mut files = Sources::new();
+    let mut errors = Errors::new();
+    let mut resolver = Resolver::new();
+    let mut checker = Checker::new();
+    checker.exact_division = options.exact_division;
+    let mut folder = Folder::new();
+    folder.exact_division = options.exact_division;
+    let mut programs = Vec::new();
+    let mut paths = Vec::new();
+
+    if options.files.is_empty() {
+        let file = files.add(String::from("main.language"), String::from(DEMO));
+        programs.push(check(Lexer::from_text(DEMO, file), &mut resolver, &mut checker, &mut folder, &mut errors));
+    }
+    for path in &options.files {
+        let lexer = Lexer::from_file(path, &mut files).unwrap_or_else(|error| {
+            eprintln!("I couldn't read `{}`: {}", path, error);
+            process::exit(2)
+        });
+        paths.push((path, lexer.file));
+        programs.push(check(lexer, &mut resolver, &mut checker, &mut folder, &mut errors));
+    }
+
+    if options.dump_ast {
+        for code in programs.iter().flatten() {
+            println!("{}", code);
+        }
+    }
+
+    errors.apply(&options.levels);
+    if options.fix {
+        fix_files(&paths, &files, &mut errors);
+    } else if !errors.has_errors() {
+        run(&programs, &paths, &files, &options, &mut errors);
+    }
+
+    let emitted = match options.format {
+        Format::Human => options.emitter.emit(&errors, &files),
+        Format::Json => export::json_lines(&errors, &files).map(|output| eprint!("{}", output)).map(|_| errors.error_count()),
+        Format::Sarif => export::sarif(&errors, &files).map(|output| eprintln!("{}", output)).map(|_| errors.error_count())
+    };
+    match emitted {
+        Ok(0) => {},
+        Ok(_) => process::exit(1),
+        Err(error) => {
+            eprintln!("I couldn't print the diagnostics: {}", error);
+            process::exit(1)
+        }
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt;
+use std::rc::Rc;
 
+use crate::analysis::types::Type;
 use crate::parser::token::TokenType;
 use crate::parser::form::Form;
 use crate::parser::position::Position;
@@ -9,7 +11,19 @@ pub enum Expression {
     Literal(Literal),
     BinaryOperation(BinaryOperation),
     UnaryOperation(UnaryOperation),
-    Identifier(Identifier)
+    Identifier(Identifier),
+    Comparison(Comparison),
+    Tuple(Sequence),
+    List(Sequence),
+    Spread(Spread),
+    Attribute(Attribute),
+    Index(Index),
+    Call(Call),
+    Lambda(Function),
+    Match(Match),
+    Construction(Construction),
+    Map(Map),
+    Cast(Cast)
 }
 
 impl Expression {
@@ -18,21 +32,82 @@ impl Expression {
             Expression::BinaryOperation(b) => format!("({} {} {})", b.loperand.as_str(), b.operator, b.roperand.as_str()),
             Expression::UnaryOperation(u) => format!("({} {})", u.operator, u.operand.as_str()),
             Expression::Literal(l) => format!("{}", l),
-            Expression::Identifier(i) => format!("{}", i.name)
+            Expression::Identifier(i) => i.name.to_string(),
+            Expression::Comparison(c) => {
+                let pairs = c.operators.iter().enumerate()
+                    .map(|(i, operator)| format!("({} {} {})", c.operands[i].as_str(), operator, c.operands[i + 1].as_str()))
+                    .collect::<Vec<_>>();
+                format!("({})", pairs.join(" and "))
+            },
+            Expression::Tuple(t) => {
+                let elements = t.elements.iter().map(|e| e.as_str()).collect::<Vec<_>>();
+                if elements.len() == 1 {
+                    format!("({},)", elements[0])
+                } else {
+                    format!("({})", elements.join(", "))
+                }
+            },
+            Expression::List(l) => format!("[{}]", l.elements.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(", ")),
+            Expression::Spread(s) => format!("..{}", s.value.as_str()),
+            Expression::Attribute(a) => format!("{}.{}", a.object.as_str(), a.name.name),
+            Expression::Index(i) => format!("{}[{}]", i.object.as_str(), i.index.as_str()),
+            Expression::Call(c) => {
+                let arguments = c.arguments.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+                format!("{}({})", c.callee.as_str(), arguments.join(", "))
+            },
+            Expression::Lambda(l) => format!("(fn {} => {})", l.signature(), l.body),
+            Expression::Match(m) => {
+                let arms = m.arms.iter().map(|arm| arm.as_str()).collect::<Vec<_>>();
+                format!("(match {} {{{}}})", m.scrutinee.as_str(), arms.join(", "))
+            },
+            Expression::Construction(c) => {
+                let fields = c.fields.iter().map(|f| format!("{}: {}", f.name.name, f.value.as_str())).collect::<Vec<_>>();
+                format!("{} {{{}}}", c.name.name, fields.join(", "))
+            },
+            Expression::Map(m) => {
+                let entries = m.entries.iter().map(|e| format!("{}: {}", e.key.as_str(), e.value.as_str())).collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(", "))
+            },
+            Expression::Cast(c) => format!("({} as {})", c.value.as_str(), c.ty)
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match &self {
+            Expression::BinaryOperation(b) => b.position,
+            Expression::UnaryOperation(u) => u.position,
+            Expression::Literal(l) => l.position,
+            Expression::Identifier(i) => i.position,
+            Expression::Comparison(c) => c.position,
+            Expression::Tuple(t) | Expression::List(t) => t.position,
+            Expression::Spread(s) => s.position,
+            Expression::Attribute(a) => a.position,
+            Expression::Index(i) => i.position,
+            Expression::Call(c) => c.position,
+            Expression::Lambda(l) => l.position,
+            Expression::Match(m) => m.position,
+            Expression::Construction(c) => c.position,
+            Expression::Map(m) => m.position,
+            Expression::Cast(c) => c.value.position()
         }
     }
 }
 
 #[derive(Debug)]
 pub struct Literal {
-    form: Form,
-    position: Position
+    pub form: Form,
+    pub position: Position
 }
 
 impl Literal {
     pub fn value(&self) -> String{
         match &self.form {
-            Form::Integer(i) => i.to_string()
+            Form::Integer(i) => i.to_string(),
+            Form::BigInteger(i) => i.to_string(),
+            Form::Decimal(d) => format!("{}d", d.to_plain_string()),
+            Form::Float(f) => format!("{:?}", f),
+            Form::String(s) => format!("{:?}", s),
+            Form::Boolean(b) => b.to_string()
         }
     }
 }
@@ -47,52 +122,599 @@ impl fmt::Display for Literal {
 pub struct BinaryOperation {
     pub loperand: Box<Expression>,
     pub operator: TokenType,
-    pub roperand: Box<Expression>
+    pub roperand: Box<Expression>,
+    pub position: Position
 }
 
 #[derive(Debug)]
 pub struct UnaryOperation {
     pub operator: TokenType,
-    pub operand: Box<Expression>
+    pub operand: Box<Expression>,
+    pub position: Position
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Identifier {
-    name: String,
-    position: Position
+    pub name: String,
+    pub position: Position
+}
+
+// A chain of two or more comparisons like `0 <= i < n`. It behaves like the
+// comparisons joined with `and`, but each operand is evaluated at most once.
+#[derive(Debug)]
+pub struct Comparison {
+    pub operands: Vec<Expression>,
+    pub operators: Vec<TokenType>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Sequence {
+    pub elements: Vec<Expression>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Spread {
+    pub value: Box<Expression>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Attribute {
+    pub object: Box<Expression>,
+    pub name: Identifier,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Index {
+    pub object: Box<Expression>,
+    pub index: Box<Expression>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Call {
+    pub callee: Box<Expression>,
+    pub arguments: Vec<Expression>,
+    pub position: Position
+}
+
+// Both named functions and lambdas. A lambda written as `fn(x) => x * 2` gets
+// a body holding that single expression. The body is reference counted so
+// function values can share it with the tree they were declared in.
+#[derive(Debug)]
+pub struct Function {
+    pub name: Option<Identifier>,
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<Annotated>,
+    pub result: Option<Annotation>,
+    pub body: Rc<Body>,
+    pub position: Position
+}
+
+impl Function {
+    pub fn parameter_names(&self) -> Vec<&str> {
+        self.parameters.iter().map(|p| p.name.name.as_str()).collect()
+    }
+
+    // The type parameters, the parameters and the result type, as in
+    // `<T: number>(a: T, b) -> T`.
+    pub fn signature(&self) -> String {
+        let parameters = self.parameters.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+        let generic = type_parameters_str(&self.type_parameters);
+        match &self.result {
+            Some(result) => format!("{}({}) -> {}", generic, parameters.join(", "), result.as_str()),
+            None => format!("{}({})", generic, parameters.join(", "))
+        }
+    }
+
+    pub fn as_str(&self) -> String {
+        let name = self.name.as_ref().map(|n| n.name.as_str()).unwrap_or_default();
+        format!("Function[{}, {}, {}]", name, self.signature(), self.body)
+    }
+}
+
+// A parameter or field, with the type it was declared with if any.
+#[derive(Debug)]
+pub struct Annotated {
+    pub name: Identifier,
+    pub annotation: Option<Annotation>
+}
+
+impl Annotated {
+    pub fn as_str(&self) -> String {
+        match &self.annotation {
+            Some(annotation) => format!("{}: {}", self.name.name, annotation.as_str()),
+            None => self.name.name.clone()
+        }
+    }
+}
+
+// A type parameter of a generic function, struct or enum, like the `T` in
+// `fn largest<T: ordered>(xs: [T]) -> T`, with the bounds every type it
+// stands for has to satisfy.
+#[derive(Debug)]
+pub struct TypeParameter {
+    pub name: Identifier,
+    pub bounds: Vec<Identifier>
+}
+
+impl TypeParameter {
+    pub fn as_str(&self) -> String {
+        if self.bounds.is_empty() {
+            return self.name.name.clone();
+        }
+        let bounds = self.bounds.iter().map(|b| b.name.as_str()).collect::<Vec<_>>();
+        format!("{}: {}", self.name.name, bounds.join(" + "))
+    }
+}
+
+// `<T: number, U>`, or nothing if there are no type parameters.
+pub fn type_parameters_str(parameters: &[TypeParameter]) -> String {
+    if parameters.is_empty() {
+        return String::new();
+    }
+    let parameters = parameters.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+    format!("<{}>", parameters.join(", "))
+}
+
+// A type written out in the source, like `int`, `[str]`, `{str: int}`,
+// `fn(int, int) -> bool` or `Pair<int, str>`. Types are only checked before
+// the program runs.
+#[derive(Debug)]
+pub enum Annotation {
+    Name(Identifier),
+    Generic(GenericAnnotation),
+    Tuple(AnnotationSequence),
+    List(ListAnnotation),
+    Map(MapAnnotation),
+    Function(FunctionAnnotation)
+}
+
+impl Annotation {
+    pub fn as_str(&self) -> String {
+        match &self {
+            Annotation::Name(n) => n.name.clone(),
+            Annotation::Generic(g) => {
+                let arguments = g.arguments.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+                format!("{}<{}>", g.name.name, arguments.join(", "))
+            },
+            Annotation::Tuple(t) => {
+                let elements = t.annotations.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+                if elements.len() == 1 {
+                    format!("({},)", elements[0])
+                } else {
+                    format!("({})", elements.join(", "))
+                }
+            },
+            Annotation::List(l) => format!("[{}]", l.element.as_str()),
+            Annotation::Map(m) => format!("{{{}: {}}}", m.key.as_str(), m.value.as_str()),
+            Annotation::Function(f) => {
+                let parameters = f.parameters.iter().map(|a| a.as_str()).collect::<Vec<_>>();
+                format!("fn({}) -> {}", parameters.join(", "), f.result.as_str())
+            }
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match &self {
+            Annotation::Name(n) => n.position,
+            Annotation::Generic(g) => g.position,
+            Annotation::Tuple(t) => t.position,
+            Annotation::List(l) => l.position,
+            Annotation::Map(m) => m.position,
+            Annotation::Function(f) => f.position
+        }
+    }
+}
+
+// A generic struct or enum given the types for its type parameters.
+#[derive(Debug)]
+pub struct GenericAnnotation {
+    pub name: Identifier,
+    pub arguments: Vec<Annotation>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct AnnotationSequence {
+    pub annotations: Vec<Annotation>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct ListAnnotation {
+    pub element: Box<Annotation>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct MapAnnotation {
+    pub key: Box<Annotation>,
+    pub value: Box<Annotation>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct FunctionAnnotation {
+    pub parameters: Vec<Annotation>,
+    pub result: Box<Annotation>,
+    pub position: Position
+}
+
+// `Point { x: 1, y }`, where a field without a value takes the variable of
+// the same name.
+#[derive(Debug)]
+pub struct Construction {
+    pub name: Identifier,
+    pub fields: Vec<FieldValue>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct FieldValue {
+    pub name: Identifier,
+    pub value: Expression
+}
+
+// `{"a": 1, key: value}`. A '{' only starts a map where an expression is
+// expected; at the start of a statement it opens a block.
+#[derive(Debug)]
+pub struct Map {
+    pub entries: Vec<Entry>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub key: Expression,
+    pub value: Expression
+}
+
+// A check the type checker adds where a value it knows nothing about reaches
+// code that expects a `ty`, made when the value arrives. `declared` is what
+// expects the type, like an annotation, and `origin` is where a variable
+// being cast got its value. The parser never makes these.
+#[derive(Debug)]
+pub struct Cast {
+    pub value: Box<Expression>,
+    pub ty: Type,
+    pub declared: Position,
+    pub origin: Option<Position>
+}
+
+#[derive(Debug)]
+pub struct Match {
+    pub scrutinee: Box<Expression>,
+    pub arms: Vec<Arm>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Body,
+    pub position: Position
+}
+
+impl Arm {
+    pub fn as_str(&self) -> String {
+        match &self.guard {
+            Some(guard) => format!("{} if {} => {}", self.pattern.as_str(), guard.as_str(), self.body),
+            None => format!("{} => {}", self.pattern.as_str(), self.body)
+        }
+    }
+}
+
+// The patterns a `match` arm can test a value against.
+#[derive(Debug)]
+pub enum Pattern {
+    Wildcard(Position),
+    Binding(Identifier),
+    Literal(Literal),
+    Range(RangePattern),
+    Tuple(PatternSequence),
+    List(PatternSequence),
+    Rest(RestPattern),
+    Alternation(PatternSequence),
+    Variant(VariantPattern)
+}
+
+impl Pattern {
+    pub fn as_str(&self) -> String {
+        match &self {
+            Pattern::Wildcard(_) => String::from("_"),
+            Pattern::Binding(i) => i.name.to_string(),
+            Pattern::Literal(l) => l.value(),
+            Pattern::Range(r) => format!("{}{}{}", r.start, if r.inclusive { "..=" } else { ".." }, r.end),
+            Pattern::Tuple(t) => {
+                let patterns = t.patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+                if patterns.len() == 1 {
+                    format!("({},)", patterns[0])
+                } else {
+                    format!("({})", patterns.join(", "))
+                }
+            },
+            Pattern::List(l) => format!("[{}]", l.patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(", ")),
+            Pattern::Rest(r) => match &r.name {
+                Some(name) => format!("..{}", name.name),
+                None => String::from("..")
+            },
+            Pattern::Alternation(a) => a.patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>().join(" | "),
+            Pattern::Variant(v) => match &v.fields {
+                Some(fields) => {
+                    let fields = fields.patterns.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+                    format!("{}.{}({})", v.enumeration.name, v.variant.name, fields.join(", "))
+                },
+                None => format!("{}.{}", v.enumeration.name, v.variant.name)
+            }
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match &self {
+            Pattern::Wildcard(position) => *position,
+            Pattern::Binding(i) => i.position,
+            Pattern::Literal(l) => l.position,
+            Pattern::Range(r) => r.position,
+            Pattern::Tuple(s) | Pattern::List(s) | Pattern::Alternation(s) => s.position,
+            Pattern::Rest(r) => r.position,
+            Pattern::Variant(v) => v.position
+        }
+    }
+
+    // Every name this pattern binds, in source order. Each alternative of an
+    // alternation binds the same names, so only the first one is looked at.
+    pub fn bindings(&self) -> Vec<&Identifier> {
+        match &self {
+            Pattern::Binding(i) => vec![i],
+            Pattern::Rest(RestPattern { name: Some(name), .. }) => vec![name],
+            Pattern::Tuple(s) | Pattern::List(s) | Pattern::Variant(VariantPattern { fields: Some(s), .. }) => {
+                s.patterns.iter().flat_map(|p| p.bindings()).collect()
+            },
+            Pattern::Alternation(s) => s.patterns.first().map(|p| p.bindings()).unwrap_or_default(),
+            _ => vec![]
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct RangePattern {
+    pub start: Literal,
+    pub end: Literal,
+    pub inclusive: bool,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct PatternSequence {
+    pub patterns: Vec<Pattern>,
+    pub position: Position
+}
+
+// `Shape.Circle(r)`, or `State.Idle` for a variant without a payload.
+#[derive(Debug)]
+pub struct VariantPattern {
+    pub enumeration: Identifier,
+    pub variant: Identifier,
+    pub fields: Option<PatternSequence>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct RestPattern {
+    pub name: Option<Identifier>,
+    pub position: Position
+}
+
+// The left hand side of an assignment. Targets are parsed as expressions
+// first and then converted, so anything that isn't a place to store a
+// value (like `1 + 2`) is rejected by the parser.
+#[derive(Debug)]
+pub enum Target {
+    Identifier(Identifier),
+    Tuple(TargetSequence),
+    List(TargetSequence),
+    Rest(Rest),
+    Attribute(Attribute),
+    Index(Index)
+}
+
+impl Target {
+    pub fn as_str(&self) -> String {
+        match &self {
+            Target::Identifier(i) => i.name.to_string(),
+            Target::Tuple(t) => {
+                let targets = t.targets.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                if targets.len() == 1 {
+                    format!("({},)", targets[0])
+                } else {
+                    format!("({})", targets.join(", "))
+                }
+            },
+            Target::List(l) => format!("[{}]", l.targets.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", ")),
+            Target::Rest(r) => format!("..{}", r.name.name),
+            Target::Attribute(a) => format!("{}.{}", a.object.as_str(), a.name.name),
+            Target::Index(i) => format!("{}[{}]", i.object.as_str(), i.index.as_str())
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        match &self {
+            Target::Identifier(i) => i.position,
+            Target::Tuple(t) | Target::List(t) => t.position,
+            Target::Rest(r) => r.position,
+            Target::Attribute(a) => a.position,
+            Target::Index(i) => i.position
+        }
+    }
+
+    // Every name this target binds, in source order.
+    pub fn names(&self) -> Vec<&Identifier> {
+        match &self {
+            Target::Identifier(i) => vec![i],
+            Target::Tuple(t) | Target::List(t) => t.targets.iter().flat_map(|t| t.names()).collect(),
+            Target::Rest(r) => vec![&r.name],
+            Target::Attribute(_) | Target::Index(_) => vec![]
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TargetSequence {
+    pub targets: Vec<Target>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Rest {
+    pub name: Identifier,
+    pub position: Position
 }
 
 #[derive(Debug)]
 pub enum Statement {
     Assignment(Assignment),
+    Declaration(Declaration),
+    Function(Function),
+    Struct(Struct),
+    Enum(Enum),
+    Return(Return),
     Body(Body)
 }
 
 impl Statement {
     pub fn as_str(&self) -> String {
         match &self {
-            Statement::Assignment(asg) => format!("Assignment[{}, {}]", asg.identifier, asg.value.as_str()),
+            Statement::Assignment(asg) => format!("Assignment[{}, {}]", asg.target.as_str(), asg.value.as_str()),
+            Statement::Declaration(dcl) => match &dcl.annotation {
+                Some(annotation) => format!("{}[{}: {}, {}]", dcl.kind, dcl.target.as_str(), annotation.as_str(), dcl.value.as_str()),
+                None => format!("{}[{}, {}]", dcl.kind, dcl.target.as_str(), dcl.value.as_str())
+            },
+            Statement::Function(fun) => fun.as_str(),
+            Statement::Struct(stc) => {
+                let fields = stc.fields.iter().map(|f| f.as_str()).collect::<Vec<_>>();
+                let methods = stc.methods.iter().map(|m| m.as_str()).collect::<Vec<_>>();
+                let name = format!("{}{}", stc.name.name, type_parameters_str(&stc.type_parameters));
+                format!("Struct[{}, ({}), ({})]", name, fields.join(", "), methods.join(" "))
+            },
+            Statement::Enum(enm) => {
+                let variants = enm.variants.iter().map(|v| v.as_str()).collect::<Vec<_>>();
+                let name = format!("{}{}", enm.name.name, type_parameters_str(&enm.type_parameters));
+                format!("Enum[{}, ({})]", name, variants.join(" "))
+            },
+            Statement::Return(ret) => match &ret.value {
+                Some(value) => format!("Return[{}]", value.as_str()),
+                None => String::from("Return[]")
+            },
             Statement::Body(bdy) => format!("Body[{}]", bdy)
         }
     }
+
+    pub fn position(&self) -> Position {
+        match &self {
+            Statement::Assignment(asg) => asg.position,
+            Statement::Declaration(dcl) => dcl.position,
+            Statement::Function(fun) => fun.position,
+            Statement::Struct(stc) => stc.position,
+            Statement::Enum(enm) => enm.position,
+            Statement::Return(ret) => ret.position,
+            Statement::Body(bdy) => bdy.position
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct Assignment {
-    pub identifier: String,
+    pub target: Target,
+    pub value: Box<Expression>,
+    pub position: Position
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum DeclarationKind {
+    Let,
+    Const
+}
+
+impl fmt::Display for DeclarationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeclarationKind::Let => write!(f, "Let"),
+            DeclarationKind::Const => write!(f, "Const")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Declaration {
+    pub kind: DeclarationKind,
+    pub target: Target,
+    pub annotation: Option<Annotation>,
     pub value: Box<Expression>,
     pub position: Position
 }
 
+// A record type. Methods are declared inside the braces alongside the fields,
+// and the ones whose first parameter is `self` are called on a record.
+#[derive(Debug)]
+pub struct Struct {
+    pub name: Identifier,
+    pub type_parameters: Vec<TypeParameter>,
+    pub fields: Vec<Annotated>,
+    pub methods: Vec<Function>,
+    pub position: Position
+}
+
+// A tagged union. Each variant names the values it carries, if any.
+#[derive(Debug)]
+pub struct Enum {
+    pub name: Identifier,
+    pub type_parameters: Vec<TypeParameter>,
+    pub variants: Vec<Variant>,
+    pub position: Position
+}
+
+#[derive(Debug)]
+pub struct Variant {
+    pub name: Identifier,
+    pub fields: Vec<Annotated>,
+    pub position: Position
+}
+
+impl Variant {
+    pub fn as_str(&self) -> String {
+        if self.fields.is_empty() {
+            return self.name.name.clone();
+        }
+        let fields = self.fields.iter().map(|f| f.as_str()).collect::<Vec<_>>();
+        format!("{}({})", self.name.name, fields.join(", "))
+    }
+}
+
+#[derive(Debug)]
+pub struct Return {
+    pub value: Option<Box<Expression>>,
+    pub position: Position
+}
+
+// A `{ ... }` block. Every body opens a new lexical scope.
 #[derive(Debug)]
 pub struct Body {
-    code: Vec<AST>
+    pub code: Vec<AST>,
+    pub position: Position
 }
 
 impl fmt::Display for Body {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "(")?;
-        for code in &self.code {
+        for (i, code) in self.code.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
             write!(f, "{}", code)?;
         }
         write!(f, ")")
@@ -105,6 +727,15 @@ pub enum AST {
     Expression(Expression)
 }
 
+impl AST {
+    pub fn position(&self) -> Position {
+        match self {
+            AST::Statement(statement) => statement.position(),
+            AST::Expression(expression) => expression.position()
+        }
+    }
+}
+
 impl fmt::Display for AST {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -112,4 +743,4 @@ impl fmt::Display for AST {
             AST::Statement(stmt) => write!(f, "{}", stmt.as_str())
         }
     }
-}
\ No newline at end of file
+}
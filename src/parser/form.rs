@@ -1,4 +1,14 @@
-#[derive(Debug)]
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+
+// Integers that fit in an `i64` are always `Integer`; `BigInteger` only
+// holds the ones that don't.
+#[derive(Debug, Clone)]
 pub enum Form {
-    Integer(i64)
-}
\ No newline at end of file
+    Integer(i64),
+    BigInteger(BigInt),
+    Decimal(BigDecimal),
+    Float(f64),
+    String(String),
+    Boolean(bool)
+}
@@ -1,22 +1,26 @@
 use std::collections::HashMap;
 use std::fs;
 use std::io;
-use std::iter::Peekable;
-use std::vec::IntoIter;
+use std::ops::Range;
 
 use codespan_reporting::diagnostic::Label;
-use codespan_reporting::files::SimpleFile;
 use unicode_xid::UnicodeXID;
 
 use crate::error::diagnostic::Error;
 use crate::error::diagnostic::ErrorForm;
 use crate::error::diagnostic::Errors;
+use crate::error::diagnostic::{FileId, Sources};
+use crate::error::fix::{Applicability, Fix};
+use crate::message;
 use crate::token;
 use crate::parser::token::{Token, TokenType};
 use crate::parser::position::Position;
 
 pub struct Lexer {
-    source: Peekable<IntoIter<char>>,
+    source: Vec<char>,
+    // The byte offset of every character, plus one for the end of the file.
+    // The lexer walks characters, but diagnostics need byte ranges.
+    offsets: Vec<usize>,
     len: usize,
     keywords: HashMap<&'static str, TokenType>,
     pub tokens: Vec<Token>,
@@ -24,17 +28,19 @@ pub struct Lexer {
     pub line_number: usize,
     pub column_number: usize,
     pub error: Errors,
-    pub file: SimpleFile<String, String>
+    pub file: FileId
 }
 
 impl Lexer {
-    pub fn from_text(source: &str, file: SimpleFile<String, String>) -> Lexer {
-        let keywords: HashMap<&str, TokenType> = HashMap::new();
+    pub fn from_text(source: &str, file: FileId) -> Lexer {
+        let offsets = source.char_indices().map(|(offset, _)| offset).chain(Some(source.len())).collect();
+        let source = source.chars().collect::<Vec<_>>();
 
         Lexer {
-            source: source.chars().collect::<Vec<_>>().into_iter().peekable(),
             len: source.len(),
-            keywords,
+            source,
+            offsets,
+            keywords: Lexer::keywords(),
             tokens: Vec::new(),
             pos: 0,
             line_number: 1,
@@ -44,8 +50,30 @@ impl Lexer {
         }
     }
 
-    pub fn from_file(path: &str, file: SimpleFile<String, String>) -> io::Result<Lexer> {
-        Ok(Lexer::from_text(&fs::read_to_string(path)?, file))
+    // Every word that lexes as a keyword rather than an identifier.
+    pub fn keywords() -> HashMap<&'static str, TokenType> {
+        let mut keywords: HashMap<&'static str, TokenType> = HashMap::new();
+        keywords.insert("true", token!(true));
+        keywords.insert("false", token!(false));
+        keywords.insert("let", token!(let));
+        keywords.insert("const", token!(const));
+        keywords.insert("fn", token!(fn));
+        keywords.insert("return", token!(return));
+        keywords.insert("match", token!(match));
+        keywords.insert("if", token!(if));
+        keywords.insert("struct", token!(struct));
+        keywords.insert("enum", token!(enum));
+        keywords.insert("and", token!(and));
+        keywords.insert("or", token!(or));
+        keywords.insert("not", token!(not));
+        keywords
+    }
+
+    // Reads the file at `path` and adds it to `files`, so diagnostics can show it.
+    pub fn from_file(path: &str, files: &mut Sources) -> io::Result<Lexer> {
+        let source = fs::read_to_string(path)?;
+        let file = files.add(String::from(path), source.clone());
+        Ok(Lexer::from_text(&source, file))
     }
 
     pub fn tokenise(&mut self) {
@@ -55,15 +83,24 @@ impl Lexer {
                 match chr {
 
                     ' ' | '\r' | '\t' => continue,
-                    '\n' => self.advance_line(),
+                    '\n' => {
+                        self.add_token(token!(newline), "\n");
+                        self.advance_line()
+                    },
                     '#' => {
-                        while self.source.peek() != Some(&'\n') && !self.at_end() {
+                        while self.peek() != Some(&'\n') && !self.at_end() {
                             self.advance();
                         }
                     },
                     
                     '+' => self.add_token(token!(+), "+"),
-                    '-' => self.add_token(token!(-), "-"),
+                    '-' => {
+                        if self.possible_advance('>') {
+                            self.add_token(token!(->), "->")
+                        } else {
+                            self.add_token(token!(-), "-")
+                        }
+                    },
                     '*' => self.add_token(token!(*), "*"),
                     '/' => self.add_token(token!(/), "/"),
                     '%' => self.add_token(token!(%), "%"),
@@ -73,6 +110,27 @@ impl Lexer {
                     '|' => self.add_token(token!(|), "|"),
                     '~' => self.add_token(token!(~), "~"),
                     '@' => self.add_token(token!(@), "@"),
+
+                    '(' => self.add_token(token!(lparen), "("),
+                    ')' => self.add_token(token!(rparen), ")"),
+                    '[' => self.add_token(token!(lbracket), "["),
+                    ']' => self.add_token(token!(rbracket), "]"),
+                    '{' => self.add_token(token!(lbrace), "{"),
+                    '}' => self.add_token(token!(rbrace), "}"),
+                    ',' => self.add_token(token!(,), ","),
+                    ':' => self.add_token(token!(:), ":"),
+                    '.' => {
+                        if self.possible_advance('.') {
+                            if self.possible_advance('=') {
+                                self.add_token(token!(..=), "..=")
+                            } else {
+                                self.add_token(token!(..), "..")
+                            }
+                        } else {
+                            self.add_token(token!(.), ".")
+                        }
+                    },
+
                     '<' => {
                         if self.possible_advance('<') {
                             self.add_token(token!(<<), "<<")
@@ -84,7 +142,7 @@ impl Lexer {
                     },
                     '>' => {
                         if self.possible_advance('>') {
-                            self.add_token(token!(>), ">>")
+                            self.add_token(token!(>>), ">>")
                         } else if self.possible_advance('=') {
                             self.add_token(token!(>=), ">=")
                         } else {
@@ -95,32 +153,38 @@ impl Lexer {
                         if self.possible_advance('=') {
                             self.add_token(token!(!=), "!=")
                         } else {
-                            let range = self.pos - 1..self.pos - 2;
+                            let range = self.pos - 1..self.pos;
                             let error = if !self.at_end() {
                                 Error::new(
                                     ErrorForm::SyntaxError,
-                                    format!(
-                                        "I expected to find a '=', instead I recieved a {}.", self.source.peek().unwrap()
-                                    ),
-                                    vec![Label::primary((), range)],
-                                    vec![String::from("Maybe you forgot the '=' after the '!'\nThis operator is the not equals operator and checks if two value are not the same.")]
+                                    message!("E0001-lone-bang", found = self.peek().unwrap()),
+                                    vec![Label::primary(self.file, self.span(range))],
+                                    vec![message!("E0001-lone-bang.note")]
                                 )
                             } else {
                                 Error::new(
                                     ErrorForm::EOFError,
-                                    String::from("I expected to find a '=', instead the file ended!"),
-                                    vec![Label::primary((), range)],
-                                    vec![String::from("Seems like you ended the file in the middle of a comparison.\nConsider finishing it.")]
+                                    message!("E0002-lone-bang"),
+                                    vec![Label::primary(self.file, self.span(range))],
+                                    vec![message!("E0002-lone-bang.note")]
                                 )
                             };
+                            // A '!' on its own means nothing, so it can only be the start of a '!='.
+                            let end = self.span(self.pos..self.pos);
+                            let fix = Fix::new(
+                                message!("E0001-lone-bang.fix"),
+                                self.file, end, "=", Applicability::MachineApplicable
+                            );
 
-                            self.error.register_error(error);
+                            self.error.register_error(error.with_fix(fix));
                         }
                     },
 
                     '=' => {
                         if self.possible_advance('=') {
                             self.add_token(token!(==), "==")
+                        } else if self.possible_advance('>') {
+                            self.add_token(token!(=>), "=>")
                         } else {
                             self.add_token(token!(=), "=")
                         }
@@ -137,12 +201,24 @@ impl Lexer {
                 }
             }
         }
+
+        let end = self.len;
+        self.add_token_from(token!(eof), "", end)
     }
 
     fn advance(&mut self) -> Option<char> {
+        let chr = self.source.get(self.pos).copied();
         self.pos += 1;
         self.column_number += 1;
-        self.source.next()
+        chr
+    }
+
+    fn peek(&self) -> Option<&char> {
+        self.source.get(self.pos)
+    }
+
+    fn peek_second(&self) -> Option<&char> {
+        self.source.get(self.pos + 1)
     }
 
     fn advance_line(&mut self) {
@@ -151,9 +227,16 @@ impl Lexer {
     }
 
     fn add_token(&mut self, form: TokenType, content: &str) {
+        let start = self.pos - content.chars().count();
+        self.add_token_from(form, content, start)
+    }
+
+    fn add_token_from(&mut self, form: TokenType, content: &str, start: usize) {
+        let span = self.span(start..self.pos);
         let position = Position {
-            start: self.pos - content.len(),
-            end: self.pos,
+            file: self.file,
+            start: span.start,
+            end: span.end,
             line_number: self.line_number,
             column_number: self.column_number
         };
@@ -167,8 +250,12 @@ impl Lexer {
         self.tokens.push(token)
     }
 
+    fn span(&self, range: Range<usize>) -> Range<usize> {
+        self.offsets[range.start.min(self.len)]..self.offsets[range.end.min(self.len)]
+    }
+
     fn possible_advance(&mut self, expected: char) -> bool {
-        if self.at_end() || self.source.peek().unwrap() != &expected {
+        if self.at_end() || self.peek().unwrap() != &expected {
             return false;
         }
 
@@ -184,8 +271,8 @@ impl Lexer {
         let current_pos = self.pos - 1;
         let mut value = String::new();
 
-        while self.source.peek() != Some(&'"') && !self.at_end() {
-            if self.source.peek() == Some(&'\n') {
+        while self.peek() != Some(&'"') && !self.at_end() {
+            if self.peek() == Some(&'\n') {
                 self.advance_line();
             }
             let character = self.advance();
@@ -196,43 +283,51 @@ impl Lexer {
             let range = current_pos..self.pos;
             let error = Error::new(
                 ErrorForm::EOFError,
-                String::from("I expected you to close your string, but instead, the file ended!"),
-                vec![Label::primary((), range)],
-                vec![String::from("Add in a closing quotation mark to indicate that your string has ended?")]    
+                message!("E0002-unterminated-string"),
+                vec![Label::primary(self.file, self.span(range))],
+                vec![]
+            );
+            // Strings can span lines, so the quote may belong anywhere after
+            // it opened, but the end of its first line is the likeliest place.
+            let line = (current_pos..self.len).find(|&i| self.source[i] == '\n').unwrap_or(self.len);
+            let end = self.span(line..line);
+            let fix = Fix::new(
+                message!("E0002-unterminated-string.fix"),
+                self.file, end, "\"", Applicability::MaybeIncorrect
             );
-            self.error.register_error(error);
+            self.error.register_error(error.with_fix(fix));
         }
 
         self.advance();
 
-        self.add_token(token!(string), &value)
+        self.add_token_from(token!(string), &value, current_pos)
     }
 
     fn get_numeric(&mut self, first: char) {
         let current_pos = self.pos - 1;
         let mut value = String::from(first);
 
-        while let Some(c) = self.source.peek() {
+        while let Some(c) = self.peek() {
             if c.is_numeric() {
                 let chr = self.advance();
                 value.push(chr.unwrap());
 
-                if self.source.peek() == Some(&'.') {
+                if self.peek() == Some(&'.') && self.peek_second() != Some(&'.') {
                     let chr = self.advance();
 
                     if self.at_end() {
                         let range = self.pos..self.pos - 1;
                         let error = Error::new(
                             ErrorForm::EOFError, 
-                            String::from("I found an incomplete float in your code"), 
-                            vec![Label::primary((), range)],
-                            vec![String::from("Make sure you finish typing out your float.")]
+                            message!("E0002-incomplete-float"),
+                            vec![Label::primary(self.file, self.span(range))],
+                            vec![message!("E0002-incomplete-float.note")]
                         );
 
                         self.error.register_error(error);
                     }
 
-                    if let Some(c) = self.source.peek() {
+                    if let Some(c) = self.peek() {
                         if c.is_numeric() {
                             let num_char = self.advance();
                             value.push(chr.unwrap());
@@ -241,31 +336,31 @@ impl Lexer {
                             let range = current_pos..self.pos;
                             let error = Error::new(
                                 ErrorForm::EOFError, 
-                                String::from("I found an invalid character inside this number"), 
-                                vec![Label::primary((), range)],
-                                vec![String::from("Make sure you finish typing out your float."), String::from("Make sure the 'invalid character' isn't a newline, it may trip you up!")]
+                                message!("E0002-invalid-digit"),
+                                vec![Label::primary(self.file, self.span(range))],
+                                vec![message!("E0002-invalid-digit.note"), message!("E0002-invalid-digit.newline")]
                             );
     
                             self.error.register_error(error);
                         }
                     }
                 }
-            } else if c == &'.' {
+            } else if c == &'.' && self.peek_second() != Some(&'.') {
                 let char = self.advance();
 
                 if self.at_end() {
                     let range = self.pos..self.pos;
                     let error = Error::new(
                         ErrorForm::EOFError, 
-                        String::from("I found an incomplete float in your code"), 
-                        vec![Label::primary((), range)],
-                        vec![String::from("Make sure you finish typing out your float.")]
+                        message!("E0002-incomplete-float"),
+                        vec![Label::primary(self.file, self.span(range))],
+                        vec![message!("E0002-incomplete-float.note")]
                     );
 
                     self.error.register_error(error);
                 }
 
-                if let Some(c) = self.source.peek() {
+                if let Some(c) = self.peek() {
                     if c.is_numeric() {
                         let num_char = self.advance();
                         value.push(char.unwrap());
@@ -274,9 +369,9 @@ impl Lexer {
                         let range = current_pos..self.pos;
                         let error = Error::new(
                             ErrorForm::EOFError, 
-                            String::from("I found an invalid character inside this number"), 
-                            vec![Label::primary((), range)],
-                            vec![String::from("Make sure you finish typing out your float.")]
+                            message!("E0002-invalid-digit"),
+                            vec![Label::primary(self.file, self.span(range))],
+                            vec![message!("E0002-invalid-digit.note")]
                         );
 
                         self.error.register_error(error);
@@ -287,21 +382,25 @@ impl Lexer {
             }
         }
 
-        let form: TokenType;
-        
-        if value.contains('.') {
-            form = token!(float)
+        // A `d` straight after the digits makes the number an exact decimal.
+        let suffixed = self.peek() == Some(&'d')
+            && !self.peek_second().is_some_and(|c| c.is_alphanumeric() || c == &'_');
+        let form = if suffixed {
+            self.advance();
+            token!(decimal)
+        } else if value.contains('.') {
+            token!(float)
         } else {
-            form = token!(integer)
-        }
+            token!(integer)
+        };
 
-        self.add_token(form, &value)
+        self.add_token_from(form, &value, current_pos)
     }
 
     fn get_identifier(&mut self, first_char: char) {
         let mut value = String::from(first_char);
 
-        while let Some(c) = self.source.peek() {
+        while let Some(c) = self.peek() {
             if c.is_alphanumeric() || c == &'_' {
                 let chr = self.advance();
                 value.push(chr.unwrap());
@@ -311,7 +410,7 @@ impl Lexer {
         }
 
         let form = match self.keywords.get(&value.as_str()) {
-            Some(t) => t.clone(),
+            Some(t) => *t,
             None => token!(identifier),
         };
 
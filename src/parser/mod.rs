@@ -2,4 +2,5 @@ pub mod position;
 pub mod token;
 pub mod ast;
 pub mod form;
-pub mod lexer;
\ No newline at end of file
+pub mod lexer;
+pub mod parse;
@@ -0,0 +1,1304 @@
+use std::rc::Rc;
+
+use codespan_reporting::diagnostic::Label;
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+
+use crate::error::diagnostic::Error;
+use crate::error::diagnostic::ErrorForm;
+use crate::error::diagnostic::Errors;
+use crate::error::fix::{Applicability, Fix};
+use crate::error::spelling;
+use crate::message;
+use crate::token;
+use crate::parser::ast::*;
+use crate::parser::form::Form;
+use crate::parser::position::Position;
+use crate::parser::token::{Token, TokenType};
+
+type ParseResult<T> = Result<T, Error>;
+
+#[derive(PartialEq)]
+enum Associativity {
+    Left,
+    Right
+}
+
+// Binding power of every infix operator, loosest first.
+fn precedence(form: TokenType) -> Option<(u8, Associativity)> {
+    match form {
+        token!(or) => Some((1, Associativity::Left)),
+        token!(and) => Some((2, Associativity::Left)),
+        token!(==) | token!(!=) | token!(<) | token!(<=) | token!(>) | token!(>=) => Some((COMPARISON_PRECEDENCE, Associativity::Left)),
+        token!(|) => Some((5, Associativity::Left)),
+        token!(@) => Some((6, Associativity::Left)),
+        token!(&) => Some((7, Associativity::Left)),
+        token!(<<) | token!(>>) => Some((8, Associativity::Left)),
+        token!(+) | token!(-) => Some((9, Associativity::Left)),
+        token!(*) | token!(/) | token!(%) => Some((10, Associativity::Left)),
+        token!(^) => Some((12, Associativity::Right)),
+        _ => None
+    }
+}
+
+// `not` binds looser than comparisons, so `not a == b` is `not (a == b)`.
+const NOT_PRECEDENCE: u8 = 3;
+const COMPARISON_PRECEDENCE: u8 = 4;
+
+// Unary minus and bitwise not bind tighter than any binary operator except `^`,
+// so `-2 ^ 2` is `-(2 ^ 2)`.
+const UNARY_PRECEDENCE: u8 = 11;
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    current: usize,
+    // Off while parsing a match scrutinee, where `x {` starts the arms rather
+    // than a struct literal.
+    struct_literals: bool,
+    pub error: Errors
+}
+
+impl Parser {
+    pub fn new(tokens: Vec<Token>) -> Parser {
+        Parser {
+            tokens,
+            current: 0,
+            struct_literals: true,
+            error: Errors::new()
+        }
+    }
+
+    pub fn parse(&mut self) -> Vec<AST> {
+        let mut code = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(eof)) {
+                break;
+            }
+
+            match self.statement() {
+                Ok(ast) => code.push(ast),
+                Err(error) => {
+                    self.error.register_error(error);
+                    self.synchronise();
+                }
+            }
+        }
+
+        code
+    }
+
+    fn statement(&mut self) -> ParseResult<AST> {
+        if self.check(token!(let)) || self.check(token!(const)) {
+            return self.declaration();
+        }
+
+        if self.check(token!(fn)) && self.peek_next().form == token!(identifier) {
+            let keyword = self.advance();
+            let name = self.identifier("context.function-name")?;
+            let function = self.function(keyword, Some(name))?;
+            self.end_of_statement()?;
+            return Ok(AST::Statement(Statement::Function(function)));
+        }
+
+        if self.check(token!(struct)) {
+            let structure = self.structure()?;
+            self.end_of_statement()?;
+            return Ok(AST::Statement(Statement::Struct(structure)));
+        }
+
+        if self.check(token!(enum)) {
+            let enumeration = self.enumeration()?;
+            self.end_of_statement()?;
+            return Ok(AST::Statement(Statement::Enum(enumeration)));
+        }
+
+        if self.check(token!(return)) {
+            let keyword = self.advance();
+            let value = if self.at_end_of_statement() { None } else { Some(Box::new(self.expression_list()?)) };
+            let position = value.as_ref().map_or(keyword.position, |v| keyword.position.merge(v.position()));
+            self.end_of_statement()?;
+            return Ok(AST::Statement(Statement::Return(Return { value, position })));
+        }
+
+        if self.check(token!(lbrace)) {
+            let body = self.body()?;
+            self.end_of_statement()?;
+            return Ok(AST::Statement(Statement::Body(body)));
+        }
+
+        let expression = self.expression_list()?;
+
+        if self.possible_advance(token!(=)) {
+            let target = self.top_level_target(expression)?;
+            let value = self.expression_list()?;
+            let position = target.position().merge(value.position());
+            self.end_of_statement()?;
+
+            return Ok(AST::Statement(Statement::Assignment(Assignment {
+                target,
+                value: Box::new(value),
+                position
+            })));
+        }
+
+        self.end_of_statement()?;
+        Ok(AST::Expression(expression))
+    }
+
+    fn declaration(&mut self) -> ParseResult<AST> {
+        let keyword = self.advance();
+        let kind = if keyword.form == token!(let) { DeclarationKind::Let } else { DeclarationKind::Const };
+
+        let expression = self.expression_list()?;
+        let target = self.top_level_target(expression)?;
+        self.declarable(&target, &keyword)?;
+        let annotation = if self.possible_advance(token!(:)) { Some(self.annotation()?) } else { None };
+
+        let context = if kind == DeclarationKind::Let { "context.let-value" } else { "context.const-value" };
+        self.consume(token!(=), context)?;
+        let value = self.expression_list()?;
+        let position = keyword.position.merge(value.position());
+        self.end_of_statement()?;
+
+        Ok(AST::Statement(Statement::Declaration(Declaration {
+            kind,
+            target,
+            annotation,
+            value: Box::new(value),
+            position
+        })))
+    }
+
+    // Declarations introduce new names, so attributes and indexes make no
+    // sense on their left hand side.
+    fn declarable(&self, target: &Target, keyword: &Token) -> ParseResult<()> {
+        match target {
+            Target::Identifier(_) | Target::Rest(_) => Ok(()),
+            Target::Tuple(t) | Target::List(t) => {
+                for target in &t.targets {
+                    self.declarable(target, keyword)?;
+                }
+                Ok(())
+            },
+            Target::Attribute(_) | Target::Index(_) => {
+                let position = target.position();
+                Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-declare-non-name", keyword = keyword.content),
+                    vec![
+                        Label::primary(position.file, position.start..position.end).with_message(message!("E0001-declare-non-name.label")),
+                        Label::secondary(keyword.position.file, keyword.position.start..keyword.position.end)
+                    ],
+                    vec![message!("E0001-declare-non-name.note", keyword = keyword.content, target = target.as_str())]
+                ))
+            }
+        }
+    }
+
+    fn body(&mut self) -> ParseResult<Body> {
+        let start = self.consume(token!(lbrace), "context.block-start")?;
+        let mut code = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) || self.check(token!(eof)) {
+                break;
+            }
+
+            match self.statement() {
+                Ok(ast) => code.push(ast),
+                Err(error) => {
+                    self.error.register_error(error);
+                    self.synchronise();
+                }
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.block-end")?;
+        Ok(Body { code, position: start.position.merge(end.position) })
+    }
+
+    // Parses everything after `fn` or `fn name`: the type parameters if
+    // there are any, the parameters, the result type if there is one and then
+    // either `=> expression` or a block.
+    fn function(&mut self, keyword: Token, name: Option<Identifier>) -> ParseResult<Function> {
+        let type_parameters = self.type_parameters()?;
+        self.consume(token!(lparen), "context.parameters-start")?;
+        let mut parameters: Vec<Annotated> = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rparen)) {
+                break;
+            }
+
+            let parameter = self.annotated("context.parameter-name")?;
+            if let Some(first) = parameters.iter().find(|p| p.name.name == parameter.name.name) {
+                let (position, first) = (parameter.name.position, first.name.position);
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-duplicate-parameter", name = parameter.name.name),
+                    vec![
+                        Label::primary(position.file, position.start..position.end),
+                        Label::secondary(first.file, first.start..first.end).with_message(message!("E0001-duplicate-parameter.first"))
+                    ],
+                    vec![]
+                ));
+            }
+            parameters.push(parameter);
+
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+        }
+        self.consume(token!(rparen), "context.parameters-end")?;
+        let result = if self.possible_advance(token!(->)) { Some(self.annotation()?) } else { None };
+
+        let body = if self.check(token!(=>)) {
+            self.advance();
+            let value = self.expression(0)?;
+            let position = value.position();
+            Body { code: vec![AST::Expression(value)], position }
+        } else if self.check(token!(lbrace)) {
+            self.body()?
+        } else {
+            let token = self.peek().clone();
+            return Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-function-body", found = token.form),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![message!("E0001-function-body.note")]
+            ));
+        };
+
+        Ok(Function {
+            name,
+            type_parameters,
+            parameters,
+            result,
+            position: keyword.position.merge(body.position),
+            body: Rc::new(body)
+        })
+    }
+
+    fn structure(&mut self) -> ParseResult<Struct> {
+        let keyword = self.advance();
+        let name = self.identifier("context.struct-name")?;
+        let type_parameters = self.type_parameters()?;
+        self.consume(token!(lbrace), "context.struct-start")?;
+        let mut fields: Vec<Annotated> = Vec::new();
+        let mut methods: Vec<Function> = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) || self.check(token!(eof)) {
+                break;
+            }
+
+            if self.check(token!(fn)) {
+                let keyword = self.advance();
+                let name = self.identifier("context.method-name")?;
+                let declared = fields.iter().map(|f| &f.name).chain(methods.iter().filter_map(|m| m.name.as_ref()));
+                duplicate_member(&name, declared, "struct")?;
+                methods.push(self.function(keyword, Some(name))?);
+                self.end_of_statement()?;
+                continue;
+            }
+
+            let field = self.annotated("context.field-name")?;
+            duplicate_member(&field.name, fields.iter().map(|f| &f.name).chain(methods.iter().filter_map(|m| m.name.as_ref())), "struct")?;
+            fields.push(field);
+
+            if !self.possible_advance(token!(,)) && !self.at_end_of_statement() {
+                let token = self.peek().clone();
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-field-separator", found = token.form),
+                    vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                    vec![]
+                ));
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.struct-end")?;
+        Ok(Struct { name, type_parameters, fields, methods, position: keyword.position.merge(end.position) })
+    }
+
+    fn enumeration(&mut self) -> ParseResult<Enum> {
+        let keyword = self.advance();
+        let name = self.identifier("context.enum-name")?;
+        let type_parameters = self.type_parameters()?;
+        self.consume(token!(lbrace), "context.enum-start")?;
+        let mut variants: Vec<Variant> = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) || self.check(token!(eof)) {
+                break;
+            }
+
+            let variant = self.identifier("context.variant-name")?;
+            duplicate_member(&variant, variants.iter().map(|v| &v.name), "enum")?;
+
+            let mut fields: Vec<Annotated> = Vec::new();
+            let mut end = variant.position;
+            if self.possible_advance(token!(lparen)) {
+                loop {
+                    self.skip_newlines();
+                    if self.check(token!(rparen)) {
+                        break;
+                    }
+                    let field = self.annotated("context.field-name")?;
+                    duplicate_member(&field.name, fields.iter().map(|f| &f.name), "variant")?;
+                    fields.push(field);
+                    self.skip_newlines();
+                    if !self.possible_advance(token!(,)) {
+                        break;
+                    }
+                }
+                end = self.consume(token!(rparen), "context.variant-end")?.position;
+            }
+
+            let position = variant.position.merge(end);
+            variants.push(Variant { name: variant, fields, position });
+
+            if !self.possible_advance(token!(,)) && !self.at_end_of_statement() {
+                let token = self.peek().clone();
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-variant-separator", found = token.form),
+                    vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                    vec![]
+                ));
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.enum-end")?;
+        Ok(Enum { name, type_parameters, variants, position: keyword.position.merge(end.position) })
+    }
+
+    fn at_end_of_statement(&self) -> bool {
+        self.check(token!(newline)) || self.check(token!(eof)) || self.check(token!(rbrace))
+    }
+
+    fn end_of_statement(&mut self) -> ParseResult<()> {
+        if self.at_end_of_statement() {
+            self.possible_advance(token!(newline));
+            return Ok(());
+        }
+
+        let token = self.peek().clone();
+        let position = token.position;
+        let mut error = Error::new(
+            ErrorForm::SyntaxError,
+            message!("E0001-end-of-statement", found = token.form),
+            vec![Label::primary(position.file, position.start..position.end)],
+            vec![message!("E0001-end-of-statement.note")]
+        );
+        // A name between two operands is most likely a misspelt operator.
+        if token.form == token!(identifier) {
+            for suggestion in spelling::suggestions(&token.content, ["and", "or"]) {
+                let message = spelling::did_you_mean(&[suggestion]).expect("there is one suggestion");
+                let fix = Fix::new(message, position.file, position.start..position.end, suggestion, Applicability::MaybeIncorrect);
+                error = error.with_fix(fix);
+            }
+        }
+        Err(error)
+    }
+
+    fn top_level_target(&mut self, expression: Expression) -> ParseResult<Target> {
+        if let Expression::Spread(s) = &expression {
+            return Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-bare-rest"),
+                vec![Label::primary(s.position.file, s.position.start..s.position.end)],
+                vec![message!("E0001-bare-rest.target")]
+            ));
+        }
+
+        self.target(expression)
+    }
+
+    fn target(&mut self, expression: Expression) -> ParseResult<Target> {
+        match expression {
+            Expression::Identifier(i) => Ok(Target::Identifier(i)),
+            Expression::Attribute(a) => Ok(Target::Attribute(a)),
+            Expression::Index(i) => Ok(Target::Index(i)),
+            Expression::Tuple(t) => Ok(Target::Tuple(self.target_sequence(t)?)),
+            Expression::List(l) => Ok(Target::List(self.target_sequence(l)?)),
+            Expression::Spread(s) => match *s.value {
+                Expression::Identifier(name) => Ok(Target::Rest(Rest { name, position: s.position })),
+                other => Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-rest-not-name"),
+                    vec![Label::primary(other.position().file, other.position().start..other.position().end)
+                        .with_message(message!("E0001-rest-not-name.label"))],
+                    vec![message!("E0001-bare-rest.target")]
+                ))
+            },
+            other => {
+                let position = other.position();
+                Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-invalid-target"),
+                    vec![Label::primary(position.file, position.start..position.end)
+                        .with_message(message!("E0001-invalid-target.label", target = other.as_str()))],
+                    vec![message!("E0001-invalid-target.note")]
+                ))
+            }
+        }
+    }
+
+    fn target_sequence(&mut self, sequence: Sequence) -> ParseResult<TargetSequence> {
+        let mut targets = Vec::new();
+        let mut rest: Option<Position> = None;
+
+        for element in sequence.elements {
+            let target = self.target(element)?;
+            if let Target::Rest(r) = &target {
+                if let Some(first) = rest {
+                    return Err(Error::new(
+                        ErrorForm::SyntaxError,
+                        message!("E0001-several-rests"),
+                        vec![
+                            Label::primary(r.position.file, r.position.start..r.position.end).with_message(message!("E0001-several-rests.second")),
+                            Label::secondary(first.file, first.start..first.end).with_message(message!("E0001-several-rests.first"))
+                        ],
+                        vec![message!("E0001-several-rests.note")]
+                    ));
+                }
+                rest = Some(r.position);
+            }
+            targets.push(target);
+        }
+
+        Ok(TargetSequence { targets, position: sequence.position })
+    }
+
+    // A comma separated list of expressions without brackets is a tuple, so
+    // `a, b = b, a` swaps two values.
+    fn expression_list(&mut self) -> ParseResult<Expression> {
+        let first = self.element()?;
+
+        if !self.check(token!(,)) {
+            return Ok(first);
+        }
+
+        let mut position = first.position();
+        let mut elements = vec![first];
+
+        while self.possible_advance(token!(,)) {
+            if self.check(token!(=)) || self.check(token!(newline)) || self.check(token!(eof)) {
+                break;
+            }
+            let element = self.element()?;
+            position = position.merge(element.position());
+            elements.push(element);
+        }
+
+        Ok(Expression::Tuple(Sequence { elements, position }))
+    }
+
+    // An item inside a tuple or list, which is allowed to be a `..spread`.
+    fn element(&mut self) -> ParseResult<Expression> {
+        if self.check(token!(..)) {
+            let start = self.advance().position;
+            let value = self.expression(0)?;
+            let position = start.merge(value.position());
+
+            return Ok(Expression::Spread(Spread { value: Box::new(value), position }));
+        }
+
+        self.expression(0)
+    }
+
+    // Everything nested in an expression is parsed through here, so the
+    // stack grows as deeply as they're nested.
+    fn expression(&mut self, min_precedence: u8) -> ParseResult<Expression> {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.parse_expression(min_precedence))
+    }
+
+    fn parse_expression(&mut self, min_precedence: u8) -> ParseResult<Expression> {
+        let mut left = self.unary()?;
+
+        while let Some((precedence, associativity)) = precedence(self.peek().form) {
+            if precedence < min_precedence {
+                break;
+            }
+
+            let operator = self.advance().form;
+            let next = if associativity == Associativity::Left { precedence + 1 } else { precedence };
+            let right = self.expression(next)?;
+            let position = left.position().merge(right.position());
+
+            if precedence == COMPARISON_PRECEDENCE && self.peek_precedence() == Some(COMPARISON_PRECEDENCE) {
+                left = self.comparison_chain(left, operator, right)?;
+                continue;
+            }
+
+            left = Expression::BinaryOperation(BinaryOperation {
+                loperand: Box::new(left),
+                operator,
+                roperand: Box::new(right),
+                position
+            });
+        }
+
+        Ok(left)
+    }
+
+    // `0 <= i < n` means `0 <= i and i < n`, with `i` only evaluated once.
+    fn comparison_chain(&mut self, first: Expression, operator: TokenType, second: Expression) -> ParseResult<Expression> {
+        let mut operands = vec![first, second];
+        let mut operators = vec![operator];
+
+        while self.peek_precedence() == Some(COMPARISON_PRECEDENCE) {
+            operators.push(self.advance().form);
+            operands.push(self.expression(COMPARISON_PRECEDENCE + 1)?);
+        }
+
+        let position = operands[0].position().merge(operands[operands.len() - 1].position());
+        Ok(Expression::Comparison(Comparison { operands, operators, position }))
+    }
+
+    fn peek_precedence(&self) -> Option<u8> {
+        precedence(self.peek().form).map(|(precedence, _)| precedence)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expression> {
+        if self.check(token!(-)) || self.check(token!(~)) || self.check(token!(not)) {
+            let operator = self.advance();
+            let binding = if operator.form == token!(not) { NOT_PRECEDENCE } else { UNARY_PRECEDENCE };
+            let operand = self.expression(binding)?;
+            let position = operator.position.merge(operand.position());
+
+            return Ok(Expression::UnaryOperation(UnaryOperation {
+                operator: operator.form,
+                operand: Box::new(operand),
+                position
+            }));
+        }
+
+        self.postfix()
+    }
+
+    fn postfix(&mut self) -> ParseResult<Expression> {
+        let mut expression = self.primary()?;
+
+        loop {
+            if self.possible_advance(token!(.)) {
+                let name = self.identifier("context.attribute-name")?;
+                let position = expression.position().merge(name.position);
+
+                expression = Expression::Attribute(Attribute {
+                    object: Box::new(expression),
+                    name,
+                    position
+                });
+            } else if self.possible_advance(token!(lparen)) {
+                let mut arguments = vec![];
+                let end = self.bracketed(|parser| {
+                    parser.skip_newlines();
+                    if !parser.check(token!(rparen)) {
+                        arguments.push(parser.element()?);
+                    }
+                    parser.sequence_tail(&mut arguments, token!(rparen), "context.call-end")
+                })?;
+                let position = expression.position().merge(end.position);
+
+                expression = Expression::Call(Call {
+                    callee: Box::new(expression),
+                    arguments,
+                    position
+                });
+            } else if self.possible_advance(token!(lbracket)) {
+                self.skip_newlines();
+                let index = self.expression_list()?;
+                self.skip_newlines();
+                let end = self.consume(token!(rbracket), "context.index-end")?;
+                let position = expression.position().merge(end.position);
+
+                expression = Expression::Index(Index {
+                    object: Box::new(expression),
+                    index: Box::new(index),
+                    position
+                });
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> ParseResult<Expression> {
+        let token = self.advance();
+
+        match token.form {
+            token!(integer) | token!(float) | token!(decimal) | token!(string) | token!(true) | token!(false) => self.literal_token(&token).map(Expression::Literal),
+            token!(identifier) => {
+                let identifier = Identifier { name: token.content, position: token.position };
+                if self.struct_literals && self.check(token!(lbrace)) {
+                    return self.construction(identifier);
+                }
+                Ok(Expression::Identifier(identifier))
+            },
+            token!(lparen) | token!(lbracket) => self.bracketed(|parser| parser.group(token)),
+            token!(lbrace) => self.bracketed(|parser| parser.map(token)),
+            token!(fn) => Ok(Expression::Lambda(self.function(token, None)?)),
+            token!(match) => self.match_expression(token),
+            token!(eof) => Err(Error::new(
+                ErrorForm::EOFError,
+                message!("E0002-expected-expression"),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![message!("E0002-expected-expression.note")]
+            )),
+            _ => Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-expected-expression", found = token.form),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![]
+            ))
+        }
+    }
+
+    // A parenthesised expression, a tuple or a list.
+    fn group(&mut self, token: Token) -> ParseResult<Expression> {
+        match token.form {
+            token!(lparen) => {
+                self.skip_newlines();
+                if self.check(token!(rparen)) {
+                    let end = self.advance();
+                    return Ok(Expression::Tuple(Sequence {
+                        elements: vec![],
+                        position: token.position.merge(end.position)
+                    }));
+                }
+
+                let first = self.element()?;
+                self.skip_newlines();
+                if self.check(token!(rparen)) {
+                    self.advance();
+                    return Ok(first);
+                }
+
+                let mut elements = vec![first];
+                let end = self.sequence_tail(&mut elements, token!(rparen), "context.tuple-end")?;
+                Ok(Expression::Tuple(Sequence { elements, position: token.position.merge(end.position) }))
+            },
+            _ => {
+                self.skip_newlines();
+                let mut elements = vec![];
+                if !self.check(token!(rbracket)) {
+                    elements.push(self.element()?);
+                }
+                let end = self.sequence_tail(&mut elements, token!(rbracket), "context.list-end")?;
+                Ok(Expression::List(Sequence { elements, position: token.position.merge(end.position) }))
+            }
+        }
+    }
+
+    fn map(&mut self, start: Token) -> ParseResult<Expression> {
+        let mut entries = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) {
+                break;
+            }
+
+            let key = self.expression(0)?;
+            self.consume(token!(:), "context.map-colon")?;
+            self.skip_newlines();
+            let value = self.expression(0)?;
+            entries.push(Entry { key, value });
+
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.map-end")?;
+        Ok(Expression::Map(Map { entries, position: start.position.merge(end.position) }))
+    }
+
+    // Struct literals are allowed again inside brackets, even in a match scrutinee.
+    fn bracketed<T>(&mut self, parse: impl FnOnce(&mut Parser) -> ParseResult<T>) -> ParseResult<T> {
+        let allowed = std::mem::replace(&mut self.struct_literals, true);
+        let result = parse(self);
+        self.struct_literals = allowed;
+        result
+    }
+
+    fn construction(&mut self, name: Identifier) -> ParseResult<Expression> {
+        self.consume(token!(lbrace), "context.struct-start")?;
+        let mut fields: Vec<FieldValue> = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) {
+                break;
+            }
+
+            let field = self.identifier("context.field-name")?;
+            if let Some(first) = fields.iter().find(|f| f.name.name == field.name) {
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-duplicate-field-value", name = field.name),
+                    vec![
+                        Label::primary(field.position.file, field.position.start..field.position.end),
+                        Label::secondary(first.name.position.file, first.name.position.start..first.name.position.end).with_message(message!("E0001-duplicate-field-value.first"))
+                    ],
+                    vec![]
+                ));
+            }
+
+            let value = if self.possible_advance(token!(:)) {
+                self.skip_newlines();
+                self.bracketed(|parser| parser.expression(0))?
+            } else {
+                Expression::Identifier(field.clone())
+            };
+            fields.push(FieldValue { name: field, value });
+
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.construction-end")?;
+        let position = name.position.merge(end.position);
+        Ok(Expression::Construction(Construction { name, fields, position }))
+    }
+
+    fn match_expression(&mut self, keyword: Token) -> ParseResult<Expression> {
+        let allowed = std::mem::replace(&mut self.struct_literals, false);
+        let scrutinee = self.expression(0);
+        self.struct_literals = allowed;
+        let scrutinee = scrutinee?;
+        self.consume(token!(lbrace), "context.match-start")?;
+        let mut arms = Vec::new();
+
+        loop {
+            self.skip_newlines();
+            if self.check(token!(rbrace)) || self.check(token!(eof)) {
+                break;
+            }
+
+            let pattern = self.pattern()?;
+            let guard = if self.possible_advance(token!(if)) { Some(self.expression(0)?) } else { None };
+            self.consume(token!(=>), "context.arm-arrow")?;
+
+            let body = if self.check(token!(lbrace)) {
+                self.body()?
+            } else {
+                let value = self.expression(0)?;
+                let position = value.position();
+                Body { code: vec![AST::Expression(value)], position }
+            };
+
+            let position = pattern.position().merge(body.position);
+            arms.push(Arm { pattern, guard, body, position });
+
+            if !self.possible_advance(token!(,)) && !self.check(token!(newline)) && !self.check(token!(rbrace)) {
+                let token = self.peek().clone();
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-end-of-arm", found = token.form),
+                    vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                    vec![message!("E0001-end-of-arm.note")]
+                ));
+            }
+        }
+
+        let end = self.consume(token!(rbrace), "context.match-end")?;
+        Ok(Expression::Match(Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            position: keyword.position.merge(end.position)
+        }))
+    }
+
+    fn pattern(&mut self) -> ParseResult<Pattern> {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.parse_pattern())
+    }
+
+    fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let first = self.pattern_alternative()?;
+        if !self.check(token!(|)) {
+            return Ok(first);
+        }
+
+        let mut position = first.position();
+        let mut patterns = vec![first];
+        while self.possible_advance(token!(|)) {
+            let pattern = self.pattern_alternative()?;
+            position = position.merge(pattern.position());
+            patterns.push(pattern);
+        }
+
+        Ok(Pattern::Alternation(PatternSequence { patterns, position }))
+    }
+
+    fn pattern_alternative(&mut self) -> ParseResult<Pattern> {
+        let pattern = self.pattern_primary()?;
+        if let Pattern::Rest(rest) = &pattern {
+            return Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-bare-rest"),
+                vec![Label::primary(rest.position.file, rest.position.start..rest.position.end)],
+                vec![message!("E0001-bare-rest.pattern")]
+            ));
+        }
+        Ok(pattern)
+    }
+
+    fn pattern_primary(&mut self) -> ParseResult<Pattern> {
+        let token = self.advance();
+
+        match token.form {
+            token!(identifier) if token.content == "_" => Ok(Pattern::Wildcard(token.position)),
+            token!(identifier) if self.check(token!(.)) => {
+                self.advance();
+                let enumeration = Identifier { name: token.content, position: token.position };
+                let variant = self.identifier("context.variant-pattern-name")?;
+
+                let fields = if self.check(token!(lparen)) {
+                    let open = self.advance();
+                    self.skip_newlines();
+                    let mut patterns = vec![];
+                    if !self.check(token!(rparen)) {
+                        patterns.push(self.pattern_primary_or_alternation()?);
+                    }
+                    let end = self.pattern_sequence(&mut patterns, token!(rparen), "context.variant-pattern-end")?;
+                    Some(PatternSequence { patterns, position: open.position.merge(end.position) })
+                } else {
+                    None
+                };
+
+                let end = fields.as_ref().map_or(variant.position, |f| f.position);
+                let position = enumeration.position.merge(end);
+                Ok(Pattern::Variant(VariantPattern { enumeration, variant, fields, position }))
+            },
+            token!(identifier) => Ok(Pattern::Binding(Identifier { name: token.content, position: token.position })),
+            token!(integer) | token!(float) | token!(decimal) | token!(string) | token!(true) | token!(false) | token!(-) => {
+                let start = self.pattern_literal(token)?;
+                if !self.check(token!(..)) && !self.check(token!(..=)) {
+                    return Ok(Pattern::Literal(start));
+                }
+
+                let inclusive = self.advance().form == token!(..=);
+                let token = self.advance();
+                let end = self.pattern_literal(token)?;
+                let position = start.position.merge(end.position);
+                let number = |form: &Form| matches!(form, Form::Integer(_) | Form::BigInteger(_) | Form::Decimal(_) | Form::Float(_));
+                let comparable = (number(&start.form) && number(&end.form))
+                    || matches!((&start.form, &end.form), (Form::String(_), Form::String(_)));
+
+                if !comparable {
+                    return Err(Error::new(
+                        ErrorForm::SyntaxError,
+                        message!("E0001-range-pattern"),
+                        vec![Label::primary(position.file, position.start..position.end)],
+                        vec![]
+                    ));
+                }
+
+                Ok(Pattern::Range(RangePattern { start, end, inclusive, position }))
+            },
+            token!(lparen) => {
+                self.skip_newlines();
+                if self.check(token!(rparen)) {
+                    let end = self.advance();
+                    return Ok(Pattern::Tuple(PatternSequence { patterns: vec![], position: token.position.merge(end.position) }));
+                }
+
+                let first = self.pattern_primary_or_alternation()?;
+                self.skip_newlines();
+                if !matches!(first, Pattern::Rest(_)) && self.possible_advance(token!(rparen)) {
+                    return Ok(first);
+                }
+
+                let mut patterns = vec![first];
+                let end = self.pattern_sequence(&mut patterns, token!(rparen), "context.tuple-pattern-end")?;
+                Ok(Pattern::Tuple(PatternSequence { patterns, position: token.position.merge(end.position) }))
+            },
+            token!(lbracket) => {
+                self.skip_newlines();
+                let mut patterns = vec![];
+                if !self.check(token!(rbracket)) {
+                    patterns.push(self.pattern_primary_or_alternation()?);
+                }
+                let end = self.pattern_sequence(&mut patterns, token!(rbracket), "context.list-pattern-end")?;
+                Ok(Pattern::List(PatternSequence { patterns, position: token.position.merge(end.position) }))
+            },
+            token!(..) => {
+                let name = if self.check(token!(identifier)) { Some(self.identifier("context.rest-name")?) } else { None };
+                let position = name.as_ref().map_or(token.position, |n| token.position.merge(n.position));
+                Ok(Pattern::Rest(RestPattern { name, position }))
+            },
+            _ => Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-expected-pattern", found = token.form),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![message!("E0001-expected-pattern.note")]
+            ))
+        }
+    }
+
+    // Inside brackets an alternation doesn't need its own parentheses, and a
+    // rest pattern is allowed.
+    fn pattern_primary_or_alternation(&mut self) -> ParseResult<Pattern> {
+        if self.check(token!(..)) {
+            return self.pattern_primary();
+        }
+        self.pattern()
+    }
+
+    fn pattern_sequence(&mut self, patterns: &mut Vec<Pattern>, close: TokenType, context: &str) -> ParseResult<Token> {
+        loop {
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+            self.skip_newlines();
+            if self.check(close) {
+                break;
+            }
+            patterns.push(self.pattern_primary_or_alternation()?);
+        }
+
+        let rests = patterns.iter().filter(|p| matches!(p, Pattern::Rest(_))).map(|p| p.position()).collect::<Vec<_>>();
+        if rests.len() > 1 {
+            return Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-several-rests"),
+                vec![
+                    Label::primary(rests[1].file, rests[1].start..rests[1].end).with_message(message!("E0001-several-rests.second")),
+                    Label::secondary(rests[0].file, rests[0].start..rests[0].end).with_message(message!("E0001-several-rests.first"))
+                ],
+                vec![message!("E0001-several-rests.note")]
+            ));
+        }
+
+        self.consume(close, context)
+    }
+
+    fn pattern_literal(&mut self, token: Token) -> ParseResult<Literal> {
+        if token.form == token!(-) {
+            let number = self.advance();
+            if !matches!(number.form, token!(integer) | token!(float) | token!(decimal)) {
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-negative-pattern", found = number.form),
+                    vec![Label::primary(number.position.file, number.position.start..number.position.end)],
+                    vec![]
+                ));
+            }
+            let negated = Token {
+                content: format!("-{}", number.content),
+                position: token.position.merge(number.position),
+                ..number
+            };
+            return self.pattern_literal(negated);
+        }
+
+        self.literal_token(&token)
+    }
+
+    // Parses the `, b, c)` that follows the first element of a bracketed sequence.
+    fn sequence_tail(&mut self, elements: &mut Vec<Expression>, close: TokenType, context: &str) -> ParseResult<Token> {
+        loop {
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+            self.skip_newlines();
+            if self.check(close) {
+                break;
+            }
+            elements.push(self.element()?);
+        }
+
+        self.consume(close, context)
+    }
+
+    fn literal_token(&self, token: &Token) -> ParseResult<Literal> {
+        match token.form {
+            token!(integer) => match (token.content.parse::<i64>(), token.content.parse::<BigInt>()) {
+                (Ok(i), _) => Ok(self.literal(Form::Integer(i), token)),
+                (_, Ok(i)) => Ok(self.literal(Form::BigInteger(i), token)),
+                _ => Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-invalid-number"),
+                    vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                    vec![message!("E0001-invalid-number.note")]
+                ))
+            },
+            token!(float) => Ok(self.literal(Form::Float(token.content.parse().unwrap_or(f64::NAN)), token)),
+            token!(decimal) => match token.content.parse::<BigDecimal>() {
+                Ok(d) => Ok(self.literal(Form::Decimal(d), token)),
+                Err(_) => Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-invalid-number"),
+                    vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                    vec![message!("E0001-invalid-number.note")]
+                ))
+            },
+            token!(string) => Ok(self.literal(Form::String(token.content.clone()), token)),
+            token!(true) => Ok(self.literal(Form::Boolean(true), token)),
+            _ => Ok(self.literal(Form::Boolean(false), token))
+        }
+    }
+
+    fn literal(&self, form: Form, token: &Token) -> Literal {
+        Literal { form, position: token.position }
+    }
+
+    // A name that may be followed by `: type`.
+    fn annotated(&mut self, context: &str) -> ParseResult<Annotated> {
+        let name = self.identifier(context)?;
+        let annotation = if self.possible_advance(token!(:)) { Some(self.annotation()?) } else { None };
+        Ok(Annotated { name, annotation })
+    }
+
+    // `<T, U: number + ordered>` after the name of a function, struct or
+    // enum, if it's generic.
+    fn type_parameters(&mut self) -> ParseResult<Vec<TypeParameter>> {
+        let mut parameters: Vec<TypeParameter> = Vec::new();
+        if !self.possible_advance(token!(<)) {
+            return Ok(parameters);
+        }
+
+        loop {
+            self.skip_newlines();
+            if self.check_angle_close() {
+                break;
+            }
+
+            let name = self.identifier("context.type-parameter-name")?;
+            if let Some(first) = parameters.iter().find(|p| p.name.name == name.name) {
+                let (position, first) = (name.position, first.name.position);
+                return Err(Error::new(
+                    ErrorForm::SyntaxError,
+                    message!("E0001-duplicate-type-parameter", name = name.name),
+                    vec![
+                        Label::primary(position.file, position.start..position.end),
+                        Label::secondary(first.file, first.start..first.end).with_message(message!("E0001-duplicate-parameter.first"))
+                    ],
+                    vec![]
+                ));
+            }
+            let mut bounds = Vec::new();
+            if self.possible_advance(token!(:)) {
+                loop {
+                    bounds.push(self.identifier("context.bound-name")?);
+                    if !self.possible_advance(token!(+)) {
+                        break;
+                    }
+                }
+            }
+            parameters.push(TypeParameter { name, bounds });
+
+            self.skip_newlines();
+            if !self.possible_advance(token!(,)) {
+                break;
+            }
+        }
+        self.consume_angle_close("context.type-parameters-end")?;
+        Ok(parameters)
+    }
+
+    // A type, like `int`, `(int, str)`, `[str]`, `{str: int}`,
+    // `fn(int) -> bool` or `Pair<int, str>`. Brackets around a single type
+    // only group it, so a tuple of one needs a trailing comma, as with values.
+    fn annotation(&mut self) -> ParseResult<Annotation> {
+        stacker::maybe_grow(128 * 1024, 1024 * 1024, || self.parse_annotation())
+    }
+
+    fn parse_annotation(&mut self) -> ParseResult<Annotation> {
+        let token = self.advance();
+
+        match token.form {
+            token!(identifier) if self.possible_advance(token!(<)) => {
+                let (arguments, _) = self.annotation_list(token!(>))?;
+                let end = self.consume_angle_close("context.type-arguments-end")?;
+                Ok(Annotation::Generic(GenericAnnotation {
+                    name: Identifier { name: token.content, position: token.position },
+                    arguments,
+                    position: token.position.merge(end.position)
+                }))
+            },
+            token!(identifier) => Ok(Annotation::Name(Identifier { name: token.content, position: token.position })),
+            token!(lparen) => {
+                let (mut annotations, trailing) = self.annotation_list(token!(rparen))?;
+                let end = self.consume(token!(rparen), "context.tuple-type-end")?;
+                if annotations.len() == 1 && !trailing {
+                    return Ok(annotations.remove(0));
+                }
+                Ok(Annotation::Tuple(AnnotationSequence { annotations, position: token.position.merge(end.position) }))
+            },
+            token!(lbracket) => {
+                let element = self.annotation()?;
+                let end = self.consume(token!(rbracket), "context.list-type-end")?;
+                Ok(Annotation::List(ListAnnotation { element: Box::new(element), position: token.position.merge(end.position) }))
+            },
+            token!(lbrace) => {
+                let key = self.annotation()?;
+                self.consume(token!(:), "context.map-type-colon")?;
+                let value = self.annotation()?;
+                let end = self.consume(token!(rbrace), "context.map-type-end")?;
+                Ok(Annotation::Map(MapAnnotation { key: Box::new(key), value: Box::new(value), position: token.position.merge(end.position) }))
+            },
+            token!(fn) => {
+                self.consume(token!(lparen), "context.function-type-start")?;
+                let (parameters, _) = self.annotation_list(token!(rparen))?;
+                self.consume(token!(rparen), "context.function-type-end")?;
+                self.consume(token!(->), "context.function-type-arrow")?;
+                let result = self.annotation()?;
+                let position = token.position.merge(result.position());
+                Ok(Annotation::Function(FunctionAnnotation { parameters, result: Box::new(result), position }))
+            },
+            token!(eof) => Err(Error::new(
+                ErrorForm::EOFError,
+                message!("E0002-expected-type"),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![message!("E0001-expected-type.note")]
+            )),
+            _ => Err(Error::new(
+                ErrorForm::SyntaxError,
+                message!("E0001-expected-type", found = token.form),
+                vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+                vec![message!("E0001-expected-type.note")]
+            ))
+        }
+    }
+
+    // Comma separated types up to `close`, and whether a comma came last.
+    fn annotation_list(&mut self, close: TokenType) -> ParseResult<(Vec<Annotation>, bool)> {
+        let mut annotations = Vec::new();
+        let mut trailing = false;
+
+        loop {
+            self.skip_newlines();
+            if self.check(close) || (close == token!(>) && self.check_angle_close()) {
+                break;
+            }
+            annotations.push(self.annotation()?);
+            self.skip_newlines();
+            trailing = self.possible_advance(token!(,));
+            if !trailing {
+                break;
+            }
+        }
+
+        Ok((annotations, trailing))
+    }
+
+    // Nested type arguments end in `>>`, which the lexer reads as a shift.
+    fn check_angle_close(&self) -> bool {
+        self.check(token!(>)) || self.check(token!(>>))
+    }
+
+    // Consumes one `>`, splitting a `>>` in two and leaving the second half.
+    fn consume_angle_close(&mut self, context: &str) -> ParseResult<Token> {
+        if !self.check(token!(>>)) {
+            return self.consume(token!(>), context);
+        }
+
+        let shift = &mut self.tokens[self.current];
+        let mut first = shift.clone();
+        first.form = token!(>);
+        first.content = String::from(">");
+        first.position.end = first.position.start + 1;
+        shift.form = token!(>);
+        shift.content = String::from(">");
+        shift.position.start += 1;
+        shift.position.column_number += 1;
+        Ok(first)
+    }
+
+    fn identifier(&mut self, context: &str) -> ParseResult<Identifier> {
+        let token = self.consume(token!(identifier), context)?;
+        Ok(Identifier { name: token.content, position: token.position })
+    }
+
+    fn consume(&mut self, expected: TokenType, context: &str) -> ParseResult<Token> {
+        if self.check(expected) {
+            return Ok(self.advance());
+        }
+
+        let token = self.peek().clone();
+        let (form, message) = if token.form == token!(eof) {
+            (ErrorForm::EOFError, message!("E0002-expected-token", expected = expected, context = message!(context)))
+        } else {
+            (ErrorForm::SyntaxError, message!("E0001-expected-token", expected = expected, context = message!(context), found = token.form))
+        };
+
+        Err(Error::new(
+            form,
+            message,
+            vec![Label::primary(token.position.file, token.position.start..token.position.end)],
+            vec![]
+        ))
+    }
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current.min(self.tokens.len() - 1)]
+    }
+
+    fn peek_next(&self) -> &Token {
+        &self.tokens[(self.current + 1).min(self.tokens.len() - 1)]
+    }
+
+    fn check(&self, form: TokenType) -> bool {
+        self.peek().form == form
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token.form != token!(eof) {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn possible_advance(&mut self, expected: TokenType) -> bool {
+        if !self.check(expected) {
+            return false;
+        }
+
+        self.advance();
+        true
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.possible_advance(token!(newline)) {}
+    }
+
+    // After an error, skip to the start of the next line so one mistake doesn't
+    // cascade into a wall of diagnostics.
+    fn synchronise(&mut self) {
+        while !self.check(token!(newline)) && !self.check(token!(eof)) {
+            self.advance();
+        }
+    }
+}
+
+fn duplicate_member<'a>(name: &Identifier, mut declared: impl Iterator<Item = &'a Identifier>, container: &str) -> ParseResult<()> {
+    match declared.find(|member| member.name == name.name) {
+        Some(first) => Err(Error::new(
+            ErrorForm::SyntaxError,
+            message!("E0001-duplicate-member", container = container, name = name.name),
+            vec![
+                Label::primary(name.position.file, name.position.start..name.position.end),
+                Label::secondary(first.position.file, first.position.start..first.position.end).with_message(message!("E0001-duplicate-member.first"))
+            ],
+            vec![]
+        )),
+        None => Ok(())
+    }
+}
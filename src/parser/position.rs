@@ -1,8 +1,24 @@
+use crate::error::diagnostic::FileId;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, PartialEq, Copy, Clone)]
 pub struct Position {
+    pub file: FileId,
     pub start: usize,
     pub end: usize,
     pub line_number: usize,
     pub column_number: usize
-}
\ No newline at end of file
+}
+
+impl Position {
+    pub fn merge(&self, other: Position) -> Position {
+        let first = if self.start <= other.start { *self } else { other };
+
+        Position {
+            file: self.file,
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            line_number: first.line_number,
+            column_number: first.column_number
+        }
+    }
+}
@@ -7,6 +7,7 @@ pub enum TokenType {
     // Values
     Integer,
     Float,
+    Decimal,
     String,
     True,
     False,
@@ -16,6 +17,22 @@ pub enum TokenType {
 
     // Assignment
     Assignment,
+    Let,
+    Const,
+
+    // Functions
+    Function,
+    Return,
+    FatArrow,
+    Arrow,
+
+    // Pattern matching
+    Match,
+    If,
+
+    // Data types
+    Struct,
+    Enum,
     
     // Arithmetic ops
     Plus,
@@ -33,6 +50,11 @@ pub enum TokenType {
     BitwiseLshift,
     BitwiseRshift,
 
+    // Logical ops
+    And,
+    Or,
+    Not,
+
     // Relational ops
     Equals,
     NotEquals,
@@ -41,12 +63,26 @@ pub enum TokenType {
     GreaterThan,
     GreaterEquals,
 
+    // Punctuation
+    LeftParen,
+    RightParen,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    Comma,
+    Colon,
+    Dot,
+    DotDot,
+    DotDotEquals,
+
     // Miscellaneous
     Hash,
+    Newline,
     Eof
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 pub struct Token {
     pub form: TokenType,
     pub position: Position,
@@ -57,6 +93,10 @@ impl Token {
     pub fn len(&self) -> usize {
         self.content.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.content.is_empty()
+    }
 }
 
 impl fmt::Debug for Token {
@@ -103,6 +143,39 @@ macro_rules! token {
     [>>] => {
         $crate::parser::token::TokenType::BitwiseRshift
     };
+    [fn] => {
+        $crate::parser::token::TokenType::Function
+    };
+    [return] => {
+        $crate::parser::token::TokenType::Return
+    };
+    [=>] => {
+        $crate::parser::token::TokenType::FatArrow
+    };
+    [->] => {
+        $crate::parser::token::TokenType::Arrow
+    };
+    [match] => {
+        $crate::parser::token::TokenType::Match
+    };
+    [if] => {
+        $crate::parser::token::TokenType::If
+    };
+    [struct] => {
+        $crate::parser::token::TokenType::Struct
+    };
+    [enum] => {
+        $crate::parser::token::TokenType::Enum
+    };
+    [and] => {
+        $crate::parser::token::TokenType::And
+    };
+    [or] => {
+        $crate::parser::token::TokenType::Or
+    };
+    [not] => {
+        $crate::parser::token::TokenType::Not
+    };
     [==] => {
         $crate::parser::token::TokenType::Equals
     };
@@ -121,12 +194,48 @@ macro_rules! token {
     [>=] => {
         $crate::parser::token::TokenType::GreaterEquals
     };
+    [lparen] => {
+        $crate::parser::token::TokenType::LeftParen
+    };
+    [rparen] => {
+        $crate::parser::token::TokenType::RightParen
+    };
+    [lbracket] => {
+        $crate::parser::token::TokenType::LeftBracket
+    };
+    [rbracket] => {
+        $crate::parser::token::TokenType::RightBracket
+    };
+    [lbrace] => {
+        $crate::parser::token::TokenType::LeftBrace
+    };
+    [rbrace] => {
+        $crate::parser::token::TokenType::RightBrace
+    };
+    [,] => {
+        $crate::parser::token::TokenType::Comma
+    };
+    [:] => {
+        $crate::parser::token::TokenType::Colon
+    };
+    [.] => {
+        $crate::parser::token::TokenType::Dot
+    };
+    [..] => {
+        $crate::parser::token::TokenType::DotDot
+    };
+    [..=] => {
+        $crate::parser::token::TokenType::DotDotEquals
+    };
     [integer] => {
         $crate::parser::token::TokenType::Integer
     };
     [float] => {
         $crate::parser::token::TokenType::Float
     };
+    [decimal] => {
+        $crate::parser::token::TokenType::Decimal
+    };
     [string] => {
         $crate::parser::token::TokenType::String
     };
@@ -142,9 +251,18 @@ macro_rules! token {
     [=] => {
         $crate::parser::token::TokenType::Assignment
     };
+    [let] => {
+        $crate::parser::token::TokenType::Let
+    };
+    [const] => {
+        $crate::parser::token::TokenType::Const
+    };
     [#] => {
         $crate::parser::token::TokenType::Hash
     };
+    [newline] => {
+        $crate::parser::token::TokenType::Newline
+    };
     [eof] => {
         $crate::parser::token::TokenType::Eof
     };
@@ -168,20 +286,46 @@ impl fmt::Display for TokenType {
                 token!(@) => "@",
                 token!(<<) => "<<",
                 token!(>>) => ">>",
+                token!(fn) => "fn",
+                token!(return) => "return",
+                token!(=>) => "=>",
+                token!(->) => "->",
+                token!(match) => "match",
+                token!(if) => "if",
+                token!(struct) => "struct",
+                token!(enum) => "enum",
+                token!(and) => "and",
+                token!(or) => "or",
+                token!(not) => "not",
                 token!(==) => "==",
                 token!(!=) => "!=",
                 token!(<) => "<",
                 token!(<=) => "<=",
                 token!(>) => ">",
                 token!(>=) => ">=",
+                token!(lparen) => "(",
+                token!(rparen) => ")",
+                token!(lbracket) => "[",
+                token!(rbracket) => "]",
+                token!(lbrace) => "{",
+                token!(rbrace) => "}",
+                token!(,) => ",",
+                token!(:) => ":",
+                token!(.) => ".",
+                token!(..) => "..",
+                token!(..=) => "..=",
                 token!(integer) => "integer",
                 token!(float) => "float",
+                token!(decimal) => "decimal",
                 token!(string) => "string",
                 token!(true) => "true",
                 token!(false) => "false",
                 token!(identifier) => "identifier",
                 token!(=) => "=",
+                token!(let) => "let",
+                token!(const) => "const",
                 token!(#) => "#",
+                token!(newline) => "newline",
                 token!(eof) => "eof"
             }
         )
@@ -0,0 +1,146 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use language::analysis::checker::Checker;
+use language::analysis::resolver::Resolver;
+use language::compiler::cache;
+use language::compiler::chunk::Function;
+use language::compiler::compile::Compiler;
+use language::compiler::disassemble::disassemble;
+use language::compiler::vm::VM;
+
+use common::{compile, parse, parse_file};
+
+const SOURCE: &str = "
+struct Point {
+    x, y
+    fn sum(self) => self.x + self.y
+}
+enum Shape { Circle(radius), Empty }
+fn describe(value) => match value {
+    0..=9 => \"digit\",
+    (a, ..rest) => rest,
+    Shape.Circle(r) | Shape.Circle(r) if r > 1 => r,
+    _ => \"other\"
+}
+let big = 123456789012345678901234567890
+let counter = fn() {
+    let count = 0
+    fn() {
+        count = count + 1
+        count
+    }
+}()
+counter()
+[describe(3), describe((1, 2.5, 0.1d)), describe(Shape.Circle(2)), Point { x: 1, y: 2 }.sum(), counter(), big, true]";
+
+fn run(script: Rc<Function>) -> String {
+    VM::new().run(script).map(|value| value.to_string()).unwrap_or_else(|error| error.message)
+}
+
+// A directory of its own for each test, since they run at the same time.
+fn scratch(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("language-cache-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory.join("main.language")
+}
+
+#[test]
+fn decoding_gives_back_what_was_encoded() {
+    let script = compile(&parse(SOURCE));
+    let decoded = cache::decode(&cache::encode(&script, &[SOURCE], 0), &[SOURCE], 0).expect("a fresh encoding should decode");
+    assert_eq!(disassemble(&decoded), disassemble(&script));
+    assert_eq!(format!("{:?}", decoded), format!("{:?}", script));
+    assert_eq!(run(Rc::new(decoded)), run(script));
+}
+
+#[test]
+fn positions_take_the_file_they_are_loaded_for() {
+    let source = "let a = 1\na + \"b\"";
+    let script = compile(&parse(source));
+    let decoded = cache::decode(&cache::encode(&script, &[source], 0), &[source], 3).unwrap();
+    let error = VM::new().run(Rc::new(decoded)).unwrap_err();
+    assert_eq!(error.labels[0].file_id, 3);
+    assert_eq!(error.labels[0].range, 10..17);
+}
+
+#[test]
+fn stale_and_damaged_files_are_rejected() {
+    let script = compile(&parse(SOURCE));
+    let bytes = cache::encode(&script, &[SOURCE], 0);
+
+    let changed = format!("{}\n", SOURCE);
+    assert!(cache::decode(&bytes, &[&changed], 0).is_none(), "the source changed");
+
+    let mut version = bytes.clone();
+    version[4] = version[4].wrapping_add(1);
+    assert!(cache::decode(&version, &[SOURCE], 0).is_none(), "another version wrote it");
+
+    let mut magic = bytes.clone();
+    magic[0] = b'X';
+    assert!(cache::decode(&magic, &[SOURCE], 0).is_none(), "it isn't a .langc file");
+
+    for at in (22..bytes.len()).step_by(7) {
+        let mut damaged = bytes.clone();
+        damaged[at] ^= 0x5a;
+        assert!(cache::decode(&damaged, &[SOURCE], 0).is_none(), "byte {} was damaged", at);
+    }
+    for length in 0..bytes.len() {
+        assert!(cache::decode(&bytes[..length], &[SOURCE], 0).is_none(), "it was cut short at {}", length);
+    }
+}
+
+#[test]
+fn the_cache_is_read_back_and_rebuilt_when_damaged() {
+    let path = scratch("rebuild");
+    let script = compile(&parse(SOURCE));
+    assert!(cache::load(&path, &[SOURCE], 0).is_none());
+
+    cache::store(&path, &[SOURCE], 0, &script);
+    let cached = cache::cache_path(&path);
+    assert_eq!(cached, path.with_file_name("__langcache__").join("main.language.langc"));
+    let loaded = cache::load(&path, &[SOURCE], 0).expect("it was just stored");
+    assert_eq!(disassemble(&loaded), disassemble(&script));
+
+    fs::write(&cached, b"LNGC nonsense").unwrap();
+    assert!(cache::load(&path, &[SOURCE], 0).is_none());
+    cache::store(&path, &[SOURCE], 0, &script);
+    assert!(cache::load(&path, &[SOURCE], 0).is_some());
+
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+// Compiles files one after another, sharing what they declare like `main`
+// does, giving back each script.
+fn compile_files(sources: &[&str]) -> Vec<Rc<Function>> {
+    let mut resolver = Resolver::new();
+    let mut checker = Checker::new();
+    let mut compiler = Compiler::new();
+    sources.iter().enumerate().map(|(file, source)| {
+        let mut code = parse_file(source, file, &mut resolver);
+        checker.check(&mut code);
+        assert!(checker.error.errors.is_empty(), "type errors: {:?}", checker.error.errors);
+        compiler.compile(&code)
+    }).collect()
+}
+
+#[test]
+fn files_checked_before_are_part_of_the_key() {
+    let library = "fn f(x: int) -> int => x";
+    let user = "let g = fn(y) => y\nf(g(\"s\"))";
+    let scripts = compile_files(&[library, user]);
+    let bytes = cache::encode(&scripts[1], &[library, user], 1);
+    assert!(cache::decode(&bytes, &["fn f(x) => x", user], 1).is_none(), "the file before it changed");
+
+    // The cast in the second file checks against an annotation in the first.
+    let decoded = cache::decode(&bytes, &[library, user], 1).expect("nothing changed");
+    let mut vm = VM::new();
+    vm.run(scripts[0].clone()).unwrap();
+    let error = vm.run(Rc::new(decoded)).unwrap_err();
+    let labels = error.labels.iter().map(|label| (label.file_id, label.range.clone())).collect::<Vec<_>>();
+    assert_eq!(labels, [(1, 21..27), (0, 8..11)]);
+}
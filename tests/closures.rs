@@ -0,0 +1,122 @@
+mod common;
+
+use language::error::diagnostic::Error;
+use language::interpreter::interpret::Interpreter;
+
+use common::parse;
+
+fn run(source: &str) -> Result<String, Error> {
+    Interpreter::new().interpret(&parse(source)).map(|value| value.to_string())
+}
+
+fn eval(source: &str) -> String {
+    run(source).expect("program should run")
+}
+
+#[test]
+fn lambda_with_expression_body() {
+    assert_eq!(eval("let double = fn(x) => x * 2\ndouble(21)"), "42");
+}
+
+#[test]
+fn lambda_called_immediately() {
+    assert_eq!(eval("(fn(a, b) => a - b)(10, 3)"), "7");
+}
+
+#[test]
+fn captured_variable_reassigned_after_capture_is_seen() {
+    let source = "
+let base = 10
+let add = fn(x) => x + base
+base = 100
+add(1)";
+    assert_eq!(eval(source), "101");
+}
+
+#[test]
+fn assignment_inside_closure_is_seen_outside() {
+    let source = "
+let count = 0
+let bump = fn() { count = count + 1 }
+bump()
+bump()
+count";
+    assert_eq!(eval(source), "2");
+}
+
+#[test]
+fn closures_from_the_same_scope_share_variables() {
+    let source = "
+fn pair() {
+    let value = 0
+    let set = fn(v) { value = v }
+    let get = fn() => value
+    return (set, get)
+}
+let (set, get) = pair()
+set(5)
+get()";
+    assert_eq!(eval(source), "5");
+}
+
+#[test]
+fn closure_returned_from_function_keeps_its_state() {
+    let source = "
+fn counter() {
+    let count = 0
+    return fn() {
+        count = count + 1
+        count
+    }
+}
+let first = counter()
+let second = counter()
+first()
+first()
+second()
+[first(), second()]";
+    assert_eq!(eval(source), "[3, 2]");
+}
+
+#[test]
+fn closure_outlives_the_block_it_captured() {
+    let source = "
+let get = 0
+{
+    let hidden = \"inside\"
+    get = fn() => hidden
+}
+get()";
+    assert_eq!(eval(source), "\"inside\"");
+}
+
+#[test]
+fn parameters_shadow_captured_names() {
+    let source = "
+let x = 1
+let f = fn(x) => x * 10
+[f(2), x]";
+    assert_eq!(eval(source), "[20, 1]");
+}
+
+#[test]
+fn named_functions_can_recurse() {
+    assert_eq!(eval("fn fact(n) => n <= 1 and 1 or n * fact(n - 1)\nfact(10)"), "3628800");
+}
+
+#[test]
+fn functions_can_call_ones_declared_after_them() {
+    let even_odd = "fn even(n) => n == 0 or odd(n - 1)\nfn odd(n) => n != 0 and even(n - 1)\n";
+    assert_eq!(common::both(&parse(&format!("{}(even(10), odd(7), even(3))", even_odd))).unwrap(), "(true, true, false)");
+
+    let nested = format!("fn parity(n) {{\n{}    (even(n), make().sum())\n}}\n", even_odd.replace("fn ", "    fn "));
+    let source = format!("{}fn make() => Point {{ x: 1 }}\nstruct Point {{\n    x\n    fn sum(self) => self.x + 1\n}}\nparity(4)", nested);
+    assert_eq!(common::both(&parse(&source)).unwrap(), "(true, 2)");
+}
+
+#[test]
+fn wrong_number_of_arguments_is_reported() {
+    let error = run("let f = fn(a, b) => a\nf(1)").unwrap_err();
+    assert_eq!(error.message, "This function takes 2 arguments, but I was given 1.");
+    assert_eq!(error.labels.len(), 2);
+}
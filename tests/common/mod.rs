@@ -0,0 +1,73 @@
+// What the integration tests share: reading a program the way `main` does,
+// and running it on both the interpreter and the VM. Not every test uses
+// all of it.
+#![allow(dead_code)]
+
+use std::ops::Range;
+use std::rc::Rc;
+
+use language::analysis::resolver::Resolver;
+use language::compiler::chunk::Function;
+use language::compiler::compile::Compiler;
+use language::compiler::vm::VM;
+use language::error::diagnostic::{Error, FileId, Severity};
+use language::interpreter::interpret::Interpreter;
+use language::parser::ast::AST;
+use language::parser::lexer::Lexer;
+use language::parser::parse::Parser;
+
+// Lexes, parses and resolves a file, giving back its code with everything
+// that was reported on the way.
+pub fn read(source: &str, file: FileId, resolver: &mut Resolver) -> (Vec<AST>, Vec<Error>) {
+    let mut lexer = Lexer::from_text(source, file);
+    lexer.tokenise();
+    let mut parser = Parser::new(lexer.tokens);
+    let code = parser.parse();
+    resolver.resolve(&code);
+
+    let mut errors = lexer.error.errors;
+    errors.append(&mut parser.error.errors);
+    errors.append(&mut resolver.error.errors);
+    (code, errors)
+}
+
+// `read`, checking it found no errors. Files that run one after another
+// share a resolver, like they do in `main`.
+pub fn parse_file(source: &str, file: FileId, resolver: &mut Resolver) -> Vec<AST> {
+    let (code, errors) = read(source, file, resolver);
+    let errors = errors.iter().filter(|error| error.severity >= Severity::Error).collect::<Vec<_>>();
+    assert!(errors.is_empty(), "errors before running: {:?}", errors);
+    code
+}
+
+pub fn parse(source: &str) -> Vec<AST> {
+    parse_file(source, 0, &mut Resolver::new())
+}
+
+pub fn compile(code: &[AST]) -> Rc<Function> {
+    let mut compiler = Compiler::new();
+    let script = compiler.compile(code);
+    assert!(compiler.error.is_empty(), "compiler errors: {:?}", compiler.error.errors);
+    script
+}
+
+// Runs code on both the interpreter and the VM and checks they agree, on
+// the value or on the error and where it points.
+pub fn both(code: &[AST]) -> Result<String, Error> {
+    let interpreted = Interpreter::new().interpret(code).map(|value| value.to_string());
+    let compiled = VM::new().run(compile(code)).map(|value| value.to_string());
+
+    match (&interpreted, &compiled) {
+        (Ok(a), Ok(b)) => assert_eq!(a, b, "the interpreter and the VM disagree"),
+        (Err(a), Err(b)) => {
+            assert_eq!(a.message, b.message, "the interpreter and the VM fail differently");
+            assert_eq!(spans(a), spans(b), "the interpreter and the VM blame different spans");
+        },
+        _ => panic!("the interpreter gave {:?} but the VM gave {:?}", interpreted, compiled)
+    }
+    compiled
+}
+
+pub fn spans(error: &Error) -> Vec<Range<usize>> {
+    error.labels.iter().map(|label| label.range.clone()).collect()
+}
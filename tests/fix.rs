@@ -0,0 +1,47 @@
+use language::error::diagnostic::{Error, Sources};
+use language::error::fix::{self, Applicability, Fix};
+use language::parser::lexer::Lexer;
+
+fn replace(range: std::ops::Range<usize>, replacement: &str) -> Fix {
+    Fix::new(String::from("fix it"), 0, range, replacement, Applicability::MachineApplicable)
+}
+
+fn lex(source: &str) -> Vec<Error> {
+    let mut files = Sources::new();
+    let file = files.add(String::from("test.language"), String::from(source));
+    let mut lexer = Lexer::from_text(source, file);
+    lexer.tokenise();
+    lexer.error.errors
+}
+
+#[test]
+fn unterminated_strings_are_closed_where_their_line_ends() {
+    let source = "let s = \"abc\nlet t = 2\n";
+    let errors = lex(source);
+    let fix = &errors[0].fixes[0];
+    assert_eq!(fix.applicability, Applicability::MaybeIncorrect);
+    assert_eq!(fix::apply(source, [fix]).0, "let s = \"abc\"\nlet t = 2\n");
+
+    let source = "let s = \"héllo";
+    assert_eq!(fix::apply(source, &lex(source)[0].fixes).0, "let s = \"héllo\"");
+}
+
+#[test]
+fn fixes_are_made_in_order_of_where_they_are() {
+    let fixes = [replace(7..8, "!"), replace(0..3, "var"), replace(4..4, "mut ")];
+    assert_eq!(fix::apply("let x = 1", &fixes), (String::from("var mut x =!1"), vec![0, 1, 2]));
+    assert_eq!(fix::apply("let x = 1", &[]), (String::from("let x = 1"), vec![]));
+}
+
+#[test]
+fn overlapping_fixes_are_left_out_of_the_count() {
+    let source = "let value = 1";
+    let fixes = [replace(4..9, "amount"), replace(6..9, "x"), replace(10..11, ":="), replace(4..4, "mut ")];
+    let (fixed, applied) = fix::apply(source, &fixes);
+    // The insertion sorts first, so it goes in and the rename still fits after it.
+    assert_eq!(fixed, "let mut amount := 1");
+    assert_eq!(applied, [0, 2, 3]);
+
+    // Fixes that reach past the end of the source are skipped too.
+    assert_eq!(fix::apply(source, &[replace(12..20, "2")]).1, Vec::<usize>::new());
+}
@@ -0,0 +1,138 @@
+mod common;
+
+use language::analysis::fold::Folder;
+use language::analysis::resolver::Resolver;
+use language::error::diagnostic::{Error, ErrorForm, Severity};
+use language::interpreter::interpret::Interpreter;
+
+use common::{parse, parse_file};
+
+// Folds a program, checking it still gives the same value, and returns the
+// folded code with the warnings.
+fn fold(source: &str) -> (String, Vec<Error>) {
+    let expected = Interpreter::new().interpret(&parse(source)).map(|value| value.to_string());
+
+    let mut code = parse(source);
+    let mut folder = Folder::new();
+    folder.fold(&mut code);
+    let folded = Interpreter::new().interpret(&code).map(|value| value.to_string());
+    match (&expected, &folded) {
+        (Ok(a), Ok(b)) => assert_eq!(a, b, "folding changed the result"),
+        (Err(a), Err(b)) => assert_eq!(a.message, b.message, "folding changed the error"),
+        _ => panic!("folding turned {:?} into {:?}", expected, folded)
+    }
+
+    let printed = code.iter().map(|ast| ast.to_string()).collect::<Vec<_>>().join("\n");
+    (printed, folder.error.errors)
+}
+
+fn folded(source: &str) -> String {
+    let (code, warnings) = fold(source);
+    assert!(warnings.is_empty(), "unexpected warnings: {:?}", warnings);
+    code
+}
+
+#[test]
+fn literal_operations_are_worked_out() {
+    assert_eq!(folded("45 + 222 * 2 - -3"), "492");
+    assert_eq!(folded("(7 % 3, 2 ^ 70, 7 / 2, 0.5d * 2, 1 + 2.5)"), "(1, 1180591620717411303424, 3.5, 1.0d, 3.5)");
+    assert_eq!(folded("(~0 & 6, 5 | 2, 6 @ 3, 1 << 70, -8 >> 1, true & false)"), "(6, 7, 5, 1180591620717411303424, -4, false)");
+    assert_eq!(folded("(1 < 2 <= 2, 3 > 4, 1 == 1.0, \"a\" != \"b\", not 0)"), "(true, false, true, true, true)");
+    assert_eq!(folded("\"ab\" + \"cd\""), "\"abcd\"");
+}
+
+#[test]
+fn folding_reaches_inside_functions_and_blocks() {
+    let source = "
+fn f(a) {
+    let b = a + 2 * 3
+    {
+        b = b * (4 - 3)
+    }
+    match b { 7 => 10 * 10, _ => 0 }
+}
+f(1)";
+    let code = folded(source);
+    assert!(code.contains("Let[b, (a + 6)]"), "{}", code);
+    assert!(code.contains("Assignment[b, (b * 1)]"), "{}", code);
+    assert!(code.contains("7 => (100)"), "{}", code);
+}
+
+#[test]
+fn short_circuits_with_a_literal_left_operand_are_decided() {
+    assert_eq!(folded("let x = 3\n(true and x, 0 and x, false or x, 1 or x)"), "Let[x, 3]\n(x, 0, x, 1)");
+}
+
+#[test]
+fn identities_only_apply_when_the_operand_is_known() {
+    let source = "const n = 5\nlet x = 3\n(n + 0, 0 + n, n - 0, n - n, (n * 2) * 1, 1 * -x, x - 0, x - x, x * 1)";
+    assert_eq!(folded(source), "Const[n, 5]\nLet[x, 3]\n(n, n, n, 0, (n * 2), (- x), (x - 0), (x - x), (x * 1))");
+
+    // A string or float operand would change the result, or the error.
+    assert_eq!(folded("let s = \"a\"\nlet f = -0.0\n(f + 0, f * 1)"), "Let[s, \"a\"]\nLet[f, -0.0]\n((f + 0), (f * 1))");
+
+    // A constant is only known where nothing shadows it.
+    let source = "const n = 5\nfn f(n) => n - n\nf(1.5)";
+    assert!(folded(source).contains("(n - n)"));
+}
+
+#[test]
+fn failures_are_warned_about_and_left_for_runtime() {
+    let (code, warnings) = fold("fn f() => (1 / 0, 2 ^ 100000000000, 1 << -1, 5 % 0)\n0");
+    assert!(code.contains("((1 / 0), (2 ^ 100000000000), (1 << -1), (5 % 0))"), "{}", code);
+    let messages = warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>();
+    assert_eq!(messages, [
+        "I can't divide by zero.",
+        "The result of this '^' would take more than 1048576 bits, which is too big to work out.",
+        "I can't shift by a negative amount (-1).",
+        "I can't divide by zero."
+    ]);
+    for warning in &warnings {
+        assert!(matches!(warning.kind, ErrorForm::ConstantEvaluation));
+        assert_eq!(warning.severity, Severity::Warning);
+    }
+    assert_eq!(warnings[0].labels[0].range, 11..16);
+}
+
+#[test]
+fn infinite_floats_are_folded_with_a_warning() {
+    let (code, warnings) = fold("10.0 ^ 400");
+    assert_eq!(code, "inf");
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].message, "The result of this '^' is too big for a float, so it becomes infinity.");
+}
+
+#[test]
+fn type_errors_are_left_for_runtime() {
+    let (code, warnings) = fold("fn f() => 1 + \"a\"\n0");
+    assert!(code.contains("(1 + \"a\")"));
+    assert!(warnings.is_empty());
+}
+
+#[test]
+fn exact_division_is_left_for_runtime() {
+    let mut code = parse("(1 / 3, 1.5 / 3)");
+    let mut folder = Folder::new();
+    folder.exact_division = true;
+    folder.fold(&mut code);
+    assert_eq!(code[0].to_string(), "((1 / 3), 0.5)");
+}
+
+#[test]
+fn results_that_grow_are_left_for_runtime() {
+    let code = folded("fn f() => (3 ^ 4000, 1.5d ^ 10000, 1 << 5000, 2 ^ 10)\n0");
+    assert!(code.contains("((3 ^ 4000), (1.5d ^ 10000), (1 << 5000), 1024)"), "{}", code);
+    // Too big to work out at all fails straight away, every time it runs.
+    let (_, warnings) = fold("fn f() => (3 ^ 4000000000, 1.5d ^ 100000000000)\n0");
+    assert_eq!(warnings.len(), 2);
+}
+
+#[test]
+fn constants_from_other_files_are_not_folded() {
+    let mut resolver = Resolver::new();
+    let mut folder = Folder::new();
+    folder.fold(&mut parse_file("const n = 5", 0, &mut resolver));
+    let mut code = parse_file("n + 0", 1, &mut resolver);
+    folder.fold(&mut code);
+    assert_eq!(code[0].to_string(), "(n + 0)");
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use language::analysis::resolver::Resolver;
+use language::error::diagnostic::{ErrorForm, Errors, LintLevels, Severity};
+
+// A shadowed variable, which is a warning, and an undeclared one, which isn't.
+const SOURCE: &str = "let x = 1\n{\n    let x = 2\n}\ny";
+
+fn apply(levels: &LintLevels) -> Errors {
+    let (_, mut found) = common::read(SOURCE, 0, &mut Resolver::new());
+    let mut errors = Errors::new();
+    errors.extend(&mut found);
+    errors.apply(levels);
+    errors
+}
+
+// The diagnostics for `SOURCE` at the given levels, by code and severity.
+fn diagnostics(levels: &LintLevels) -> Vec<(&'static str, Severity)> {
+    apply(levels).iter().map(|error| (error.kind.code(), error.severity)).collect()
+}
+
+#[test]
+fn warnings_are_kept_by_default() {
+    assert_eq!(diagnostics(&LintLevels::new()), [("E0005", Severity::Warning), ("E0003", Severity::Error)]);
+}
+
+#[test]
+fn allowed_warnings_are_dropped_but_errors_are_not() {
+    for code in ["E0005", "e0005", " E0005 "] {
+        let mut levels = LintLevels::new();
+        levels.allow(code);
+        levels.allow("E0003");
+        assert_eq!(diagnostics(&levels), [("E0003", Severity::Error)], "allowing `{}`", code);
+    }
+}
+
+#[test]
+fn denied_warnings_become_errors() {
+    for code in ["E0005", "e0005"] {
+        let mut levels = LintLevels::new();
+        levels.deny(code);
+        assert_eq!(diagnostics(&levels), [("E0005", Severity::Error), ("E0003", Severity::Error)], "denying `{}`", code);
+    }
+
+    let mut levels = LintLevels::new();
+    levels.deny_warnings = true;
+    let errors = apply(&levels);
+    assert_eq!(errors.error_count(), 2);
+    assert_eq!(errors.iter().next().unwrap().notes.last().unwrap(), "`--deny warnings` turns every warning into an error.");
+}
+
+#[test]
+fn codes_are_explained_however_they_are_written() {
+    let code = |code| ErrorForm::from_code(code).map(|form| form.code());
+    assert_eq!(code("e0005"), Some("E0005"));
+    assert_eq!(code(" E0005"), Some("E0005"));
+    assert_eq!(code("E9999"), None);
+}
@@ -0,0 +1,51 @@
+use language::error::diagnostic::ErrorForm;
+use language::error::messages::{self, Catalogue};
+use language::message;
+
+#[test]
+fn parameters_are_filled_in() {
+    assert_eq!(
+        message!("E0003-undeclared-variable", name = "total"),
+        "I couldn't find a variable called `total`."
+    );
+}
+
+#[test]
+fn doubled_braces_are_literal() {
+    assert_eq!(
+        message!("E0001-function-body.note"),
+        "Write either `fn(x) => x * 2` or `fn(x) { ... }`."
+    );
+}
+
+#[test]
+fn installed_catalogue_falls_back_to_english() {
+    let catalogue = Catalogue::parse("# French\nE0004-constant-reassignment = Je ne peux pas changer `{name}`.\n").unwrap();
+    messages::install(catalogue);
+
+    assert_eq!(message!("E0004-constant-reassignment", name = "limit"), "Je ne peux pas changer `limit`.");
+    assert_eq!(message!("E0007-division-by-zero"), "I can't divide by zero.");
+}
+
+#[test]
+fn catalogue_lines_need_an_equals_sign() {
+    assert!(Catalogue::parse("E0001-lone-bang I expected a '='").is_err());
+}
+
+#[test]
+fn every_code_has_an_explanation() {
+    for form in ErrorForm::ALL.iter() {
+        assert!(matches!(ErrorForm::from_code(form.code()), Some(ref found) if found.code() == form.code()));
+        assert!(!form.explanation().trim().is_empty());
+    }
+    assert!(ErrorForm::from_code("E9999").is_none());
+}
+
+#[test]
+fn english_messages_mention_their_code() {
+    for key in messages::english().keys() {
+        let prefix = key.split(['-', '.']).next().unwrap();
+        let known = ErrorForm::ALL.iter().any(|form| form.code() == prefix);
+        assert!(known || ["context", "help", "lint"].contains(&prefix), "`{}` isn't keyed by an error code", key);
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use language::error::diagnostic::Error;
+use language::interpreter::interpret::Interpreter;
+
+use common::parse;
+
+fn run_in(mut interpreter: Interpreter, source: &str) -> Result<String, Error> {
+    interpreter.interpret(&parse(source)).map(|value| value.to_string())
+}
+
+fn run(source: &str) -> Result<String, Error> {
+    run_in(Interpreter::new(), source)
+}
+
+fn eval(source: &str) -> String {
+    run(source).expect("program should run")
+}
+
+const HUNDRED_DIGITS: &str = "1234567890123456789012345678901234567890123456789012345678901234567890123456789012345678901234567890";
+
+#[test]
+fn long_literals_are_read_exactly() {
+    assert_eq!(eval(HUNDRED_DIGITS), HUNDRED_DIGITS);
+    assert_eq!(eval(&format!("-{}", HUNDRED_DIGITS)), format!("-{}", HUNDRED_DIGITS));
+}
+
+#[test]
+fn overflowing_operations_promote() {
+    assert_eq!(eval("9223372036854775807 + 1"), "9223372036854775808");
+    assert_eq!(eval("-9223372036854775807 - 2"), "-9223372036854775809");
+    assert_eq!(eval("4294967296 * 4294967296"), "18446744073709551616");
+    assert_eq!(eval("2 ^ 100"), "1267650600228229401496703205376");
+    assert_eq!(eval("1 << 64"), "18446744073709551616");
+    assert_eq!(eval("-(-9223372036854775807 - 1)"), "9223372036854775808");
+}
+
+#[test]
+fn results_that_fit_demote() {
+    assert_eq!(eval("(2 ^ 64 - 2 ^ 64 + 5) + 9223372036854775802"), "9223372036854775807");
+    assert_eq!(eval("(1 << 100) >> 98"), "4");
+    assert_eq!(eval(&format!("{} % 1000", HUNDRED_DIGITS)), "890");
+}
+
+#[test]
+fn big_and_small_integers_compare() {
+    assert_eq!(eval("(2 ^ 64 > 9223372036854775807, 2 ^ 64 == 2 ^ 32 * 2 ^ 32, -(2 ^ 64) < 0)"), "(true, true, true)");
+    assert_eq!(eval("(2 ^ 64 == 18446744073709551616.0, 2 ^ 64 + 1 == 18446744073709551616.0)"), "(true, false)");
+}
+
+#[test]
+fn big_integers_are_map_keys() {
+    assert_eq!(eval("let m = {2 ^ 64: \"big\"}\n(m[18446744073709551616], m[18446744073709551616.0])"), "(\"big\", \"big\")");
+}
+
+#[test]
+fn big_indexes_are_out_of_range() {
+    let error = run("[1, 2][2 ^ 70]").unwrap_err();
+    assert_eq!(error.message, "The index 1180591620717411303424 is out of range for a sequence of length 2.");
+}
+
+#[test]
+fn decimals_are_exact() {
+    assert_eq!(eval("(0.1 + 0.2 == 0.3, 0.1d + 0.2d == 0.3d)"), "(false, true)");
+    assert_eq!(eval("(1.10d, 1.10d * 3, 19.99d - 20)"), "(1.10d, 3.30d, -0.01d)");
+}
+
+#[test]
+fn decimal_division_stays_exact() {
+    assert_eq!(eval("(1d / 8, 10d / 4, 1d / 3, 2d ^ -1)"), "(0.125d, 2.5d, 1/3, 0.5d)");
+}
+
+#[test]
+fn mixed_numbers_take_the_more_general_kind() {
+    assert_eq!(eval("(1 + 0.5d, 0.5d + rational(1, 3), rational(1, 3) + 0.5, 2 ^ 64 + 0.5d)"), "(1.5d, 5/6, 0.8333333333333333, 18446744073709551616.5d)");
+}
+
+#[test]
+fn rationals_are_reduced() {
+    assert_eq!(eval("(rational(2, 4), rational(6, 3), rational(1, 3) * 3, rational(-7, 2) % 2)"), "(1/2, 2, 1, 1/2)");
+}
+
+#[test]
+fn integer_division_is_exact_when_asked_for() {
+    assert_eq!(eval("(1 / 4, 6 / 3)"), "(0.25, 2.0)");
+
+    let mut interpreter = Interpreter::new();
+    interpreter.exact_division = true;
+    assert_eq!(run_in(interpreter, "(1 / 4, 6 / 3, 1 / 4.0)").unwrap(), "(1/4, 2, 0.25)");
+}
+
+#[test]
+fn numbers_of_every_kind_are_equal_and_hash_alike() {
+    assert_eq!(eval("(1 == 1.0d, 0.5d == rational(1, 2), rational(1, 2) == 0.5, 0.1d == 0.1)"), "(true, true, true, false)");
+    assert_eq!(eval("let m = {0.5: \"half\"}\n(m[0.50d], m[rational(1, 2)])"), "(\"half\", \"half\")");
+    assert_eq!(eval("(1.5d < rational(8, 5), rational(1, 3) > 0.3)"), "(true, true)");
+}
+
+#[test]
+fn conversions_between_kinds() {
+    assert_eq!(eval("(decimal(0.1), rational(0.1), decimal(rational(3, 4)), float(rational(1, 4)))"), "(0.1d, 1/10, 0.75d, 0.25)");
+    assert_eq!(eval("(int(-2.7d), int(rational(7, 2)), int(2.9), int(\"42\"), decimal(\"1.50\"), rational(\"3/9\"))"), "(-2, 3, 2, 42, 1.50d, 1/3)");
+}
+
+#[test]
+fn inexact_conversions_are_errors() {
+    assert_eq!(run("decimal(rational(1, 3))").unwrap_err().message, "1/3 has no exact `decimal` value.");
+    assert_eq!(run("rational([1])").unwrap_err().message, "I can't convert a `list` to `rational`.");
+    assert_eq!(run("rational(1, 2, 3)").unwrap_err().message, "This function takes 1 to 2 arguments, but I was given 3.");
+}
+
+#[test]
+fn results_too_big_to_work_out_are_errors() {
+    let message = |operator: &str| format!("The result of this '{}' would take more than 1048576 bits, which is too big to work out.", operator);
+    for source in &["3 ^ 4000000000", "2.5d ^ 100000000000", "0.1d ^ 1000000", "rational(3, 2) ^ 1000000000", "1.5d ^ -1000000000"] {
+        assert_eq!(run(source).unwrap_err().message, message("^"), "{}", source);
+    }
+    assert_eq!(run("1 << 4000000000").unwrap_err().message, message("<<"));
+    assert_eq!(eval("((-1) ^ 100000000001, 1 ^ 100000000000, 0 << 100000000000, 2 ^ 1000 > 0)"), "(-1, 1, 0, true)");
+}
@@ -0,0 +1,309 @@
+mod common;
+
+use language::analysis::checker::Checker;
+use language::error::diagnostic::Error;
+use language::parser::ast::AST;
+
+use common::{both, parse, spans};
+
+fn check(source: &str) -> (Checker, Vec<Error>) {
+    let (checker, _, errors) = check_code(source);
+    (checker, errors)
+}
+
+fn check_code(source: &str) -> (Checker, Vec<AST>, Vec<Error>) {
+    let mut code = parse(source);
+    let mut checker = Checker::new();
+    checker.check(&mut code);
+    let errors = std::mem::take(&mut checker.error.errors);
+    (checker, code, errors)
+}
+
+// The types of the given globals, checking the program has no type errors.
+fn types(source: &str, names: &[&str]) -> Vec<String> {
+    let (checker, errors) = check(source);
+    assert!(errors.is_empty(), "unexpected type errors: {:?}", errors.iter().map(|e| &e.message).collect::<Vec<_>>());
+    names.iter().map(|name| checker.type_of(name).expect("the name should be declared")).collect()
+}
+
+fn messages(source: &str) -> Vec<String> {
+    check(source).1.into_iter().map(|error| error.message).collect()
+}
+
+// The program with the casts the checker added, checking it has no type errors.
+fn checked(source: &str) -> String {
+    let (_, code, errors) = check_code(source);
+    assert!(errors.is_empty(), "unexpected type errors: {:?}", errors.iter().map(|e| &e.message).collect::<Vec<_>>());
+    code.iter().map(|ast| ast.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+// Runs a checked program on both the interpreter and the VM, which have to
+// fail a cast the same way.
+fn run(source: &str) -> Result<String, Error> {
+    let (_, code, errors) = check_code(source);
+    assert!(errors.is_empty(), "unexpected type errors: {:?}", errors.iter().map(|e| &e.message).collect::<Vec<_>>());
+    both(&code)
+}
+
+#[test]
+fn literals_and_operators_are_inferred() {
+    let source = "const a = 1 + 2\nconst b = 1 + 2.5\nconst c = 7 / 2\nconst d = \"x\" + \"y\"\nconst e = (1, \"s\") + (true,)\nconst f = 1 < 2 and 0.5d > 0";
+    assert_eq!(types(source, &["a", "b", "c", "d", "e", "f"]), ["int", "float", "float", "str", "(int, str, bool)", "bool"]);
+}
+
+#[test]
+fn unannotated_code_is_dynamic() {
+    let source = "
+fn identity(x) => x
+fn add(a, b) => a + b
+let count = 1
+count = \"one\"
+const mixed = [1, \"two\", add(1, 2)]
+const pair = (identity(1), add(\"a\", 2), count)";
+    assert_eq!(
+        types(source, &["identity", "count", "mixed", "pair"]),
+        ["fn(dynamic) -> dynamic", "dynamic", "[dynamic]", "(dynamic, dynamic, dynamic)"]
+    );
+}
+
+#[test]
+fn annotations_pin_types_down() {
+    let source = "let x: float = 1\nfn shout(text: str) -> str => text + \"!\"\nlet pairs: [(int, str)] = []";
+    assert_eq!(types(source, &["x", "shout", "pairs"]), ["float", "fn(str) -> str", "[(int, str)]"]);
+}
+
+#[test]
+fn records_methods_and_variants_have_types() {
+    let source = "
+struct Point { x: int, y: int, fn sum(self) -> int => self.x + self.y }
+enum Shape { Circle(radius: float), Empty }
+const total = Point { x: 1, y: 2 }.sum()
+const shape = Shape.Circle(2.5)
+const radius = match shape { Shape.Circle(r) => r, Shape.Empty => 0.0 }";
+    assert_eq!(types(source, &["total", "shape", "radius"]), ["int", "Shape", "float"]);
+}
+
+#[test]
+fn mismatches_are_reported() {
+    assert_eq!(messages("1 + \"str\" + 34567.8"), ["I can't use '+' on a `int` and a `str`."]);
+    assert_eq!(messages("let x: int = \"one\""), ["I expected a `int` here, but this is a `str`."]);
+    assert_eq!(messages("fn f(a: str) -> int => a"), ["I expected a `int` here, but this is a `str`."]);
+    assert_eq!(messages("const xs = [1, 2]\nlet x: str = xs[0]"), ["I expected a `str` here, but this is a `int`."]);
+    assert_eq!(messages("let x: int = 1\nx = \"s\""), ["I expected a `int` here, but this is a `str`."]);
+    assert_eq!(messages("fn f(a, b) => a\nf(1)"), ["This function takes 2 arguments, but it's given 1."]);
+    assert_eq!(messages("let x: number = 1"), ["I don't know a type called `number`."]);
+}
+
+#[test]
+fn mismatches_are_labelled_at_both_spans() {
+    let (_, errors) = check("let x: int = \"one\"");
+    let spans = errors[0].labels.iter().map(|label| label.range.clone()).collect::<Vec<_>>();
+    assert_eq!(spans, [13..18, 7..10]);
+}
+
+#[test]
+fn dynamic_values_are_cast_where_they_meet_annotations() {
+    let code = checked("fn twice(n: int) -> int => n * 2\nlet x = 4\nlet y: float = x\ntwice(x)");
+    assert!(code.contains("(x as float)"), "{}", code);
+    assert!(code.contains("(x as int)"), "{}", code);
+    // Values whose types are already known don't need one.
+    assert!(!checked("fn twice(n: int) -> int => n * 2\ntwice(4)").contains(" as "));
+}
+
+#[test]
+fn casts_that_hold_change_nothing() {
+    assert_eq!(run("fn twice(n: float) -> float => n * 2\nlet x = 4\nlet xs: [int] = [x, 5]\n(twice(x), xs)").unwrap(), "(8, [4, 5])");
+}
+
+#[test]
+fn failed_casts_point_at_the_value_and_the_annotation() {
+    let source = "fn twice(n: int) -> int => n * 2\nlet x = \"four\"\ntwice(x)";
+    let error = run(source).unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [54..55, 12..15, 41..47]);
+
+    let error = run("let xs = [1, \"two\"]\nlet ints: [int] = xs").unwrap_err();
+    assert_eq!(error.message, "I expected a `[int]` here, but this `list` holds a `str` where a `int` should be.");
+}
+
+#[test]
+fn functions_are_checked_each_time_they_are_called() {
+    let apply = "fn apply(f: fn(int) -> int) -> int => f(1)\n";
+    assert_eq!(run(&format!("{}apply(fn(x) => x + 1)", apply)).unwrap(), "2");
+
+    let error = run(&format!("{}const r: int = apply(fn(x) => \"s\")\nr + 1", apply)).unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [38..42, 12..26, 64..76]);
+
+    // What it's given is checked too, once it's back in code without types.
+    let error = run("fn keep(f: fn(int) -> int) => f\nlet g = keep(fn(x) => x)\ng(\"s\")").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(run("fn keep(f: fn(int) -> int) => f\nlet g = fn(x) => x\nkeep(g) == g").unwrap(), "true");
+}
+
+#[test]
+fn typed_functions_are_checked_when_code_without_types_calls_them() {
+    let error = run("let f = fn(x: int) -> int => x\nf(\"a\")").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [31..37, 8..30]);
+
+    let error = run("fn f(x: int) -> int => x\nlet h = f\nh(\"a\")").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [35..41, 3..4]);
+
+    let error = run("let f = fn(x: int) -> int => x\nlet g = fn(y) => y\nf(g(\"a\"))").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [50..59, 8..30]);
+
+    // Passed to a parameter without a type, or given back from a function without one.
+    assert!(run("fn f(x: int) -> int => x\nfn apply(g, v) => g(v)\napply(f, \"a\")").is_err());
+    assert!(run("fn make() => fn(x: int) -> int => x\nmake()(\"a\")").is_err());
+    assert_eq!(run("let f = fn(x: int) -> int => x + 1\nf(1)").unwrap(), "2");
+}
+
+#[test]
+fn elements_are_checked_where_typed_code_reads_them() {
+    let error = run("let d = [1]\nlet xs: [int] = d\nd[0] = \"s\"\nxs[0] + 1").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(spans(&error), [41..46, 20..25]);
+
+    let error = run("let d = {\"a\": [1]}\nlet m: {str: [int]} = d\nd[\"a\"][0] = true\nm[\"a\"][0]").unwrap_err();
+    assert_eq!(error.message, "I expected a `int` here, but this is a `bool`.");
+    assert_eq!(run("let d = [1]\nlet xs: [int] = d\nd[0] = 2\nxs[0] + 1").unwrap(), "3");
+}
+
+#[test]
+fn long_chains_of_operators_are_checked_without_overflowing() {
+    let source = format!("let x: int = 1\nx{}", " + x".repeat(5000));
+    assert_eq!(types(&source, &["x"]), ["int"]);
+    assert_eq!(run(&source).unwrap(), "5001");
+}
+
+#[test]
+fn deeply_nested_code_is_read_without_overflowing() {
+    let depth = 5000;
+    let brackets = format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(run(&brackets).unwrap(), "1");
+    assert_eq!(run(&format!("{}1", "-".repeat(depth))).unwrap(), "1");
+    assert_eq!(run(&format!("{}true", "not ".repeat(depth + 1))).unwrap(), "false");
+
+    let lists = format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+    assert_eq!(run(&lists).unwrap(), lists);
+    let pattern = format!("let x = 1\nmatch x {{ {}y{} => y }}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(run(&pattern).unwrap(), "1");
+}
+
+#[test]
+fn declarations_are_typed_before_the_code_above_them() {
+    let source = "
+fn even(n: int) -> bool => n == 0 or odd(n - 1)
+fn odd(n: int) -> bool => n != 0 and even(n - 1)
+fn origin() -> Point => Point { at: Place.Here }
+struct Point { at: Place }
+enum Place { Here, There }
+const a = even(4)";
+    assert_eq!(types(source, &["even", "odd", "origin", "a"]), ["fn(int) -> bool", "fn(int) -> bool", "fn() -> Point", "bool"]);
+    assert_eq!(messages("fn f() => g(\"s\")\nfn g(x: int) => x"), ["I expected a `int` here, but this is a `str`."]);
+}
+
+#[test]
+fn generic_functions_are_instantiated_at_each_use() {
+    let source = "
+fn identity<T>(x: T) -> T => x
+fn largest<T: ordered>(xs: [T]) -> T => match xs {
+    [x] => x,
+    [x, ..rest] => {
+        const best = largest(rest)
+        x > best and x or best
+    },
+    _ => xs[0]
+}
+fn sum<T: number>(a: T, b: T) -> T => a + b * 2
+const a = identity(1)
+const b = identity(\"s\")
+const c = largest([1.5, 2.5])
+const d = sum(1, 2)";
+    assert_eq!(
+        types(source, &["identity", "largest", "a", "b", "c", "d"]),
+        ["fn(a) -> a", "fn([a]) -> a", "int", "str", "float", "int"]
+    );
+}
+
+#[test]
+fn constants_bound_to_values_stay_generic() {
+    let source = "fn identity<T>(x: T) -> T => x\nconst f = identity\nconst pair = (identity, 1)\nconst a = f(1)\nconst b = f(\"s\")";
+    assert_eq!(types(source, &["f", "pair", "a", "b"]), ["fn(a) -> a", "(fn(a) -> a, int)", "int", "str"]);
+    assert_eq!(run("fn identity<T>(x: T) -> T => x\nconst f = identity\n(f(1), f(\"s\"))").unwrap(), "(1, \"s\")");
+
+    // A call could give back the same instance every time, so it stays as it is.
+    let (_, errors) = check("fn identity<T>(x: T) -> T => x\nconst f = identity(identity)\nf(1)\nf(\"s\")");
+    assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn unsatisfied_bounds_show_the_substituted_types() {
+    let (_, errors) = check("struct Point { x: int }\nfn largest<T: ordered>(xs: [T]) -> T => xs[0]\nlargest([Point { x: 1 }])");
+    assert_eq!(errors[0].message, "`Point` doesn't satisfy `ordered`, so this can't be a `fn([Point]) -> Point`.");
+    assert_eq!(spans(&errors[0]), [70..77, 35..36]);
+
+    assert_eq!(messages("fn twice<T>(x: T) -> T => x + x"), ["I can't use '+' on a `T` and a `T`."]);
+    assert_eq!(messages("fn f<T: number, U: number>(a: T, b: U) => a + b"), ["I can't use '+' on a `T` and a `U`."]);
+    assert_eq!(messages("fn f<T: sortable>(a: T) => a"), ["I don't know a bound called `sortable`."]);
+}
+
+#[test]
+fn generic_calls_say_what_their_type_parameters_stood_for() {
+    let (_, errors) = check("fn pair<T>(a: T, b: T) -> (T, T) => (a, b)\npair(1, \"two\")");
+    assert_eq!(errors[0].message, "I expected a `int` here, but this is a `str`.");
+    assert_eq!(errors[0].notes, ["Here `pair` is a `fn(int, int) -> (int, int)`."]);
+}
+
+#[test]
+fn generic_structs_and_enums_take_type_arguments() {
+    let source = "
+struct Pair<A, B> {
+    first: A, second: B
+    fn swap(self) -> Pair<B, A> => Pair { first: self.second, second: self.first }
+}
+enum Option<T> { Some(value: T), None }
+const pair = Pair { first: 1, second: \"one\" }
+const swapped = pair.swap()
+const second = swapped.second
+const maybe = Option.Some([1.5])
+const inner = match maybe { Option.Some(v) => v, Option.None => [] }
+let nested: Option<Option<int>> = Option.None
+let annotated: Pair<int, str> = pair";
+    assert_eq!(
+        types(source, &["pair", "swapped", "second", "maybe", "inner", "nested", "annotated"]),
+        ["Pair<int, str>", "Pair<str, int>", "int", "Option<[float]>", "[float]", "Option<Option<int>>", "Pair<int, str>"]
+    );
+
+    assert_eq!(
+        messages("struct Pair<A, B> { first: A, second: B }\nlet p: Pair<int> = Pair { first: 1, second: 2 }"),
+        ["`Pair` takes 2 type arguments, but it's given 1."]
+    );
+    assert_eq!(
+        messages("struct Box<T: number> { value: T }\nconst b = Box { value: \"s\" }"),
+        ["`str` doesn't satisfy `number`, so this can't be a `Box<str>`."]
+    );
+    assert_eq!(
+        messages("enum Option<T> { Some(value: T), None }\nlet x: Option<int> = Option.Some(\"s\")"),
+        ["I expected a `Option<int>` here, but this is a `Option<str>`."]
+    );
+}
+
+#[test]
+fn generic_programs_run_the_same_way() {
+    let source = "
+struct Stack<T> {
+    items: [T]
+    fn push(self, item: T) => Stack { items: [..self.items, item] }
+}
+fn map<T, U>(xs: [T], f: fn(T) -> U) -> [U] => match xs {
+    [] => [],
+    [first, ..rest] => [f(first), ..map(rest, f)]
+}
+let stack: Stack<int> = Stack { items: [] }.push(1).push(2)
+map(stack.items, fn(x: int) -> (int, int) => (x, x * 10))";
+    assert_eq!(run(source).unwrap(), "[(1, 10), (2, 20)]");
+}
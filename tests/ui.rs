@@ -0,0 +1,263 @@
+// Runs the lexer, parser, resolver, checker and folder over every
+// `tests/ui/*.language` file and compares the diagnostics with the `.stderr`
+// file beside it, and their JSON with the `.json` file. One fixture is kept
+// as SARIF and with colour as well. Run `cargo test --test ui -- --bless` to
+// rewrite the snapshots instead.
+//
+// Annotations in comments say where each diagnostic should land:
+//
+//     let y = totel    #~ ERROR couldn't find a variable
+//     #~^ WARNING shadows
+//
+// `#~` means this line and each `^` moves one line up. The text after the
+// severity has to appear in the message. Every error and warning needs an
+// annotation, and every annotation needs a diagnostic.
+//
+// The files in a directory named after a fixture, like `tests/ui/imports/`
+// for `imports.language`, are checked before it in order, sharing what they
+// declare like files given to `main` together. They can be annotated too.
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use codespan_reporting::files::Files;
+use codespan_reporting::term::termcolor::{Ansi, NoColor};
+
+use language::analysis::checker::Checker;
+use language::analysis::fold::Folder;
+use language::analysis::resolver::Resolver;
+use language::error::diagnostic::{Errors, Severity, Sources};
+use language::error::emitter::{Colour, Emitter};
+use language::error::export;
+
+struct Annotation {
+    file: usize,
+    line: usize,
+    severity: Severity,
+    text: String
+}
+
+fn main() {
+    let bless = env::args().any(|arg| arg == "--bless") || env::var_os("BLESS").is_some();
+    let filter = env::args().skip(1).find(|arg| !arg.starts_with('-'));
+
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("ui");
+    let mut fixtures = fs::read_dir(&directory).expect("tests/ui should exist")
+        .map(|entry| entry.expect("tests/ui should be readable").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "language"))
+        .filter(|path| filter.as_ref().is_none_or(|filter| path.to_string_lossy().contains(filter.as_str())))
+        .collect::<Vec<_>>();
+    fixtures.sort();
+
+    println!("\nrunning {} ui tests", fixtures.len() + 1);
+    let mut failures = Vec::new();
+    for fixture in &fixtures {
+        let problems = run(fixture, bless);
+        let name = fixture.file_name().unwrap().to_string_lossy().into_owned();
+        if problems.is_empty() {
+            println!("test {} ... ok", name);
+        } else {
+            println!("test {} ... FAILED", name);
+            failures.push((name, problems));
+        }
+    }
+
+    let problems = formats(bless);
+    if problems.is_empty() {
+        println!("test formats ... ok");
+    } else {
+        println!("test formats ... FAILED");
+        failures.push((String::from("formats"), problems));
+    }
+
+    for (name, problems) in &failures {
+        println!("\n---- {} ----", name);
+        for problem in problems {
+            println!("{}", problem);
+        }
+    }
+
+    let result = if failures.is_empty() { "ok" } else { "FAILED" };
+    println!("\ntest result: {}. {} passed; {} failed\n", result, fixtures.len() + 1 - failures.len(), failures.len());
+    if !failures.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn run(fixture: &Path, bless: bool) -> Vec<String> {
+    let (errors, files, count) = check_fixture(fixture);
+    let mut problems = compare_annotations(&errors, &files, count);
+
+    let emitter = Emitter { colour: Colour::Never, ..Emitter::new() };
+    let mut output = NoColor::new(Vec::new());
+    emitter.emit_to(&mut output, &errors, &files).expect("diagnostics should render");
+    problems.extend(snapshot(&fixture.with_extension("stderr"), &rendered(output.into_inner()), bless));
+
+    let json = export::json_lines(&errors, &files).expect("diagnostics should export");
+    problems.extend(snapshot(&fixture.with_extension("json"), &json, bless));
+    problems
+}
+
+// The output for people and tools are kept for one fixture in the other
+// formats too, and the colour setting is checked against what it's for.
+fn formats(bless: bool) -> Vec<String> {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("ui").join("declarations.language");
+    let (errors, files, _) = check_fixture(&fixture);
+    let mut problems = Vec::new();
+
+    let sarif = export::sarif(&errors, &files).expect("diagnostics should export") + "\n";
+    problems.extend(snapshot(&fixture.with_extension("sarif"), &sarif, bless));
+
+    let emitter = Emitter { colour: Colour::Always, ..Emitter::new() };
+    let mut output = Ansi::new(Vec::new());
+    emitter.emit_to(&mut output, &errors, &files).expect("diagnostics should render");
+    problems.extend(snapshot(&fixture.with_extension("ansi"), &rendered(output.into_inner()), bless));
+
+    // (setting, `NO_COLOR` is set, writing to a terminal, coloured)
+    let settings = [
+        (Colour::Auto, false, true, true),
+        (Colour::Auto, true, true, false),
+        (Colour::Auto, false, false, false),
+        (Colour::Always, true, false, true),
+        (Colour::Never, false, true, false)
+    ];
+    for (colour, no_colour, terminal, expected) in settings {
+        if colour.enabled(no_colour, terminal) != expected {
+            problems.push(format!(
+                "{:?} with NO_COLOR {} and a terminal {} should {}colour",
+                colour, if no_colour { "set" } else { "unset" }, if terminal { "attached" } else { "missing" },
+                if expected { "" } else { "not " }
+            ));
+        }
+    }
+    problems
+}
+
+// The diagnostics for a fixture and the files before it, with the sources
+// and how many files there are.
+fn check_fixture(fixture: &Path) -> (Errors, Sources, usize) {
+    let mut paths = fs::read_dir(fixture.with_extension(""))
+        .map(|entries| entries.map(|entry| entry.expect("fixture directories should be readable").path()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    paths.sort();
+    paths.push(fixture.to_path_buf());
+
+    let directory = fixture.parent().expect("fixtures are in tests/ui");
+    let mut files = Sources::new();
+    for path in &paths {
+        let source = fs::read_to_string(path).expect("fixture should be readable");
+        let name = path.strip_prefix(directory).expect("fixtures are in tests/ui").to_string_lossy().replace('\\', "/");
+        files.add(format!("tests/ui/{}", name), source);
+    }
+    (check(&files, paths.len()), files, paths.len())
+}
+
+// Trailing spaces are dropped so editors that strip them don't break the snapshots.
+fn rendered(output: Vec<u8>) -> String {
+    String::from_utf8(output).expect("diagnostics should be UTF-8")
+        .lines().map(|line| format!("{}\n", line.trim_end())).collect()
+}
+
+// Compares output with the snapshot at `path`, or with `--bless` writes it
+// there, and removes the snapshot when there's nothing to write.
+fn snapshot(path: &Path, actual: &str, bless: bool) -> Option<String> {
+    if bless {
+        if actual.is_empty() {
+            let _ = fs::remove_file(path);
+        } else {
+            fs::write(path, actual).expect("snapshot should be writable");
+        }
+        return None;
+    }
+
+    let expected = fs::read_to_string(path).unwrap_or_default();
+    if expected == actual {
+        return None;
+    }
+    Some(format!(
+        "the output differs from {}, run with `--bless` if that's expected\n\
+         --- expected\n{}\n--- actual\n{}",
+        path.display(), expected, actual
+    ))
+}
+
+fn check(files: &Sources, count: usize) -> Errors {
+    let mut resolver = Resolver::new();
+    let mut checker = Checker::new();
+    let mut folder = Folder::new();
+    let mut errors = Errors::new();
+    for file in 0..count {
+        let source = files.get(file).expect("the file was added").source();
+        let (mut code, mut found) = common::read(source, file, &mut resolver);
+        checker.check(&mut code);
+        folder.fold(&mut code);
+        errors.extend(&mut found);
+        errors.extend(&mut checker.error.errors);
+        errors.extend(&mut folder.error.errors);
+    }
+    errors
+}
+
+fn annotations(source: &str, file: usize) -> Vec<Annotation> {
+    let mut annotations = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let comment = match line.find("#~") {
+            Some(start) => &line[start + 2..],
+            None => continue
+        };
+        let up = comment.chars().take_while(|c| *c == '^').count();
+        let mut words = comment[up..].trim().splitn(2, ' ');
+        let severity = match words.next() {
+            Some("ERROR") => Severity::Error,
+            Some("WARNING") => Severity::Warning,
+            other => panic!("line {}: unknown annotation {:?}", index + 1, other)
+        };
+        annotations.push(Annotation {
+            file,
+            line: index + 1 - up,
+            severity,
+            text: words.next().unwrap_or("").trim().to_string()
+        });
+    }
+    annotations
+}
+
+fn compare_annotations(errors: &Errors, files: &Sources, count: usize) -> Vec<String> {
+    let mut expected = (0..count)
+        .flat_map(|file| annotations(files.get(file).expect("the file was added").source(), file))
+        .collect::<Vec<_>>();
+    let mut problems = Vec::new();
+
+    for error in errors.iter().filter(|error| error.severity >= Severity::Warning) {
+        let (file, line) = error.labels.first()
+            .map(|label| (label.file_id, files.line_index(label.file_id, label.range.start).expect("label should be in the file") + 1))
+            .unwrap_or((0, 0));
+        let severity = error.severity.min(Severity::Error);
+
+        let found = expected.iter().position(|annotation| {
+            annotation.file == file && annotation.line == line && annotation.severity == severity && error.message.contains(&annotation.text)
+        });
+        match found {
+            Some(index) => {
+                expected.remove(index);
+            },
+            None => problems.push(format!("unexpected {} in {} on line {}: {}", error.severity, name(files, file), line, error.message))
+        }
+    }
+
+    for annotation in expected {
+        problems.push(format!(
+            "expected {} in {} on line {} containing {:?}, but there wasn't one",
+            annotation.severity, name(files, annotation.file), annotation.line, annotation.text
+        ));
+    }
+    problems
+}
+
+fn name(files: &Sources, file: usize) -> String {
+    files.get(file).map(|file| file.name().clone()).unwrap_or_default()
+}
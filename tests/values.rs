@@ -0,0 +1,84 @@
+mod common;
+
+use language::analysis::resolver::Resolver;
+use language::interpreter::interpret::Interpreter;
+
+use common::parse_file;
+
+fn run_with(interpreter: &mut Interpreter, resolver: &mut Resolver, source: &str) -> String {
+    match interpreter.interpret(&parse_file(source, 0, resolver)) {
+        Ok(value) => value.to_string(),
+        Err(error) => error.message
+    }
+}
+
+fn eval(source: &str) -> String {
+    run_with(&mut Interpreter::new(), &mut Resolver::new(), source)
+}
+
+#[test]
+fn values_display_as_literals() {
+    assert_eq!(eval("(1, 2.5, \"ab\", [1], (1,), {\"k\": [2]})"), "(1, 2.5, \"ab\", [1], (1,), {\"k\": [2]})");
+}
+
+#[test]
+fn maps_look_keys_up_by_equality() {
+    assert_eq!(eval("let m = {1: \"one\", (2, \"b\"): \"pair\"}\n(m[1.0], m[(2, \"b\")])"), "(\"one\", \"pair\")");
+}
+
+#[test]
+fn map_assignment_adds_and_replaces_keys() {
+    assert_eq!(eval("let m = {\"a\": 1}\nm[\"a\"] = 2\nm[\"b\"] = 3\nm"), "{\"a\": 2, \"b\": 3}");
+}
+
+#[test]
+fn mutable_values_are_not_keys() {
+    assert_eq!(eval("let m = {}\nm[[1]] = 1"), "I can't use a `list` as a map key.");
+}
+
+#[test]
+fn missing_keys_are_errors() {
+    assert_eq!(eval("let m = {\"a\": 1}\nm[\"b\"]"), "This map has no key \"b\".");
+}
+
+#[test]
+fn maps_are_equal_regardless_of_order() {
+    assert_eq!(eval("({1: 2, 3: 4} == {3: 4, 1: 2.0})"), "true");
+    assert_eq!(eval("({1: 2} == {1: 3})"), "false");
+}
+
+#[test]
+fn empty_containers_are_falsy() {
+    assert_eq!(eval("(not {}, not [], not (), not \"\", not 0, not {0: 0})"), "(true, true, true, true, true, false)");
+}
+
+#[test]
+fn self_containing_values_display_and_compare() {
+    assert_eq!(eval("let xs = [1]\nxs[0] = xs\n(xs, xs == xs)"), "([[...]], true)");
+    assert_eq!(eval("let m = {}\nm[\"me\"] = m\nm"), "{\"me\": {...}}");
+}
+
+#[test]
+fn unreachable_cycles_are_collected() {
+    let mut interpreter = Interpreter::new();
+    run_with(&mut interpreter, &mut Resolver::new(), "
+        fn cycle() {
+            let m = {}
+            m[\"self\"] = m
+            fn inner() => m
+            return 0
+        }
+        cycle()
+        cycle()");
+    assert!(interpreter.collect_garbage() >= 2);
+    assert_eq!(interpreter.collect_garbage(), 0);
+}
+
+#[test]
+fn reachable_cycles_are_kept() {
+    let mut interpreter = Interpreter::new();
+    let mut resolver = Resolver::new();
+    run_with(&mut interpreter, &mut resolver, "let m = {}\nm[\"self\"] = m");
+    assert_eq!(interpreter.collect_garbage(), 0);
+    assert_eq!(run_with(&mut interpreter, &mut resolver, "m[\"self\"][\"self\"]"), "{\"self\": {...}}");
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use language::analysis::resolver::Resolver;
+use language::compiler::compile::Compiler;
+use language::compiler::disassemble::disassemble;
+use language::compiler::vm::VM;
+use language::error::diagnostic::Error;
+
+use common::{compile, parse, parse_file};
+
+fn both(source: &str) -> Result<String, Error> {
+    common::both(&parse(source))
+}
+
+fn eval(source: &str) -> String {
+    both(source).expect("program should run")
+}
+
+#[test]
+fn expressions_and_globals() {
+    assert_eq!(eval("let x = 6\nlet y = 7\nx * y - 2 ^ 3 % 5"), "39");
+    assert_eq!(eval("let (a, b) = (1, 2)\na, b = b, a\n(a, b, a < b, 1 < a <= 2 < b)"), "(2, 1, false, false)");
+    assert_eq!(eval("(0 and 1, 0 or 2, not 0, 3 and 4, -(1), ~0)"), "(0, 2, true, 4, -1, -1)");
+    assert_eq!(eval("let [first, ..rest] = [1, 2, 3]\nlet (x, ..middle, y) = (4, 5, 6, 7)\n(first, rest, x, middle, y)"), "(1, [2, 3], 4, (5, 6), 7)");
+}
+
+#[test]
+fn sequences_maps_and_spreads() {
+    assert_eq!(eval("let xs = [1, 2]\nlet t = (0, ..xs, 3)\n[..t, ..xs]"), "[0, 1, 2, 3, 1, 2]");
+    assert_eq!(eval("let m = {\"a\": 1, 2: [3]}\nm[\"b\"] = m[2][0]\nm"), "{\"a\": 1, 2: [3], \"b\": 3}");
+    assert_eq!(eval("let xs = [1, 2, 3]\nxs[-1] = 9\n(xs, \"abc\"[1], (1, 2)[0])"), "([1, 2, 9], \"b\", 1)");
+    assert_eq!(eval("fn add(a, b, c) => a + b + c\nlet args = (2, 3)\nadd(1, ..args)"), "6");
+}
+
+#[test]
+fn blocks_scope_and_shadow() {
+    assert_eq!(eval("let x = 1\nlet y = 0\n{\n    let x = 2\n    y = x + 1\n}\n(x, y)"), "(1, 3)");
+    assert_eq!(eval("fn f(n) {\n    let a = n\n    {\n        let a = a * 2\n        n = a\n    }\n    (a, n)\n}\nf(4)"), "(4, 8)");
+}
+
+#[test]
+fn closures_capture_by_reference() {
+    let source = "
+fn counter() {
+    let count = 0
+    return fn() {
+        count = count + 1
+        count
+    }
+}
+let first = counter()
+let second = counter()
+first()
+first()
+second()
+[first(), second()]";
+    assert_eq!(eval(source), "[3, 2]");
+
+    let source = "
+fn pair() {
+    let value = 0
+    let set = fn(v) { value = v }
+    let get = fn() => value
+    return (set, get)
+}
+let (set, get) = pair()
+set(5)
+get()";
+    assert_eq!(eval(source), "5");
+
+    // Captured through a function that doesn't use the variable itself.
+    let source = "
+fn outer(x) {
+    fn middle() => fn() => x
+    x = x + 1
+    middle()()
+}
+outer(1)";
+    assert_eq!(eval(source), "2");
+}
+
+#[test]
+fn recursion_and_returns() {
+    assert_eq!(eval("fn fact(n) => n <= 1 and 1 or n * fact(n - 1)\nfact(25)"), "15511210043330985984000000");
+    let source = "
+fn collatz(n, steps) {
+    match n {
+        1 => { return steps },
+        _ if n % 2 == 0 => collatz(n / 2, steps + 1),
+        _ => collatz(3 * n + 1, steps + 1)
+    }
+}
+collatz(27, 0)";
+    assert_eq!(eval(source), "111");
+}
+
+#[test]
+fn structs_methods_and_enums() {
+    let source = "
+struct Point {
+    x, y
+    fn norm(self) => self.x * self.x + self.y * self.y
+    fn origin() => Point { x: 0, y: 0 }
+}
+enum Shape { Circle(radius), Square(side), Empty }
+let x = 3
+let p = Point { x, y: 4 }
+p.x = p.x + 1
+let shapes = [Shape.Circle(2), Shape.Square(3), Shape.Empty]
+let areas = [
+    match shapes[0] { Shape.Circle(r) => 3 * r * r, _ => 0 },
+    match shapes[1] { Shape.Circle(r) | Shape.Square(r) => r * r, _ => 0 },
+    match shapes[2] { Shape.Empty => 0, _ => 1 }
+]
+(p, p.norm(), Point.origin(), areas, shapes[0].radius)";
+    assert_eq!(eval(source), "(Point { x: 4, y: 4 }, 32, Point { x: 0, y: 0 }, [12, 9, 0], 2)");
+}
+
+#[test]
+fn match_patterns() {
+    let source = "
+fn describe(value) => match value {
+    0 => \"zero\",
+    1..=9 => \"digit\",
+    (a, b) if a == b => \"pair\",
+    (_, ..rest) => rest,
+    [first, .., last] => (first, last),
+    \"a\" | \"b\" => \"letter\",
+    _ => \"other\"
+}
+[describe(0), describe(5), describe((2, 2)), describe((1, 2, 3)), describe([1, 2, 3]), describe(\"b\"), describe(10)]";
+    assert_eq!(eval(source), "[\"zero\", \"digit\", \"pair\", (2, 3), (1, 3), \"letter\", \"other\"]");
+}
+
+#[test]
+fn numbers_and_builtins() {
+    assert_eq!(eval("(9223372036854775807 + 1, 0.1d + 0.2d, rational(1, 3) + 1, float(\"2.5\"), 7 / 2)"), "(9223372036854775808, 0.3d, 4/3, 2.5, 3.5)");
+
+    let code = parse("1 / 3");
+    let mut vm = VM::new();
+    vm.exact_division = true;
+    assert_eq!(vm.run(compile(&code)).unwrap().to_string(), "1/3");
+}
+
+#[test]
+fn errors_point_at_the_same_spans() {
+    let failures = [
+        "1 + \"a\"",
+        "fn f(a) {\n    a + \"s\"\n}\nf(1)",
+        "fn f(a, b) => a\nf(1)",
+        "let xs = [1]\nxs[3]",
+        "let m = {}\nm[[1]] = 2",
+        "struct P { x }\nP { x: 1, y: 2 }",
+        "struct P { x, y }\nP { x: 1 }",
+        "struct P { x }\nlet p = P { x: 1 }\np.z",
+        "enum E { A(x) }\nE.A(1, 2)",
+        "let (a, b) = (1, 2, 3)",
+        "let a = 1\na(2)",
+        "int(\"nope\")",
+        "fn down(n) => down(n + 1)\ndown(0)"
+    ];
+    for source in &failures {
+        assert!(both(source).is_err(), "`{}` should fail", source);
+    }
+}
+
+#[test]
+fn globals_persist_between_runs() {
+    let mut resolver = Resolver::new();
+    let mut vm = VM::new();
+    vm.run(compile(&parse_file("let total = 40\nfn add(n) { total = total + n }", 0, &mut resolver))).unwrap();
+    assert_eq!(vm.run(compile(&parse_file("add(2)\ntotal", 1, &mut resolver))).unwrap().to_string(), "42");
+}
+
+#[test]
+fn cycles_through_captured_variables_are_collected() {
+    let mut vm = VM::new();
+    vm.collect_garbage();
+    let source = "
+fn make() {
+    fn again() => again
+    again
+}
+make()
+0";
+    vm.run(compile(&parse(source))).unwrap();
+    assert!(vm.collect_garbage() >= 2);
+    assert_eq!(vm.collect_garbage(), 0);
+}
+
+#[test]
+fn bare_spreads_are_rejected() {
+    let code = parse("let x = [1]\n..x");
+    let mut compiler = Compiler::new();
+    compiler.compile(&code);
+    assert_eq!(compiler.error.errors[0].message, "A spread only makes sense inside a tuple or a list.");
+}
+
+#[test]
+fn sequences_longer_than_an_operand_can_count() {
+    let elements = vec!["1"; 70000].join(", ");
+    assert_eq!(eval(&format!("let xs = [{}]\nxs[69999] + xs[0]", elements)), "2");
+    assert_eq!(eval(&format!("let t = ({}, 2)\nt[70000]", elements)), "2");
+    let error = both(&format!("fn f(x) => x\nf({})", elements)).unwrap_err();
+    assert_eq!(error.message, "This function takes 1 argument, but I was given 70000.");
+}
+
+#[test]
+fn functions_too_big_for_the_vm_are_reported() {
+    let names = (0..70000).map(|i| format!("a{}", i)).collect::<Vec<_>>().join(", ");
+    let code = parse(&format!("fn f(xs) {{\n    let ({}) = xs\n    a0\n}}", names));
+    let mut compiler = Compiler::new();
+    compiler.compile(&code);
+    let error = &compiler.error.errors[0];
+    assert_eq!(error.kind.code(), "E0012");
+    assert_eq!(error.message, "This function needs more than 65535 variables, captured variables or names to unpack into, which is more than the VM can hold.");
+}
+
+#[test]
+fn disassembly_shows_slots_captures_and_lines() {
+    let listing = disassemble(&compile(&parse("let x = 1\nfn f(a) => fn(b) => a + b + x\nf(1)(2)")));
+    let expected = "\
+== <script>() ==
+locals 0, cells 0, captures []
+0000     1:9  Constant        0  ; 1
+0005       |  DefineGlobal    1  ; x";
+    assert!(listing.starts_with(expected), "{}", listing);
+    assert!(listing.contains("== fn f(a) ==\nlocals 1, cells 1, captures []\n"), "{}", listing);
+    assert!(listing.contains("== fn(b) ==\nlocals 1, cells 0, captures [cell 0]\n"), "{}", listing);
+    assert!(listing.contains("GetCapture      0"), "{}", listing);
+    assert!(listing.contains("GetGlobal       0  ; x"), "{}", listing);
+}